@@ -0,0 +1,876 @@
+use super::{
+	body_of_revolution::*, complex_number::*, cpu_electromagnetic_field_updater::*, cpu_subgrid::*,
+	electromagnetic_field_updater::*, electrostatic_solver::*, grid_spacing::*, harmonic_inversion::*, medium::*,
+	numerical_dispersion::*, subgrid::*, waveform::*,
+};
+use std::io::Write;
+
+// Comparison of simulation results with analytic results. All simulations use CPU field updater, so they don't
+// require OpenGL context and may be used in tests.
+
+pub struct ValidationResult
+{
+	pub name: String,
+	pub measured: f64,
+	pub expected: f64,
+	// Maximum allowed relative error.
+	pub tolerance: f64,
+}
+
+impl ValidationResult
+{
+	pub fn get_relative_error(&self) -> f64
+	{
+		(self.measured - self.expected).abs() / self.expected.abs().max(1.0e-30)
+	}
+
+	pub fn is_passed(&self) -> bool
+	{
+		self.get_relative_error() <= self.tolerance
+	}
+}
+
+pub fn run_all_validations() -> Vec<ValidationResult>
+{
+	let mut results = validate_plane_wave_propagation();
+	results.extend(validate_cavity_resonances());
+	results.extend(validate_graded_mesh_cavity_resonances());
+	results.extend(validate_subgrid_cavity_resonances());
+	results.extend(validate_fourth_order_subgrid_cavity_resonances());
+	results.extend(validate_body_of_revolution_cavity_resonances());
+	results.extend(validate_dipole_radiation());
+	results.extend(validate_dielectric_reflection());
+	results.extend(validate_reduced_simulation_modes());
+	results.extend(validate_point_charge_field());
+	results
+}
+
+pub fn write_validation_report(results: &[ValidationResult], file_path: &std::path::Path) -> std::io::Result<()>
+{
+	let mut file = std::io::BufWriter::new(std::fs::File::create(file_path)?);
+	writeln!(file, "name,measured,expected,relative_error,tolerance,passed")?;
+	for result in results
+	{
+		writeln!(
+			file,
+			"\"{}\",{},{},{},{},{}",
+			result.name,
+			result.measured,
+			result.expected,
+			result.get_relative_error(),
+			result.tolerance,
+			result.is_passed()
+		)?;
+	}
+	file.flush()
+}
+
+// Measure phase velocity of sinusoidal plane wave in periodic domain, along axis and along diagonal, for each spatial
+// stencil. It's compared with numerical dispersion relation of the scheme (see numerical_dispersion) and for well
+// resolved waves with speed of light. Fourth-order stencil resolves waves well with much fewer cells per wavelength.
+pub fn validate_plane_wave_propagation() -> Vec<ValidationResult>
+{
+	let time_step = 0.5;
+	let num_steps = 200;
+	let domain_size = 64;
+
+	let mut results = Vec::new();
+	for (spatial_stencil, min_well_resolved_cells_per_wavelength) in
+		[(SpatialStencil::SecondOrder, 32), (SpatialStencil::FourthOrder, 8)]
+	{
+		for (direction_name, field_size, direction) in [
+			("axial", [1, 1, domain_size], [0.0, 0.0, 1.0]),
+			("diagonal", [1, domain_size, domain_size], [0.0, 1.0, 1.0]),
+		]
+		{
+			for cells_per_wavelength in [32, 16, 8]
+			{
+				// Wave vector should be periodic in domain.
+				let wave_number_component = 2.0 * std::f64::consts::PI / (cells_per_wavelength as f64);
+				let wave_vector = direction.map(|d: f64| d * wave_number_component);
+				let wave_number = wave_vector.iter().map(|k| k * k).sum::<f64>().sqrt();
+
+				let expected_angular_frequency =
+					calculate_angular_frequency(wave_vector, time_step as f64, 1.0, spatial_stencil).unwrap();
+				let measured_velocity =
+					measure_phase_velocity(field_size, wave_vector, time_step, num_steps, spatial_stencil);
+
+				results.push(ValidationResult {
+					name: format!(
+						"plane wave phase velocity (order {}, {}, {} cells per wavelength) vs numerical dispersion",
+						spatial_stencil.get_order(),
+						direction_name,
+						cells_per_wavelength
+					),
+					measured: measured_velocity,
+					expected: expected_angular_frequency / wave_number,
+					tolerance: 1.0e-3,
+				});
+				if cells_per_wavelength >= min_well_resolved_cells_per_wavelength
+				{
+					results.push(ValidationResult {
+						name: format!(
+							"plane wave phase velocity (order {}, {}, {} cells per wavelength) vs speed of light",
+							spatial_stencil.get_order(),
+							direction_name,
+							cells_per_wavelength
+						),
+						measured: measured_velocity,
+						expected: 1.0,
+						tolerance: 0.01,
+					});
+				}
+			}
+		}
+	}
+	results
+}
+
+// Find lowest resonant frequencies of rectangular cavity with perfectly conducting walls and compare them with
+// "f = 0.5 * sqrt((m / a)^2 + (n / b)^2 + (p / d)^2)".
+pub fn validate_cavity_resonances() -> Vec<ValidationResult>
+{
+	let interior_size = [24, 18];
+	let grid_spacing = GridSpacing::uniform([interior_size[0] + 2, interior_size[1] + 2, 1]);
+	measure_cavity_resonances(
+		&grid_spacing,
+		None,
+		SpatialStencil::SecondOrder,
+		[8.3, 6.6],
+		[17, 12],
+		0.5,
+		"rectangular cavity",
+	)
+}
+
+// Same as validate_cavity_resonances, but cavity is simulated on graded mesh - with fine cells in the middle along X
+// axis and smoothly growing cells towards walls. Time step is limited by smallest cells.
+pub fn validate_graded_mesh_cavity_resonances() -> Vec<ValidationResult>
+{
+	let grid_spacing = GridSpacing::new([
+		make_graded_spacings(40, 14, 26, 0.5, 1.25, 1.0),
+		vec![1.0; 20],
+		vec![1.0],
+	]);
+	measure_cavity_resonances(
+		&grid_spacing,
+		None,
+		SpatialStencil::SecondOrder,
+		[12.3, 6.6],
+		[27, 12],
+		0.25,
+		"rectangular cavity on graded mesh",
+	)
+}
+
+// Same as validate_cavity_resonances, but part of cavity is simulated on refined subgrid. Probe is placed inside
+// subgrid, so measured field passes through both interfaces between grids.
+pub fn validate_subgrid_cavity_resonances() -> Vec<ValidationResult>
+{
+	let interior_size = [24, 18];
+	let grid_spacing = GridSpacing::uniform([interior_size[0] + 2, interior_size[1] + 2, 1]);
+	let subgrid_params = SubgridParams {
+		region_min: [11, 3, 0],
+		region_max: [22, 17, 1],
+		refinement: 3,
+	};
+	measure_cavity_resonances(
+		&grid_spacing,
+		Some(subgrid_params),
+		SpatialStencil::SecondOrder,
+		[6.3, 6.6],
+		[17, 12],
+		0.5,
+		"rectangular cavity with subgrid",
+	)
+}
+
+// Same as validate_subgrid_cavity_resonances, but coarse grid uses fourth-order stencil, which is faded into
+// second-order one near subgrid. Time step is reduced because of lower stability limit of fourth-order stencil.
+pub fn validate_fourth_order_subgrid_cavity_resonances() -> Vec<ValidationResult>
+{
+	let interior_size = [24, 18];
+	let grid_spacing = GridSpacing::uniform([interior_size[0] + 2, interior_size[1] + 2, 1]);
+	let subgrid_params = SubgridParams {
+		region_min: [11, 3, 0],
+		region_max: [22, 17, 1],
+		refinement: 3,
+	};
+	measure_cavity_resonances(
+		&grid_spacing,
+		Some(subgrid_params),
+		SpatialStencil::FourthOrder,
+		[6.3, 6.6],
+		[17, 12],
+		0.4,
+		"rectangular cavity with subgrid and fourth-order stencil",
+	)
+}
+
+// Modes TM(m, n, 0) don't depend on cavity height, so cavity is simulated with single cell along Z and periodic
+// boundary condition. Walls are made of conductor cells, tangential electric field is zero in centers of wall
+// cells and central differences skip one cell, so cavity size is size of whole grid (interior plus wall cells).
+// Source center and probe are given in cell coordinates. Source shouldn't overlap subgrid.
+// Energy of lossless cavity after source pulse should be conserved, so late-time instability is checked too.
+fn measure_cavity_resonances(
+	grid_spacing: &GridSpacing,
+	subgrid_params: Option<SubgridParams>,
+	spatial_stencil: SpatialStencil,
+	source_center: [f32; 2],
+	probe_coord: [u32; 2],
+	time_step: f32,
+	name: &str,
+) -> Vec<ValidationResult>
+{
+	let field_size = grid_spacing.get_size();
+	assert_eq!(field_size[2], 1);
+	let extent = grid_spacing.get_extent();
+	let cavity_size = [extent.x as f64, extent.y as f64];
+
+	let mut medium = Medium::new(field_size);
+	medium.set_grid_spacing(grid_spacing.clone());
+	let conductor_material_id = medium.add_material(Material {
+		conductivity: 1.0e6,
+		..Material::VACUUM
+	});
+	medium.fill_box([0, 0, 0], field_size, conductor_material_id);
+	medium.fill_box(
+		[1, 1, 0],
+		[field_size[0] - 1, field_size[1] - 1, 1],
+		Medium::VACUUM_MATERIAL_ID,
+	);
+
+	let boundary_conditions = [
+		BoundaryCondition::Reflecting,
+		BoundaryCondition::Reflecting,
+		BoundaryCondition::Periodic,
+	];
+	let mut updater = CpuElectromagneticFieldUpdater::new(&medium, boundary_conditions);
+	updater.set_spatial_stencil(spatial_stencil);
+	let mut subgrid = subgrid_params.map(|params| {
+		// Subgrid is coupled with second-order stencil.
+		updater.set_second_order_box(Some((params.region_min, params.region_max)));
+		let fine_medium = params.make_fine_medium(&medium);
+		let subgrid = CpuSubgrid::new(params, &medium, boundary_conditions, &fine_medium);
+		(subgrid, fine_medium.get_grid_spacing().clone())
+	});
+	// Field of covered cells is restricted from fine grid, so their energy is taken from fine cells.
+	let calculate_energy = |field: &CpuElectromagneticField, subgrid: &Option<(CpuSubgrid, GridSpacing)>| {
+		let (subgrid, fine_grid_spacing) = match subgrid
+		{
+			Some(subgrid) => subgrid,
+			None => return calculate_field_energy(field, grid_spacing, |_| true),
+		};
+		let params = subgrid.get_params();
+		let coarse_energy = calculate_field_energy(field, grid_spacing, |coord| !params.is_covered(field_size, coord));
+		let fine_energy = calculate_field_energy(subgrid.get_fine_field(), fine_grid_spacing, |fine_coord| {
+			params.is_covered(
+				field_size,
+				[0, 1, 2].map(|i| fine_coord[i] / params.refinement + params.region_min[i]),
+			)
+		});
+		coarse_energy + fine_energy
+	};
+
+	// Excite modes with electric field along Z by smooth current pulse at asymmetric point. Smooth source doesn't
+	// excite spurious short-wavelength modes of grid.
+	let duration = 1200.0;
+	let skipped_duration = 100.0;
+	let num_steps = (duration / time_step) as usize;
+	let num_skipped_steps = (skipped_duration / time_step) as usize;
+	let waveform = Waveform::modulated_gaussian_pulse(0.045, 12.0);
+	let current_density_profile =
+		make_gaussian_profile(field_size, [source_center[0], source_center[1], 0.5], 1.5, 2);
+	let probe_address = get_address(field_size, [probe_coord[0], probe_coord[1], 0]);
+
+	let mut field = CpuElectromagneticField::new(field_size);
+	let mut signal = Vec::with_capacity(num_steps);
+	let mut initial_energy = 0.0;
+	let mut time = 0.0;
+	for step in 0 .. num_steps
+	{
+		let current_density = scale_profile(&current_density_profile, waveform.get_value(time + 0.5 * time_step));
+		if let Some((subgrid, _)) = &mut subgrid
+		{
+			subgrid.update_with_current_density(&mut field, &updater, Some(&current_density), time_step);
+		}
+		else
+		{
+			updater.update_with_current_density(&mut field, Some(&current_density), time_step);
+		}
+		time += time_step;
+		if step == num_skipped_steps
+		{
+			initial_energy = calculate_energy(&field, &subgrid);
+		}
+		if step >= num_skipped_steps
+		{
+			signal.push(field.electric_field[probe_address][2]);
+		}
+	}
+	let final_energy = calculate_energy(&field, &subgrid);
+
+	let modes = extract_resonant_modes(
+		&signal,
+		time_step,
+		&HarmonicInversionParams {
+			frequency_min: 0.025,
+			frequency_max: 0.06,
+			max_modes: 12,
+			min_relative_amplitude: 0.01,
+		},
+	);
+
+	let mut results = vec![ValidationResult {
+		name: format!("{} energy conservation", name),
+		measured: final_energy,
+		expected: initial_energy,
+		// Subcycling conserves modified energy, so measured energy oscillates slightly with subgrid.
+		tolerance: if subgrid_params.is_some() { 0.1 } else { 0.05 },
+	}];
+	results.extend([[1, 1], [2, 1], [1, 2]].iter().map(|[m, n]| {
+		let expected = 0.5 * ((*m as f64 / cavity_size[0]).powi(2) + (*n as f64 / cavity_size[1]).powi(2)).sqrt();
+		let measured = modes
+			.iter()
+			.map(|mode| mode.frequency as f64)
+			.min_by(|a, b| (a - expected).abs().partial_cmp(&(b - expected).abs()).unwrap())
+			.unwrap_or(0.0);
+		ValidationResult {
+			name: format!("{} TM{}{}0 resonant frequency", name, m, n),
+			measured,
+			expected,
+			// Numerical dispersion is main source of error, coupling of subgrid adds reflections at interfaces.
+			tolerance: if subgrid_params.is_some() { 0.03 } else { 0.02 },
+		}
+	}));
+	results
+}
+
+// Find lowest resonant frequencies of cylindrical cavity with perfectly conducting walls, simulated in
+// body-of-revolution mode, and compare them with "f = sqrt((x / R)^2 + (p * pi / L)^2) / (2 * pi)", where x is zero of
+// Bessel function J_m for TM modes or zero of its derivative for TE modes. Field on axis is axial for m = 0 and
+// transverse for m = 1, so both cases check handling of axis.
+pub fn validate_body_of_revolution_cavity_resonances() -> Vec<ValidationResult>
+{
+	let size = [16, 24];
+	let cavity_size = [size[0] as f64, size[1] as f64];
+	// Azimuthal mode number and expected modes (name, zero of Bessel function, axial index) for it.
+	let cases = [
+		(
+			0,
+			[
+				("TM010", 2.404826, 0),
+				("TM011", 2.404826, 1),
+				("TE011", 3.831706, 1),
+				("TM020", 5.520078, 0),
+			],
+		),
+		(
+			1,
+			[
+				("TE111", 1.841184, 1),
+				("TM110", 3.831706, 0),
+				("TM111", 3.831706, 1),
+				("TE112", 1.841184, 2),
+			],
+		),
+	];
+
+	let mut results = Vec::new();
+	for (azimuthal_mode_number, modes) in cases.iter()
+	{
+		let medium = Medium::new([size[0], 1, size[1]]);
+		let boundary_conditions = [BoundaryCondition::Reflecting; 2];
+		let time_step =
+			0.9 * BodyOfRevolutionUpdater::new(&medium, boundary_conditions, *azimuthal_mode_number).get_max_time_step();
+		let duration = 1200.0;
+		let skipped_duration = 100.0;
+		// Excite all components by smooth current pulse at point off axis, probe is placed at other point.
+		let setup = BodyOfRevolutionResonatorSetup {
+			medium,
+			boundary_conditions,
+			azimuthal_mode_number: *azimuthal_mode_number,
+			source_center: [5.3, 7.7],
+			source_width: 1.5,
+			source_waveform: Waveform::modulated_gaussian_pulse(0.04, 6.0),
+			probe_coord: [9, 15],
+			time_step,
+			num_steps: (duration / time_step) as u32,
+			num_skipped_steps: (skipped_duration / time_step) as u32,
+			harmonic_inversion_params: HarmonicInversionParams {
+				frequency_min: 0.02,
+				frequency_max: 0.06,
+				max_modes: 16,
+				min_relative_amplitude: 0.01,
+			},
+		};
+		let name = format!("cylindrical cavity with m = {}", azimuthal_mode_number);
+		let resonances = compute_body_of_revolution_resonances(&setup);
+
+		results.push(ValidationResult {
+			name: format!("{} energy conservation", name),
+			measured: resonances.final_energy,
+			expected: resonances.initial_energy,
+			tolerance: 0.05,
+		});
+		results.extend(modes.iter().map(|(mode_name, bessel_zero, axial_index)| {
+			let expected = ((bessel_zero / cavity_size[0]).powi(2) +
+				(*axial_index as f64 * std::f64::consts::PI / cavity_size[1]).powi(2))
+			.sqrt() / (2.0 * std::f64::consts::PI);
+			let measured = resonances
+				.modes
+				.iter()
+				.map(|mode| mode.frequency as f64)
+				.min_by(|a, b| (a - expected).abs().partial_cmp(&(b - expected).abs()).unwrap())
+				.unwrap_or(0.0);
+			ValidationResult {
+				name: format!("{} {} resonant frequency", name, mode_name),
+				measured,
+				expected,
+				tolerance: 0.02,
+			}
+		}));
+	}
+	results
+}
+
+// Calculate energy of fields in vacuum, weighted by cell volumes. Only cells, for which filter returns true, are
+// included.
+fn calculate_field_energy<F>(field: &CpuElectromagneticField, grid_spacing: &GridSpacing, filter: F) -> f64
+where
+	F: Fn([u32; 3]) -> bool,
+{
+	let field_size = field.get_size();
+	field
+		.electric_field
+		.iter()
+		.zip(field.magnetic_field.iter())
+		.enumerate()
+		.filter(|(address, _)| filter(get_coord(field_size, *address)))
+		.map(|(address, (e, h))| {
+			let coord = get_coord(field_size, address);
+			let volume = (0 .. 3).map(|i| grid_spacing.get_spacing(i, coord[i])).product::<f32>();
+			let energy_density = e[.. 3].iter().chain(h[.. 3].iter()).map(|v| v * v).sum::<f32>() * 0.5;
+			(energy_density * volume) as f64
+		})
+		.sum()
+}
+
+// Measure amplitude of field, radiated by small current element, at several distances in equatorial plane and
+// compare it with field of Hertzian dipole "k / (4 * PI * r) * |1 - 1 / (k * r)^2 - i / (k * r)|" (for unit current
+// moment), so falloff of field from near zone to far zone is checked.
+pub fn validate_dipole_radiation() -> Vec<ValidationResult>
+{
+	let field_size = [64, 64, 64];
+	let absorbing_layer_width = 8;
+	let center = field_size.map(|s| s / 2);
+	let frequency = 0.05;
+	let angular_frequency = 2.0 * std::f64::consts::PI * frequency;
+	let distances = [10, 14, 18];
+
+	let medium = Medium::new(field_size);
+	let updater = CpuElectromagneticFieldUpdater::new(
+		&medium,
+		[BoundaryCondition::Absorbing {
+			width: absorbing_layer_width,
+		}; 3],
+	);
+
+	let time_step = 0.5;
+	let num_steps = 320;
+	let waveform = Waveform::modulated_gaussian_pulse(frequency as f32, 10.0);
+	// Field of spherically symmetric source outside it is equal to field of point dipole. For Gaussian profile its
+	// moment is sum of current density, multiplied by "exp(-(k * width)^2 / 2)".
+	let source_center = center.map(|c| c as f32 + 0.5);
+	let source_width = 1.5;
+	let current_density_profile = make_gaussian_profile(field_size, source_center, source_width, 2);
+	let wave_number = angular_frequency;
+	let current_moment = current_density_profile.iter().map(|j| j[2] as f64).sum::<f64>() *
+		(-0.5 * (wave_number * source_width as f64).powi(2)).exp();
+	let probe_addresses = distances
+		.iter()
+		.map(|d| get_address(field_size, [center[0] + d, center[1], center[2]]))
+		.collect::<Vec<_>>();
+
+	let mut field = CpuElectromagneticField::new(field_size);
+	let mut source_spectrum = Complex::ZERO;
+	let mut probe_spectra = vec![Complex::ZERO; distances.len()];
+	let mut time = 0.0;
+	for _step in 0 .. num_steps
+	{
+		let source_time = time + 0.5 * time_step;
+		let source_value = waveform.get_value(source_time);
+		let current_density = scale_profile(&current_density_profile, source_value);
+		updater.update_with_current_density(&mut field, Some(&current_density), time_step);
+		time += time_step;
+
+		source_spectrum += Complex::from_polar(
+			(source_value * time_step) as f64,
+			-angular_frequency * source_time as f64,
+		);
+		for (spectrum, address) in probe_spectra.iter_mut().zip(probe_addresses.iter())
+		{
+			let value = field.electric_field[*address][2];
+			*spectrum += Complex::from_polar((value * time_step) as f64, -angular_frequency * time as f64);
+		}
+	}
+
+	let get_expected_amplitude = |distance: f64| {
+		let kr = wave_number * distance;
+		wave_number / (4.0 * std::f64::consts::PI * distance) *
+			Complex::new(1.0 - 1.0 / (kr * kr), -1.0 / kr).abs() *
+			current_moment
+	};
+
+	// Discretization error of near field is about "(2 / r)^2", like for electrostatic field.
+	distances
+		.iter()
+		.zip(probe_spectra.iter())
+		.map(|(distance, spectrum)| ValidationResult {
+			name: format!("dipole field amplitude at distance {}", distance),
+			measured: spectrum.abs() / source_spectrum.abs(),
+			expected: get_expected_amplitude(*distance as f64),
+			tolerance: 0.05,
+		})
+		.collect()
+}
+
+// Measure power reflection coefficient of plane wave pulse, normally incident on dielectric half-space, and compare
+// it with "((1 - n) / (1 + n))^2".
+pub fn validate_dielectric_reflection() -> Vec<ValidationResult>
+{
+	let field_size = [1, 1, 2000];
+	let interface_position = 900;
+	let permittivity = 4.0;
+	let probe_position = 700;
+	// Reflection from abrupt interface has second order discretization error, so wavelengths should be well resolved.
+	let frequencies = [0.004, 0.006, 0.008];
+
+	let mut medium = Medium::new(field_size);
+	let dielectric_material_id = medium.add_material(Material::dielectric(permittivity));
+	// Dielectric continues inside absorbing layer, which is matched to medium.
+	medium.fill_box([0, 0, interface_position], field_size, dielectric_material_id);
+
+	let reference_spectra = measure_reflection_probe_spectra(&Medium::new(field_size), probe_position, &frequencies);
+	let device_spectra = measure_reflection_probe_spectra(&medium, probe_position, &frequencies);
+
+	let refractive_index = (permittivity as f64).sqrt();
+	let expected = ((1.0 - refractive_index) / (1.0 + refractive_index)).powi(2);
+	frequencies
+		.iter()
+		.zip(reference_spectra.iter().zip(device_spectra.iter()))
+		.map(|(frequency, (incident, total))| {
+			let reflected = *total - *incident;
+			ValidationResult {
+				name: format!("dielectric half-space reflection at frequency {}", frequency),
+				measured: reflected.norm_sqr() / incident.norm_sqr(),
+				expected,
+				tolerance: 0.02,
+			}
+		})
+		.collect()
+}
+
+// Simulate pulse of current density along used field components in each reduced simulation mode and compare result
+// with full simulation of same problem. Reduced modes only mask unused components (see "SimulationMode"), which
+// aren't excited in full simulation either, so fields should match. Energy of reduced field and its overlap with full
+// field (both relative to energy of full field) together check, that their difference is small.
+pub fn validate_reduced_simulation_modes() -> Vec<ValidationResult>
+{
+	let time_step = 0.5;
+	let num_steps = 120;
+	let waveform = Waveform::modulated_gaussian_pulse(0.05, 8.0);
+	let planar_boundary_conditions = [
+		BoundaryCondition::Absorbing { width: 8 },
+		BoundaryCondition::Absorbing { width: 8 },
+		BoundaryCondition::Periodic,
+	];
+	let axial_boundary_conditions = [
+		BoundaryCondition::Periodic,
+		BoundaryCondition::Periodic,
+		BoundaryCondition::Absorbing { width: 16 },
+	];
+
+	let mut results = Vec::new();
+	for (name, simulation_mode, field_size, boundary_conditions, source_component) in [
+		(
+			"two-dimensional transverse magnetic",
+			SimulationMode::TwoDimensional {
+				polarization: TwoDimensionalPolarization::TransverseMagnetic,
+			},
+			[32, 32, 1],
+			planar_boundary_conditions,
+			2,
+		),
+		(
+			"two-dimensional transverse electric",
+			SimulationMode::TwoDimensional {
+				polarization: TwoDimensionalPolarization::TransverseElectric,
+			},
+			[32, 32, 1],
+			planar_boundary_conditions,
+			1,
+		),
+		("one-dimensional", SimulationMode::OneDimensional, [1, 1, 64], axial_boundary_conditions, 0),
+	]
+	{
+		let medium = Medium::new(field_size);
+		let current_density_profile =
+			make_gaussian_profile(field_size, field_size.map(|s| s as f32 * 0.5), 2.0, source_component);
+		let simulate = |simulation_mode: SimulationMode| {
+			let mut updater = CpuElectromagneticFieldUpdater::new(&medium, boundary_conditions);
+			updater.set_simulation_mode(simulation_mode);
+			let mut field = CpuElectromagneticField::new(field_size);
+			for step in 0 .. num_steps
+			{
+				let time = step as f32 * time_step;
+				let current_density =
+					scale_profile(&current_density_profile, waveform.get_value(time + 0.5 * time_step));
+				updater.update_with_current_density(&mut field, Some(&current_density), time_step);
+			}
+			field
+		};
+		let reduced_field = simulate(simulation_mode);
+		let full_field = simulate(SimulationMode::Full);
+
+		let grid_spacing = medium.get_grid_spacing();
+		let full_energy = calculate_field_energy(&full_field, grid_spacing, |_| true);
+		let reduced_energy = calculate_field_energy(&reduced_field, grid_spacing, |_| true);
+		// Cells are uniform, so overlap is calculated like energy.
+		let overlap = reduced_field
+			.electric_field
+			.iter()
+			.zip(full_field.electric_field.iter())
+			.chain(reduced_field.magnetic_field.iter().zip(full_field.magnetic_field.iter()))
+			.map(|(reduced, full)| (0 .. 3).map(|i| reduced[i] * full[i]).sum::<f32>() as f64 * 0.5)
+			.sum::<f64>();
+		results.push(ValidationResult {
+			name: format!("{} simulation mode field energy vs full simulation", name),
+			measured: reduced_energy,
+			expected: full_energy,
+			tolerance: 1.0e-4,
+		});
+		results.push(ValidationResult {
+			name: format!("{} simulation mode field overlap with full simulation", name),
+			measured: overlap,
+			expected: full_energy,
+			tolerance: 1.0e-4,
+		});
+	}
+	results
+}
+
+// Compare electrostatic field of point charge with Coulomb field "q / (4 * PI * r^2)" at several distances.
+pub fn validate_point_charge_field() -> Vec<ValidationResult>
+{
+	let field_size = [96, 96, 96];
+	let charge = 1.0;
+
+	// Use charge with smooth Gaussian distribution, centered at corner point between cells, in order to avoid
+	// discretization errors. Probes are far enough from charge, so it's like point charge.
+	let center = field_size.map(|s| s / 2);
+	let charge_profile = make_gaussian_profile(field_size, center.map(|c| c as f32), 1.5, 0);
+	let charge_scale = charge / charge_profile.iter().map(|v| v[0]).sum::<f32>();
+	let mut setup = ElectrostaticSetup::new(field_size);
+	for (density, value) in setup
+		.charge_density
+		.get_values_mut()
+		.iter_mut()
+		.zip(charge_profile.iter())
+	{
+		*density = value[0] * charge_scale;
+	}
+	let field = solve_electrostatic_field(&setup);
+
+	// Central differences skip one cell, so discretization error is about "(2 / r)^2". Influence of zero potential
+	// outside grid is small for distances much less than half of grid size.
+	[10, 13, 16]
+		.iter()
+		.map(|distance| {
+			// Charge center is corner point between cells, probe cell is shifted along X axis.
+			let cell = [center[0] + distance, center[1], center[2]];
+			let offset = [0, 1, 2].map(|i| cell[i] as f64 + 0.5 - center[i] as f64);
+			let r2 = offset.iter().map(|o| o * o).sum::<f64>();
+			let value = field[get_address(field_size, cell)];
+			ValidationResult {
+				name: format!("point charge field magnitude at distance {:.2}", r2.sqrt()),
+				measured: value[.. 3].iter().map(|v| (v * v) as f64).sum::<f64>().sqrt(),
+				expected: charge as f64 / (4.0 * std::f64::consts::PI * r2),
+				tolerance: 0.05,
+			}
+		})
+		.collect()
+}
+
+// Run simulation of plane wave pulse, propagating along Z axis, and get spectrum of electric field at probe.
+fn measure_reflection_probe_spectra(medium: &Medium, probe_position: u32, frequencies: &[f64]) -> Vec<Complex>
+{
+	let field_size = medium.get_size();
+	let absorbing_layer_width = 40;
+	let updater = CpuElectromagneticFieldUpdater::new(
+		medium,
+		[
+			BoundaryCondition::Periodic,
+			BoundaryCondition::Periodic,
+			BoundaryCondition::Absorbing {
+				width: absorbing_layer_width,
+			},
+		],
+	);
+
+	let time_step = 0.5;
+	let num_steps = 2200;
+	let pulse_center = 300.0;
+	let pulse_width = 60.0;
+	let pulse_frequency = 0.006;
+	let pulse_value = |z: f32| {
+		let dz = z - pulse_center;
+		(-0.5 * dz * dz / (pulse_width * pulse_width)).exp() *
+			(dz * pulse_frequency * (2.0 * std::f32::consts::PI)).sin()
+	};
+
+	// Pulse propagates towards positive Z direction, magnetic field is half step ahead.
+	let mut field = CpuElectromagneticField::new(field_size);
+	for z in 0 .. field_size[2]
+	{
+		field.electric_field[z as usize][0] = pulse_value(z as f32);
+		field.magnetic_field[z as usize][1] = pulse_value(z as f32 - 0.5 * time_step);
+	}
+
+	let probe_address = get_address(field_size, [0, 0, probe_position]);
+	let mut spectra = vec![Complex::ZERO; frequencies.len()];
+	let mut time = 0.0;
+	for _step in 0 .. num_steps
+	{
+		updater.update(&mut field, time_step);
+		time += time_step;
+
+		let value = field.electric_field[probe_address][0];
+		for (spectrum, frequency) in spectra.iter_mut().zip(frequencies.iter())
+		{
+			let angular_frequency = 2.0 * std::f64::consts::PI * frequency;
+			*spectrum += Complex::from_polar((value * time_step) as f64, -angular_frequency * time as f64);
+		}
+	}
+	spectra
+}
+
+// Create vector field data with Gaussian profile of given component.
+fn make_gaussian_profile(field_size: [u32; 3], center: [f32; 3], width: f32, component: usize) -> Vec<[f32; 4]>
+{
+	let len = (field_size[0] * field_size[1] * field_size[2]) as usize;
+	(0 .. len)
+		.map(|address| {
+			let coord = get_coord(field_size, address);
+			let r2 = (0 .. 3)
+				.map(|i| (coord[i] as f32 + 0.5 - center[i]).powi(2))
+				.sum::<f32>();
+			let mut value = [0.0; 4];
+			value[component] = (-0.5 * r2 / (width * width)).exp();
+			value
+		})
+		.collect()
+}
+
+fn scale_profile(profile: &[[f32; 4]], scale: f32) -> Vec<[f32; 4]>
+{
+	profile.iter().map(|v| v.map(|c| c * scale)).collect()
+}
+
+fn get_coord(field_size: [u32; 3], address: usize) -> [u32; 3]
+{
+	let address = address as u32;
+	[
+		address % field_size[0],
+		address / field_size[0] % field_size[1],
+		address / (field_size[0] * field_size[1]),
+	]
+}
+
+fn get_address(field_size: [u32; 3], coord: [u32; 3]) -> usize
+{
+	(coord[0] + coord[1] * field_size[0] + coord[2] * (field_size[0] * field_size[1])) as usize
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	fn check_results(results: &[ValidationResult])
+	{
+		assert!(!results.is_empty());
+		for result in results
+		{
+			assert!(
+				result.is_passed(),
+				"{}: measured {}, expected {}, relative error {} exceeds tolerance {}",
+				result.name,
+				result.measured,
+				result.expected,
+				result.get_relative_error(),
+				result.tolerance
+			);
+		}
+	}
+
+	#[test]
+	fn plane_wave_propagation()
+	{
+		check_results(&validate_plane_wave_propagation());
+	}
+
+	#[test]
+	fn cavity_resonances()
+	{
+		check_results(&validate_cavity_resonances());
+	}
+
+	#[test]
+	fn body_of_revolution_cavity_resonances()
+	{
+		check_results(&validate_body_of_revolution_cavity_resonances());
+	}
+
+	#[test]
+	fn graded_mesh_cavity_resonances()
+	{
+		check_results(&validate_graded_mesh_cavity_resonances());
+	}
+
+	#[test]
+	fn subgrid_cavity_resonances()
+	{
+		check_results(&validate_subgrid_cavity_resonances());
+	}
+
+	#[test]
+	fn fourth_order_subgrid_cavity_resonances()
+	{
+		check_results(&validate_fourth_order_subgrid_cavity_resonances());
+	}
+
+	#[test]
+	fn dipole_radiation()
+	{
+		check_results(&validate_dipole_radiation());
+	}
+
+	#[test]
+	fn dielectric_reflection()
+	{
+		check_results(&validate_dielectric_reflection());
+	}
+
+	#[test]
+	fn reduced_simulation_modes()
+	{
+		check_results(&validate_reduced_simulation_modes());
+	}
+
+	#[test]
+	fn point_charge_field()
+	{
+		check_results(&validate_point_charge_field());
+	}
+}
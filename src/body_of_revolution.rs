@@ -0,0 +1,485 @@
+use super::{
+	cpu_electromagnetic_field_updater::*, electromagnetic_field_updater::*, harmonic_inversion::*, medium::*, waveform::*,
+};
+use std::io::Write;
+
+// Body-of-revolution simulation of axisymmetric structures (horn antennas, fibers, cylindrical cavities).
+// Field is expanded into azimuthal harmonics, and for given azimuthal mode number m only two-dimensional problem in
+// (r, z) plane is solved: Er, Ez and H(phi) are proportional to cos(m * phi), E(phi), Hr and Hz - to sin(m * phi).
+// For m = 0 both sets are independent and don't depend on phi.
+// Unlike three-dimensional updaters, staggered grid is used, since it allows exact handling of axis. Components of cell
+// (i, k) are located at (r / dr, z / dz): Er - (i + 0.5, k), E(phi) - (i, k), Ez - (i, k + 0.5), Hr - (i, k + 0.5),
+// H(phi) - (i + 0.5, k + 0.5), Hz - (i + 0.5, k).
+// Components with integer radial position of cell with i = 0 are on axis.
+
+// Electric and magnetic fields of body-of-revolution simulation, each vector contains (r, phi, z) components.
+#[derive(Clone)]
+pub struct BodyOfRevolutionField
+{
+	size: [u32; 2],
+	pub electric_field: Vec<[f32; 3]>,
+	pub magnetic_field: Vec<[f32; 3]>,
+}
+
+impl BodyOfRevolutionField
+{
+	// Create zero field with given number of cells along r and z.
+	pub fn new(size: [u32; 2]) -> Self
+	{
+		let len = (size[0] * size[1]) as usize;
+		Self {
+			size,
+			electric_field: vec![[0.0; 3]; len],
+			magnetic_field: vec![[0.0; 3]; len],
+		}
+	}
+
+	pub fn get_size(&self) -> [u32; 2]
+	{
+		self.size
+	}
+}
+
+// Updater of body-of-revolution field on CPU. Medium has single cell along Y, its X axis is radial and Z axis is
+// axial one, grid should be uniform along each axis. Only linear isotropic non-dispersive media are supported.
+// Unlike three-dimensional updaters (where field outside is equal to field in border cells), "Reflecting" boundary
+// means perfectly conducting wall with zero field outside. Outer radial boundary is always such wall, it may be covered
+// by absorbing layer. Boundaries along Z may be also periodic. Time step should be less than "get_max_time_step".
+pub struct BodyOfRevolutionUpdater
+{
+	size: [u32; 2],
+	// Cell sizes along r and z.
+	cell_size: [f32; 2],
+	azimuthal_mode_number: u32,
+	axial_boundary_condition: BoundaryCondition,
+	// (permittivity, permeability, conductivity, magnetic conductivity) for each cell.
+	materials: Vec<[f32; 4]>,
+	// Conductivity of absorbing layers (relative to medium permittivity or permeability) at positions of each
+	// component.
+	electric_layer_conductivities: Vec<[f32; 3]>,
+	magnetic_layer_conductivities: Vec<[f32; 3]>,
+}
+
+impl BodyOfRevolutionUpdater
+{
+	// Boundary conditions are given for outer radial boundary and for both boundaries along Z.
+	pub fn new(medium: &Medium, boundary_conditions: [BoundaryCondition; 2], azimuthal_mode_number: u32) -> Self
+	{
+		assert!(
+			!medium.has_dispersive_materials() &&
+				!medium.has_nonlinear_materials() &&
+				!medium.has_anisotropic_materials(),
+			"body-of-revolution updater supports only linear isotropic non-dispersive media"
+		);
+		assert_ne!(
+			boundary_conditions[0],
+			BoundaryCondition::Periodic,
+			"radial boundary can't be periodic"
+		);
+
+		let medium_size = medium.get_size();
+		assert_eq!(medium_size[1], 1, "medium should have single cell along Y");
+		let size = [medium_size[0], medium_size[2]];
+		let grid_spacing = medium.get_grid_spacing();
+		let cell_size = [grid_spacing.get_spacing(0, 0), grid_spacing.get_spacing(2, 0)];
+		for (i, &axis) in [0, 2].iter().enumerate()
+		{
+			assert!(
+				(0 .. size[i]).all(|index| grid_spacing.get_spacing(axis, index) == cell_size[i]),
+				"grid should be uniform"
+			);
+		}
+
+		let materials = medium.get_materials();
+		let material_ids = medium.get_material_ids();
+		let len = (size[0] * size[1]) as usize;
+
+		// Positions (in cells) of components relative to cell corner.
+		let get_layer_conductivities = |address: usize, offsets: [[f32; 2]; 3]| {
+			let coord = get_coord(size, address);
+			offsets.map(|offset| {
+				let position = [0, 1].map(|i| coord[i] as f32 + offset[i]);
+				let mut conductivity = 0.0;
+				if let BoundaryCondition::Absorbing { width } = boundary_conditions[0]
+				{
+					let layer_width = width as f32 * cell_size[0];
+					conductivity +=
+						get_graded_conductivity((position[0] - (size[0] - width) as f32) * cell_size[0], layer_width);
+				}
+				if let BoundaryCondition::Absorbing { width } = boundary_conditions[1]
+				{
+					let layer_width = width as f32 * cell_size[1];
+					let depth = (width as f32 - position[1]).max(position[1] - (size[1] - width) as f32);
+					conductivity += get_graded_conductivity(depth * cell_size[1], layer_width);
+				}
+				conductivity
+			})
+		};
+
+		Self {
+			size,
+			cell_size,
+			azimuthal_mode_number,
+			axial_boundary_condition: boundary_conditions[1],
+			materials: material_ids
+				.iter()
+				.map(|id| {
+					let m = &materials[*id as usize];
+					[m.permittivity, m.permeability, m.conductivity, m.magnetic_conductivity]
+				})
+				.collect(),
+			electric_layer_conductivities: (0 .. len)
+				.map(|address| get_layer_conductivities(address, [[0.5, 0.0], [0.0, 0.0], [0.0, 0.5]]))
+				.collect(),
+			magnetic_layer_conductivities: (0 .. len)
+				.map(|address| get_layer_conductivities(address, [[0.0, 0.5], [0.5, 0.5], [0.5, 0.0]]))
+				.collect(),
+		}
+	}
+
+	// Stability limit of time step. Terms with "m / r" make it stricter for higher azimuthal mode numbers.
+	pub fn get_max_time_step(&self) -> f32
+	{
+		let min_cell_size = self.cell_size[0].min(self.cell_size[1]);
+		if self.azimuthal_mode_number == 0
+		{
+			min_cell_size / std::f32::consts::SQRT_2
+		}
+		else
+		{
+			min_cell_size / (self.azimuthal_mode_number + 1) as f32
+		}
+	}
+
+	// Perform update step with external current density, which should be given at half step at positions of
+	// electric field components. Magnetic field is considered to be half step ahead.
+	pub fn update_with_current_density(
+		&self,
+		field: &mut BodyOfRevolutionField,
+		current_density: Option<&[[f32; 3]]>,
+		time_delta_s: f32,
+	)
+	{
+		assert_eq!(field.get_size(), self.size);
+		if let Some(current_density) = current_density
+		{
+			assert_eq!(current_density.len(), field.electric_field.len());
+		}
+
+		let dt = time_delta_s;
+		for address in 0 .. field.electric_field.len()
+		{
+			let curl = self.calculate_magnetic_field_curl(&field.magnetic_field, get_coord(self.size, address));
+			let material = self.materials[address];
+			let j = current_density.map_or([0.0; 3], |current_density| current_density[address]);
+			let e = &mut field.electric_field[address];
+			for c in 0 .. 3
+			{
+				let permittivity = material[0];
+				let conductivity = material[2] + self.electric_layer_conductivities[address][c] * permittivity;
+				// Use semi-implicit scheme for conductivity.
+				let half_loss = 0.5 * conductivity * dt;
+				let self_factor = (permittivity - half_loss) / (permittivity + half_loss);
+				let inverse_factor = 1.0 / (permittivity + half_loss);
+				e[c] = e[c] * self_factor + (curl[c] - j[c]) * (dt * inverse_factor);
+			}
+		}
+		self.apply_electric_field_constraints(&mut field.electric_field);
+
+		for address in 0 .. field.magnetic_field.len()
+		{
+			let curl = self.calculate_electric_field_curl(&field.electric_field, get_coord(self.size, address));
+			let material = self.materials[address];
+			let h = &mut field.magnetic_field[address];
+			for c in 0 .. 3
+			{
+				let permeability = material[1];
+				// Absorbing layer is matched, as in three-dimensional updaters.
+				let magnetic_conductivity = material[3] + self.magnetic_layer_conductivities[address][c] * permeability;
+				let half_loss = 0.5 * magnetic_conductivity * dt;
+				let self_factor = (permeability - half_loss) / (permeability + half_loss);
+				let inverse_factor = 1.0 / (permeability + half_loss);
+				h[c] = h[c] * self_factor - curl[c] * (dt * inverse_factor);
+			}
+		}
+		// Hr on axis is non-zero only for m = 1.
+		if self.azimuthal_mode_number != 1
+		{
+			for k in 0 .. self.size[1]
+			{
+				field.magnetic_field[(k * self.size[0]) as usize][0] = 0.0;
+			}
+		}
+	}
+
+	// Calculate energy of field in whole volume (integrated over phi).
+	pub fn calculate_energy(&self, field: &BodyOfRevolutionField) -> f64
+	{
+		let [dr, dz] = self.cell_size;
+		// Cell on axis is disk with radius of half cell, it's equivalent to ring with radius of eighth of cell.
+		let get_radius = |position: f32| if position == 0.0 { 0.125 * dr } else { position * dr };
+		let angular_factor = if self.azimuthal_mode_number == 0
+		{
+			2.0 * std::f32::consts::PI
+		}
+		else
+		{
+			std::f32::consts::PI
+		};
+
+		let mut energy = 0.0;
+		for address in 0 .. field.electric_field.len()
+		{
+			let i = get_coord(self.size, address)[0] as f32;
+			let [permittivity, permeability, ..] = self.materials[address];
+			let e = field.electric_field[address];
+			let h = field.magnetic_field[address];
+			let radii = [[i + 0.5, i, i], [i, i + 0.5, i + 0.5]].map(|r| r.map(get_radius));
+			let cell_energy = (0 .. 3)
+				.map(|c| permittivity * e[c] * e[c] * radii[0][c] + permeability * h[c] * h[c] * radii[1][c])
+				.sum::<f32>();
+			energy += (0.5 * cell_energy * dr * dz * angular_factor) as f64;
+		}
+		energy
+	}
+
+	// Curl of magnetic field at positions of electric field components of given cell.
+	fn calculate_magnetic_field_curl(&self, h: &[[f32; 3]], coord: [i32; 2]) -> [f32; 3]
+	{
+		let [i, k] = coord;
+		let [dr, dz] = self.cell_size;
+		let m = self.azimuthal_mode_number as f32;
+		let get = |i: i32, k: i32, c: usize| self.get_component(h, i, k, c);
+
+		let r_half = (i as f32 + 0.5) * dr;
+		let curl_r = m * get(i, k, 2) / r_half - (get(i, k, 1) - get(i, k - 1, 1)) / dz;
+		if i == 0
+		{
+			// On axis Hz of opposite side (r = -dr / 2) is equal to "(-1)^m * Hz", E(phi) is non-zero only for m = 1.
+			// Ez is non-zero only for m = 0, its curl is found from circulation of H(phi) around axis.
+			let curl_phi = (get(0, k, 0) - get(0, k - 1, 0)) / dz - 2.0 * get(0, k, 2) / dr;
+			let curl_z = 4.0 * get(0, k, 1) / dr;
+			return [curl_r, curl_phi, curl_z];
+		}
+
+		let r = i as f32 * dr;
+		let curl_phi = (get(i, k, 0) - get(i, k - 1, 0)) / dz - (get(i, k, 2) - get(i - 1, k, 2)) / dr;
+		let curl_z = ((i as f32 + 0.5) * get(i, k, 1) - (i as f32 - 0.5) * get(i - 1, k, 1) - m * get(i, k, 0)) / r;
+		[curl_r, curl_phi, curl_z]
+	}
+
+	// Curl of electric field at positions of magnetic field components of given cell.
+	fn calculate_electric_field_curl(&self, e: &[[f32; 3]], coord: [i32; 2]) -> [f32; 3]
+	{
+		let [i, k] = coord;
+		let [dr, dz] = self.cell_size;
+		let m = self.azimuthal_mode_number as f32;
+		let get = |i: i32, k: i32, c: usize| self.get_component(e, i, k, c);
+
+		// On axis Ez is zero for m = 1 and grows linearly with radius.
+		let ez_over_r = if i == 0
+		{
+			get(1, k, 2) / dr
+		}
+		else
+		{
+			get(i, k, 2) / (i as f32 * dr)
+		};
+		let curl_r = -m * ez_over_r - (get(i, k + 1, 1) - get(i, k, 1)) / dz;
+		let curl_phi = (get(i, k + 1, 0) - get(i, k, 0)) / dz - (get(i + 1, k, 2) - get(i, k, 2)) / dr;
+		let r_half = (i as f32 + 0.5) * dr;
+		let curl_z = ((i + 1) as f32 * get(i + 1, k, 1) - i as f32 * get(i, k, 1) + m * get(i, k, 0)) / r_half;
+		[curl_r, curl_phi, curl_z]
+	}
+
+	// Zero tangential electric field on perfectly conducting axial walls and components, which vanish on axis.
+	fn apply_electric_field_constraints(&self, e: &mut [[f32; 3]])
+	{
+		let m = self.azimuthal_mode_number;
+		for k in 0 .. self.size[1]
+		{
+			let address = (k * self.size[0]) as usize;
+			if m != 1
+			{
+				e[address][1] = 0.0;
+			}
+			if m != 0
+			{
+				e[address][2] = 0.0;
+			}
+		}
+		if self.axial_boundary_condition != BoundaryCondition::Periodic
+		{
+			for v in &mut e[.. self.size[0] as usize]
+			{
+				v[0] = 0.0;
+				v[1] = 0.0;
+			}
+		}
+	}
+
+	// Get field component of cell with given coordinates. Field outside of outer radial wall and outside of
+	// non-periodic axial boundaries is zero (only tangential electric field is read there).
+	fn get_component(&self, data: &[[f32; 3]], i: i32, k: i32, component: usize) -> f32
+	{
+		let num_axial_cells = self.size[1] as i32;
+		let k = if self.axial_boundary_condition == BoundaryCondition::Periodic
+		{
+			(k + num_axial_cells) % num_axial_cells
+		}
+		else
+		{
+			k
+		};
+		if i < 0 || i >= self.size[0] as i32 || k < 0 || k >= num_axial_cells
+		{
+			return 0.0;
+		}
+		data[(i + k * self.size[0] as i32) as usize][component]
+	}
+}
+
+// Setup for search of resonant modes of axisymmetric structure with given azimuthal mode number.
+// Field is excited by pulse of current density (all components) with Gaussian profile in (r, z) plane, signal is
+// recorded in probe cell.
+pub struct BodyOfRevolutionResonatorSetup
+{
+	pub medium: Medium,
+	// Boundary conditions for outer radial boundary and for both boundaries along Z (see "BodyOfRevolutionUpdater").
+	pub boundary_conditions: [BoundaryCondition; 2],
+	pub azimuthal_mode_number: u32,
+	// Position (r, z) and width of current density profile, measured in cells.
+	pub source_center: [f32; 2],
+	pub source_width: f32,
+	pub source_waveform: Waveform,
+	// Sum of electric field components of this cell is used as signal.
+	pub probe_coord: [u32; 2],
+	pub time_step: f32,
+	pub num_steps: u32,
+	// Number of initial steps, ignored for analysis (while source is still active).
+	pub num_skipped_steps: u32,
+	pub harmonic_inversion_params: HarmonicInversionParams,
+}
+
+pub struct BodyOfRevolutionResonances
+{
+	pub modes: Vec<ResonantMode>,
+	// Energy of field after skipped steps and at the end of simulation.
+	pub initial_energy: f64,
+	pub final_energy: f64,
+}
+
+pub fn compute_body_of_revolution_resonances(setup: &BodyOfRevolutionResonatorSetup) -> BodyOfRevolutionResonances
+{
+	let updater = BodyOfRevolutionUpdater::new(&setup.medium, setup.boundary_conditions, setup.azimuthal_mode_number);
+	let size = updater.size;
+
+	let inv_width2 = 1.0 / (setup.source_width * setup.source_width);
+	let current_density_profile = (0 .. size[0] * size[1])
+		.map(|address| {
+			let coord = [address % size[0], address / size[0]];
+			let r2 = (0 .. 2)
+				.map(|i| (coord[i] as f32 + 0.5 - setup.source_center[i]).powi(2))
+				.sum::<f32>();
+			[(-0.5 * r2 * inv_width2).exp(); 3]
+		})
+		.collect::<Vec<_>>();
+	let probe_address = (setup.probe_coord[0] + setup.probe_coord[1] * size[0]) as usize;
+
+	let mut field = BodyOfRevolutionField::new(size);
+	let mut signal = Vec::with_capacity(setup.num_steps as usize);
+	let mut initial_energy = 0.0;
+	let mut time = 0.0;
+	for step in 0 .. setup.num_steps
+	{
+		let scale = setup.source_waveform.get_value(time + 0.5 * setup.time_step);
+		let current_density = current_density_profile
+			.iter()
+			.map(|v| v.map(|c| c * scale))
+			.collect::<Vec<_>>();
+		updater.update_with_current_density(&mut field, Some(&current_density), setup.time_step);
+		time += setup.time_step;
+		if step == setup.num_skipped_steps
+		{
+			initial_energy = updater.calculate_energy(&field);
+		}
+		if step >= setup.num_skipped_steps
+		{
+			signal.push(field.electric_field[probe_address].iter().sum::<f32>());
+		}
+	}
+
+	BodyOfRevolutionResonances {
+		modes: extract_resonant_modes(&signal, setup.time_step, &setup.harmonic_inversion_params),
+		initial_energy,
+		final_energy: updater.calculate_energy(&field),
+	}
+}
+
+// Write resonant modes along with their azimuthal mode numbers.
+pub fn write_body_of_revolution_modes_table(
+	modes: &[(u32, ResonantMode)],
+	file_path: &std::path::Path,
+) -> std::io::Result<()>
+{
+	let mut file = std::io::BufWriter::new(std::fs::File::create(file_path)?);
+	writeln!(file, "azimuthal_mode_number,frequency,decay_rate,quality_factor,amplitude,phase")?;
+	for (azimuthal_mode_number, mode) in modes
+	{
+		writeln!(
+			file,
+			"{},{},{},{},{},{}",
+			azimuthal_mode_number, mode.frequency, mode.decay_rate, mode.quality_factor, mode.amplitude, mode.phase
+		)?;
+	}
+	file.flush()
+}
+
+// Dielectric cylinder with high permittivity on axis of open (absorbing) domain.
+pub fn create_test_dielectric_cylinder_setup(azimuthal_mode_number: u32) -> BodyOfRevolutionResonatorSetup
+{
+	let size = [48, 96];
+	let cylinder_radius = 8;
+	let cylinder_height = 12;
+
+	let mut medium = Medium::new([size[0], 1, size[1]]);
+	let dielectric_material_id = medium.add_material(Material::dielectric(9.0));
+	let cylinder_start = (size[1] - cylinder_height) / 2;
+	medium.fill_box(
+		[0, 0, cylinder_start],
+		[cylinder_radius, 1, cylinder_start + cylinder_height],
+		dielectric_material_id,
+	);
+
+	let boundary_conditions = [BoundaryCondition::Absorbing { width: 12 }; 2];
+	let time_step =
+		0.9 * BodyOfRevolutionUpdater::new(&medium, boundary_conditions, azimuthal_mode_number).get_max_time_step();
+	let duration = 4000.0;
+	let skipped_duration = 100.0;
+
+	BodyOfRevolutionResonatorSetup {
+		medium,
+		boundary_conditions,
+		azimuthal_mode_number,
+		// Use excitation and probe away from symmetry plane of cylinder in order to excite and detect more modes.
+		source_center: [5.3, (size[1] / 2) as f32 + 3.7],
+		source_width: 1.5,
+		source_waveform: Waveform::modulated_gaussian_pulse(0.04, 6.0),
+		probe_coord: [3, size[1] / 2 - 4],
+		time_step,
+		num_steps: (duration / time_step) as u32,
+		num_skipped_steps: (skipped_duration / time_step) as u32,
+		harmonic_inversion_params: HarmonicInversionParams {
+			frequency_min: 0.01,
+			frequency_max: 0.08,
+			max_modes: 16,
+			min_relative_amplitude: 0.01,
+		},
+	}
+}
+
+fn get_coord(size: [u32; 2], address: usize) -> [i32; 2]
+{
+	let address = address as u32;
+	[(address % size[0]) as i32, (address / size[0]) as i32]
+}
@@ -0,0 +1,436 @@
+use super::{electromagnetic_field::*, math_types::*, scalar_grid::*, vector_field::*};
+
+// Charged particles, moving under Lorentz force of electromagnetic field and producing current density.
+// Particles, leaving domain (except one-cell border), deposit current up to border crossing and are deactivated there,
+// so their charge remains at crossing point (like charge, absorbed by wall).
+// Positions and velocities are given in grid units (cell coordinates), so particles should be used only with uniform
+// grid spacing.
+pub struct ChargedParticles
+{
+	shader_clear_current: glium::program::ComputeShader,
+	shader_push: glium::program::ComputeShader,
+	shader_convert_current: glium::program::ComputeShader,
+	num_particles: u32,
+	// Two values for each particle - (position, charge) and (momentum per unit mass, mass).
+	// Mass is zero for deactivated particles.
+	particles_buffer: glium::buffer::Buffer<[[f32; 4]]>,
+	// Fixed-point currents through cell faces (in positive directions), deposited by particles.
+	face_current_buffer: glium::buffer::Buffer<[i32]>,
+	current_density: VectorField,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct ChargedParticle
+{
+	pub position: Vec3f,
+	// Should be less than speed of light.
+	pub velocity: Vec3f,
+	pub charge: f32,
+	pub mass: f32,
+}
+
+impl ChargedParticles
+{
+	pub fn new(display: &glium::Display, field_size: [u32; 3], particles: &[ChargedParticle]) -> Self
+	{
+		let mut particles_data = Vec::with_capacity(particles.len() * 2);
+		for particle in particles
+		{
+			let gamma = 1.0 / (1.0 - particle.velocity.magnitude2()).max(1.0e-12).sqrt();
+			particles_data.push(particle.position.extend(particle.charge).into());
+			particles_data.push((particle.velocity * gamma).extend(particle.mass).into());
+		}
+		if particles_data.is_empty()
+		{
+			// Avoid creation of empty buffer.
+			particles_data.push([0.0; 4]);
+			particles_data.push([0.0; 4]);
+		}
+
+		let cell_count = (field_size[0] * field_size[1] * field_size[2]) as usize;
+
+		Self {
+			shader_clear_current: glium::program::ComputeShader::from_source(
+				display,
+				&[SHADER_COMMON, SHADER_CLEAR_CURRENT].concat(),
+			)
+			.unwrap(),
+			shader_push: glium::program::ComputeShader::from_source(display, &[SHADER_COMMON, SHADER_PUSH].concat())
+				.unwrap(),
+			shader_convert_current: glium::program::ComputeShader::from_source(
+				display,
+				&[SHADER_COMMON, SHADER_CONVERT_CURRENT].concat(),
+			)
+			.unwrap(),
+			num_particles: particles.len() as u32,
+			particles_buffer: glium::buffer::Buffer::new(
+				display,
+				&particles_data[..],
+				glium::buffer::BufferType::ShaderStorageBuffer,
+				glium::buffer::BufferMode::Default,
+			)
+			.unwrap(),
+			face_current_buffer: glium::buffer::Buffer::new(
+				display,
+				&vec![0; cell_count * 3][..],
+				glium::buffer::BufferType::ShaderStorageBuffer,
+				glium::buffer::BufferMode::Default,
+			)
+			.unwrap(),
+			current_density: VectorField::new(display, field_size),
+		}
+	}
+
+	pub fn get_num_particles(&self) -> u32
+	{
+		self.num_particles
+	}
+
+	pub fn get_particles_buffer(&self) -> &glium::buffer::Buffer<[[f32; 4]]>
+	{
+		&self.particles_buffer
+	}
+
+	// Current density, produced by particles during last update.
+	pub fn get_current_density(&self) -> &VectorField
+	{
+		&self.current_density
+	}
+
+	// Advance particles by one time step, using electric field at current time and magnetic field half step ahead.
+	// Produced current density should be used for following field update.
+	pub fn update(&mut self, field: &ElectromagneticField, time_delta_s: f32)
+	{
+		let field_size = field.electric_field.get_size();
+		assert_eq!(field_size, self.current_density.get_size());
+
+		let uniforms = glium::uniform! {
+			dt: time_delta_s,
+			field_size: field_size,
+			num_particles: self.num_particles,
+			particles_data: &self.particles_buffer,
+			face_current_data: &self.face_current_buffer,
+			current_density_data: self.current_density.get_buffer(),
+			electric_field_data: field.electric_field.get_buffer(),
+			magnetic_field_data: field.magnetic_field.get_buffer(),
+		};
+
+		self.shader_clear_current
+			.execute(uniforms, field_size[0], field_size[1], field_size[2]);
+		self.shader_push
+			.execute(uniforms, self.num_particles.div_ceil(PUSH_GROUP_SIZE), 1, 1);
+		self.shader_convert_current
+			.execute(uniforms, field_size[0], field_size[1], field_size[2]);
+	}
+}
+
+// Calculate charge density of particles, consistent with current density, produced by particles.
+// Cloud-in-cell shape is used, with following smoothing along each axis.
+pub fn calculate_charge_density(field_size: [u32; 3], particles: &[ChargedParticle]) -> ScalarGrid
+{
+	let mut charge_density = ScalarGrid::new(field_size);
+	for particle in particles
+	{
+		let coord = particle.position - Vec3f::new(0.5, 0.5, 0.5);
+		let base = [coord.x.floor(), coord.y.floor(), coord.z.floor()];
+		let k = [coord.x - base[0], coord.y - base[1], coord.z - base[2]];
+		for dz in 0 .. 2
+		{
+			for dy in 0 .. 2
+			{
+				for dx in 0 .. 2
+				{
+					let weight = (if dx == 0 { 1.0 - k[0] } else { k[0] }) *
+						(if dy == 0 { 1.0 - k[1] } else { k[1] }) *
+						(if dz == 0 { 1.0 - k[2] } else { k[2] });
+					charge_density.add(
+						base[0] as i32 + dx,
+						base[1] as i32 + dy,
+						base[2] as i32 + dz,
+						particle.charge * weight,
+					);
+				}
+			}
+		}
+	}
+
+	const SMOOTH_WEIGHTS: [f32; 3] = [0.25, 0.5, 0.25];
+	for axis in 0 .. 3
+	{
+		let mut smoothed = ScalarGrid::new(field_size);
+		for z in 0 .. field_size[2] as i32
+		{
+			for y in 0 .. field_size[1] as i32
+			{
+				for x in 0 .. field_size[0] as i32
+				{
+					let value = charge_density.get(x, y, z);
+					if value == 0.0
+					{
+						continue;
+					}
+					for (i, weight) in SMOOTH_WEIGHTS.iter().enumerate()
+					{
+						let mut coord = [x, y, z];
+						coord[axis] += i as i32 - 1;
+						smoothed.add(coord[0], coord[1], coord[2], value * weight);
+					}
+				}
+			}
+		}
+		charge_density = smoothed;
+	}
+
+	charge_density
+}
+
+const PUSH_GROUP_SIZE: u32 = 64;
+
+const SHADER_COMMON: &str = r#"
+	#version 430
+
+	uniform float dt;
+	uniform uvec3 field_size;
+	uniform uint num_particles;
+
+	layout(std430) buffer particles_data
+	{
+		vec4 particles[];
+	};
+	// Three values for each cell - currents through faces in positive X, Y, Z directions.
+	layout(std430) buffer face_current_data
+	{
+		int face_currents[];
+	};
+	layout(std430) buffer current_density_data
+	{
+		vec4 current_density_vecs[];
+	};
+	layout(std430) buffer electric_field_data
+	{
+		vec4 electric_vecs[];
+	};
+	layout(std430) buffer magnetic_field_data
+	{
+		vec4 magnetic_vecs[];
+	};
+
+	// Scale for conversion of currents into fixed-point values, which are needed for atomic addition.
+	const float c_current_scale = 1048576.0;
+
+	uint get_address(ivec3 coord)
+	{
+		uvec3 c = uvec3(clamp(coord, ivec3(0, 0, 0), ivec3(field_size) - ivec3(1, 1, 1)));
+		return c.x + c.y * field_size.x + c.z * (field_size.x * field_size.y);
+	}
+"#;
+
+const SHADER_CLEAR_CURRENT: &str = r#"
+	layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
+
+	void main()
+	{
+		uint address = get_address(ivec3(gl_GlobalInvocationID));
+		for(uint i = 0u; i < 3u; ++i)
+		{
+			face_currents[address * 3u + i] = 0;
+		}
+	}
+"#;
+
+const SHADER_PUSH: &str = r#"
+	layout(local_size_x = 64, local_size_y = 1, local_size_z = 1) in;
+
+	// Interpolate field, defined in cell centers, using trilinear interpolation.
+	vec3 interpolate_field(vec3 position, bool use_electric_field)
+	{
+		vec3 coord = position - vec3(0.5, 0.5, 0.5);
+		ivec3 base = ivec3(floor(coord));
+		vec3 k = coord - vec3(base);
+
+		vec3 result = vec3(0.0, 0.0, 0.0);
+		for(int dz = 0; dz < 2; ++dz)
+		for(int dy = 0; dy < 2; ++dy)
+		for(int dx = 0; dx < 2; ++dx)
+		{
+			vec3 w = mix(vec3(1.0, 1.0, 1.0) - k, k, vec3(dx, dy, dz));
+			uint address = get_address(base + ivec3(dx, dy, dz));
+			vec3 value = use_electric_field ? electric_vecs[address].xyz : magnetic_vecs[address].xyz;
+			result += value * (w.x * w.y * w.z);
+		}
+		return result;
+	}
+
+	// Weight of node (cell center) with given coordinate for particle with given coordinate (cloud-in-cell shape).
+	float get_shape(float particle_coord, int node_coord)
+	{
+		return max(0.0, 1.0 - abs(particle_coord - float(node_coord)));
+	}
+
+	// Deposit currents through cell faces using charge-conserving method by Esirkepov.
+	// Particle should move less than one cell.
+	void deposit_current(vec3 old_position, vec3 new_position, float charge)
+	{
+		vec3 old_coord = old_position - vec3(0.5, 0.5, 0.5);
+		vec3 new_coord = new_position - vec3(0.5, 0.5, 0.5);
+		ivec3 base = ivec3(floor(old_coord)) - ivec3(1, 1, 1);
+
+		// Shape values in 4 nodes along each axis.
+		vec4 old_shape[3];
+		vec4 shape_delta[3];
+		for(int axis = 0; axis < 3; ++axis)
+		{
+			for(int i = 0; i < 4; ++i)
+			{
+				float s0 = get_shape(old_coord[axis], base[axis] + i);
+				old_shape[axis][i] = s0;
+				shape_delta[axis][i] = get_shape(new_coord[axis], base[axis] + i) - s0;
+			}
+		}
+
+		for(int axis = 0; axis < 3; ++axis)
+		{
+			int axis1 = (axis + 1) % 3;
+			int axis2 = (axis + 2) % 3;
+			for(int j = 0; j < 4; ++j)
+			for(int k = 0; k < 4; ++k)
+			{
+				float s01 = old_shape[axis1][j];
+				float ds1 = shape_delta[axis1][j];
+				float s02 = old_shape[axis2][k];
+				float ds2 = shape_delta[axis2][k];
+				float transverse_weight = s01 * s02 + 0.5 * ds1 * s02 + 0.5 * s01 * ds2 + ds1 * ds2 / 3.0;
+				if(transverse_weight == 0.0)
+				{
+					continue;
+				}
+
+				// Current through face between nodes "i" and "i + 1" is accumulated sum of shape changes.
+				float current = 0.0;
+				for(int i = 0; i < 3; ++i)
+				{
+					current -= charge / dt * shape_delta[axis][i] * transverse_weight;
+					if(current == 0.0)
+					{
+						continue;
+					}
+
+					ivec3 node = base;
+					node[axis] += i;
+					node[axis1] += j;
+					node[axis2] += k;
+					uint address = get_address(node) * 3u + uint(axis);
+					atomicAdd(face_currents[address], int(round(current * c_current_scale)));
+				}
+			}
+		}
+	}
+
+	void main()
+	{
+		uint particle_index = gl_GlobalInvocationID.x;
+		if(particle_index >= num_particles)
+		{
+			return;
+		}
+
+		vec4 position_and_charge = particles[particle_index * 2u];
+		vec4 momentum_and_mass = particles[particle_index * 2u + 1u];
+		float charge = position_and_charge.w;
+		float mass = momentum_and_mass.w;
+		if(mass <= 0.0)
+		{
+			return;
+		}
+
+		vec3 position = position_and_charge.xyz;
+		vec3 momentum = momentum_and_mass.xyz;
+
+		// Particles are assumed to move in vacuum, where magnetic induction is equal to magnetic field.
+		vec3 electric_vec = interpolate_field(position, true);
+		vec3 magnetic_vec = interpolate_field(position, false);
+
+		// Relativistic Boris pusher. Momentum is per unit mass and speed of light is one.
+		float half_step_factor = 0.5 * dt * charge / mass;
+		vec3 momentum_minus = momentum + half_step_factor * electric_vec;
+		float gamma_minus = sqrt(1.0 + dot(momentum_minus, momentum_minus));
+		vec3 t = half_step_factor / gamma_minus * magnetic_vec;
+		vec3 s = 2.0 * t / (1.0 + dot(t, t));
+		vec3 momentum_prime = momentum_minus + cross(momentum_minus, t);
+		vec3 momentum_plus = momentum_minus + cross(momentum_prime, s);
+		vec3 new_momentum = momentum_plus + half_step_factor * electric_vec;
+
+		float gamma = sqrt(1.0 + dot(new_momentum, new_momentum));
+		vec3 new_position = position + new_momentum * (dt / gamma);
+
+		// Deactivate particles, leaving domain, after deposition of current up to border crossing.
+		vec3 domain_min = vec3(1.0, 1.0, 1.0);
+		vec3 domain_max = vec3(field_size) - vec3(1.0, 1.0, 1.0);
+		if(any(lessThan(new_position, domain_min)) || any(greaterThan(new_position, domain_max)))
+		{
+			// Fraction of step, passed before crossing of first border.
+			vec3 delta = new_position - position;
+			float fraction = 1.0;
+			for(int axis = 0; axis < 3; ++axis)
+			{
+				if(new_position[axis] < domain_min[axis])
+				{
+					fraction = min(fraction, (domain_min[axis] - position[axis]) / delta[axis]);
+				}
+				else if(new_position[axis] > domain_max[axis])
+				{
+					fraction = min(fraction, (domain_max[axis] - position[axis]) / delta[axis]);
+				}
+			}
+			deposit_current(position, position + delta * max(fraction, 0.0), charge);
+
+			particles[particle_index * 2u + 1u] = vec4(new_momentum, 0.0);
+			return;
+		}
+
+		deposit_current(position, new_position, charge);
+
+		particles[particle_index * 2u] = vec4(new_position, charge);
+		particles[particle_index * 2u + 1u] = vec4(new_momentum, mass);
+	}
+"#;
+
+const SHADER_CONVERT_CURRENT: &str = r#"
+	layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
+
+	// Convert face currents into current density in cell centers.
+	// Each component is averaged over two adjacent faces along its axis and smoothed along other axes.
+	// With such conversion central-difference divergence of current density is equal to change of smoothed charge
+	// density, so Gauss's law is preserved.
+	void main()
+	{
+		ivec3 coord = ivec3(gl_GlobalInvocationID);
+		const float c_smooth_weights[3] = float[3](0.25, 0.5, 0.25);
+
+		vec3 result = vec3(0.0, 0.0, 0.0);
+		for(int axis = 0; axis < 3; ++axis)
+		{
+			int axis1 = (axis + 1) % 3;
+			int axis2 = (axis + 2) % 3;
+			float sum = 0.0;
+			for(int i = -1; i <= 0; ++i)
+			for(int j = -1; j <= 1; ++j)
+			for(int k = -1; k <= 1; ++k)
+			{
+				ivec3 node = coord;
+				node[axis] += i;
+				node[axis1] += j;
+				node[axis2] += k;
+				if(any(lessThan(node, ivec3(0, 0, 0))) || any(greaterThanEqual(node, ivec3(field_size))))
+				{
+					continue;
+				}
+				float weight = 0.5 * c_smooth_weights[j + 1] * c_smooth_weights[k + 1];
+				sum += weight * float(face_currents[get_address(node) * 3u + uint(axis)]);
+			}
+			result[axis] = sum / c_current_scale;
+		}
+
+		current_density_vecs[get_address(coord)] = vec4(result, 0.0);
+	}
+"#;
@@ -0,0 +1,288 @@
+use std::ops::{Add, AddAssign, Div, Mul, MulAssign, Neg, Sub, SubAssign};
+
+// Minimal complex number implementation for analysis code.
+#[derive(Copy, Clone, Default, PartialEq, Debug)]
+pub struct Complex
+{
+	pub re: f64,
+	pub im: f64,
+}
+
+impl Complex
+{
+	pub const ZERO: Complex = Complex { re: 0.0, im: 0.0 };
+	pub const ONE: Complex = Complex { re: 1.0, im: 0.0 };
+
+	pub fn new(re: f64, im: f64) -> Self
+	{
+		Self { re, im }
+	}
+
+	pub fn from_real(re: f64) -> Self
+	{
+		Self { re, im: 0.0 }
+	}
+
+	pub fn from_polar(magnitude: f64, angle: f64) -> Self
+	{
+		Self {
+			re: magnitude * angle.cos(),
+			im: magnitude * angle.sin(),
+		}
+	}
+
+	pub fn conj(&self) -> Self
+	{
+		Self {
+			re: self.re,
+			im: -self.im,
+		}
+	}
+
+	pub fn norm_sqr(&self) -> f64
+	{
+		self.re * self.re + self.im * self.im
+	}
+
+	pub fn abs(&self) -> f64
+	{
+		self.re.hypot(self.im)
+	}
+
+	pub fn arg(&self) -> f64
+	{
+		self.im.atan2(self.re)
+	}
+
+	pub fn ln(&self) -> Self
+	{
+		Self {
+			re: self.abs().ln(),
+			im: self.arg(),
+		}
+	}
+
+	pub fn scale(&self, s: f64) -> Self
+	{
+		Self {
+			re: self.re * s,
+			im: self.im * s,
+		}
+	}
+}
+
+impl Add for Complex
+{
+	type Output = Complex;
+	fn add(self, other: Complex) -> Complex
+	{
+		Complex::new(self.re + other.re, self.im + other.im)
+	}
+}
+
+impl Sub for Complex
+{
+	type Output = Complex;
+	fn sub(self, other: Complex) -> Complex
+	{
+		Complex::new(self.re - other.re, self.im - other.im)
+	}
+}
+
+impl Mul for Complex
+{
+	type Output = Complex;
+	fn mul(self, other: Complex) -> Complex
+	{
+		Complex::new(
+			self.re * other.re - self.im * other.im,
+			self.re * other.im + self.im * other.re,
+		)
+	}
+}
+
+impl Div for Complex
+{
+	type Output = Complex;
+	fn div(self, other: Complex) -> Complex
+	{
+		let inv_norm = 1.0 / other.norm_sqr();
+		Complex::new(
+			(self.re * other.re + self.im * other.im) * inv_norm,
+			(self.im * other.re - self.re * other.im) * inv_norm,
+		)
+	}
+}
+
+impl Neg for Complex
+{
+	type Output = Complex;
+	fn neg(self) -> Complex
+	{
+		Complex::new(-self.re, -self.im)
+	}
+}
+
+impl AddAssign for Complex
+{
+	fn add_assign(&mut self, other: Complex)
+	{
+		*self = *self + other;
+	}
+}
+
+impl SubAssign for Complex
+{
+	fn sub_assign(&mut self, other: Complex)
+	{
+		*self = *self - other;
+	}
+}
+
+impl MulAssign for Complex
+{
+	fn mul_assign(&mut self, other: Complex)
+	{
+		*self = *self * other;
+	}
+}
+
+// Solve linear system "matrix * x = rhs" using Gaussian elimination with partial pivoting.
+// Returns None if matrix is singular.
+pub fn solve_linear_system(mut matrix: Vec<Vec<Complex>>, mut rhs: Vec<Complex>) -> Option<Vec<Complex>>
+{
+	let n = rhs.len();
+	assert_eq!(matrix.len(), n);
+
+	for column in 0 .. n
+	{
+		let pivot_row = (column .. n).max_by(|&a, &b| {
+			matrix[a][column]
+				.norm_sqr()
+				.partial_cmp(&matrix[b][column].norm_sqr())
+				.unwrap_or(std::cmp::Ordering::Equal)
+		})?;
+		if matrix[pivot_row][column].norm_sqr() == 0.0
+		{
+			return None;
+		}
+		matrix.swap(column, pivot_row);
+		rhs.swap(column, pivot_row);
+
+		let inv_pivot = Complex::ONE / matrix[column][column];
+		let (upper_rows, lower_rows) = matrix.split_at_mut(column + 1);
+		let pivot_row = &upper_rows[column];
+		for (i, row) in lower_rows.iter_mut().enumerate()
+		{
+			let factor = row[column] * inv_pivot;
+			if factor == Complex::ZERO
+			{
+				continue;
+			}
+			for (dst, src) in row[column ..].iter_mut().zip(pivot_row[column ..].iter())
+			{
+				*dst -= factor * *src;
+			}
+			let v = rhs[column];
+			rhs[column + 1 + i] -= factor * v;
+		}
+	}
+
+	let mut result = vec![Complex::ZERO; n];
+	for row in (0 .. n).rev()
+	{
+		let mut sum = rhs[row];
+		for k in row + 1 .. n
+		{
+			sum -= matrix[row][k] * result[k];
+		}
+		result[row] = sum / matrix[row][row];
+	}
+
+	Some(result)
+}
+
+// Solve overdetermined system "matrix * x = rhs" in least squares sense using normal equations.
+// Small Tikhonov regularization is used in order to handle nearly singular systems.
+pub fn solve_least_squares(matrix: &[Vec<Complex>], rhs: &[Complex], regularization: f64) -> Option<Vec<Complex>>
+{
+	assert_eq!(matrix.len(), rhs.len());
+	let n = matrix.first().map(|row| row.len()).unwrap_or(0);
+
+	let mut normal_matrix = vec![vec![Complex::ZERO; n]; n];
+	let mut normal_rhs = vec![Complex::ZERO; n];
+	for (row, r) in matrix.iter().zip(rhs.iter())
+	{
+		for i in 0 .. n
+		{
+			let c = row[i].conj();
+			for j in 0 .. n
+			{
+				normal_matrix[i][j] += c * row[j];
+			}
+			normal_rhs[i] += c * *r;
+		}
+	}
+
+	let trace = (0 .. n).map(|i| normal_matrix[i][i].re).sum::<f64>();
+	let regularization_value = regularization * trace / (n.max(1) as f64);
+	for (i, row) in normal_matrix.iter_mut().enumerate()
+	{
+		row[i].re += regularization_value;
+	}
+
+	solve_linear_system(normal_matrix, normal_rhs)
+}
+
+// Find all roots of polynomial with given coefficients (starting from highest power) using Durand-Kerner method.
+pub fn find_polynomial_roots(coefficients: &[Complex]) -> Vec<Complex>
+{
+	assert!(!coefficients.is_empty());
+	let degree = coefficients.len() - 1;
+	if degree == 0
+	{
+		return Vec::new();
+	}
+
+	// Make polynomial monic.
+	let leading = coefficients[0];
+	let monic = coefficients.iter().map(|c| *c / leading).collect::<Vec<_>>();
+	let evaluate = |z: Complex| monic.iter().fold(Complex::ZERO, |acc, c| acc * z + *c);
+
+	// Initial approximations are placed on a circle with radius equal to bound of roots magnitudes.
+	let radius = 1.0 + monic[1 ..].iter().map(|c| c.abs()).fold(0.0, f64::max);
+	let mut roots = (0 .. degree)
+		.map(|i| Complex::from_polar(radius, 2.0 * std::f64::consts::PI * (i as f64 + 0.25) / (degree as f64)))
+		.collect::<Vec<_>>();
+
+	const MAX_ITERATIONS: usize = 1000;
+	for _iteration in 0 .. MAX_ITERATIONS
+	{
+		let mut max_change = 0.0f64;
+		for i in 0 .. degree
+		{
+			let mut denominator = Complex::ONE;
+			for j in 0 .. degree
+			{
+				if i != j
+				{
+					denominator *= roots[i] - roots[j];
+				}
+			}
+			if denominator == Complex::ZERO
+			{
+				denominator = Complex::new(1.0e-12, 0.0);
+			}
+			let change = evaluate(roots[i]) / denominator;
+			roots[i] -= change;
+			max_change = max_change.max(change.abs() / roots[i].abs().max(1.0));
+		}
+
+		if max_change < 1.0e-14
+		{
+			break;
+		}
+	}
+
+	roots
+}
@@ -0,0 +1,380 @@
+use super::{electromagnetic_field_updater::*, grid_spacing::*, math_types::*, medium::*};
+
+// Electric and magnetic fields, stored on CPU side, in the same layout as data of vector fields.
+#[derive(Clone)]
+pub struct CpuElectromagneticField
+{
+	size: [u32; 3],
+	pub electric_field: Vec<[f32; 4]>,
+	pub magnetic_field: Vec<[f32; 4]>,
+}
+
+impl CpuElectromagneticField
+{
+	// Create zero field.
+	pub fn new(size: [u32; 3]) -> Self
+	{
+		let len = (size[0] * size[1] * size[2]) as usize;
+		Self {
+			size,
+			electric_field: vec![[0.0; 4]; len],
+			magnetic_field: vec![[0.0; 4]; len],
+		}
+	}
+
+	pub fn get_size(&self) -> [u32; 3]
+	{
+		self.size
+	}
+}
+
+// CPU implementation of field update, which is equivalent to ElectromagneticFieldUpdater.
+// Only linear isotropic non-dispersive media are supported, plane wave sources, lumped elements and thin wires are not
+// supported.
+// It's much slower than GPU implementation, but doesn't require OpenGL context, so it's used for validation of
+// numerical scheme.
+pub struct CpuElectromagneticFieldUpdater
+{
+	size: [u32; 3],
+	grid_spacing: GridSpacing,
+	// (permittivity, permeability, conductivity, magnetic conductivity) for each material.
+	materials: Vec<[f32; 4]>,
+	material_ids: Vec<MaterialId>,
+	boundary_conditions: [BoundaryCondition; 3],
+	spatial_stencil: SpatialStencil,
+	// Box of cells, where second-order stencil is used, see "set_second_order_box".
+	second_order_box: Option<([u32; 3], [u32; 3])>,
+	simulation_mode: SimulationMode,
+	// Conductivity of absorbing layers (relative to medium permittivity or permeability) for each cell.
+	absorbing_layer_conductivities: Vec<f32>,
+}
+
+impl CpuElectromagneticFieldUpdater
+{
+	pub fn new(medium: &Medium, boundary_conditions: [BoundaryCondition; 3]) -> Self
+	{
+		assert!(
+			!medium.has_dispersive_materials() &&
+				!medium.has_nonlinear_materials() &&
+				!medium.has_anisotropic_materials(),
+			"CPU field updater supports only linear isotropic non-dispersive media"
+		);
+
+		let size = medium.get_size();
+		let grid_spacing = medium.get_grid_spacing();
+		let len = (size[0] * size[1] * size[2]) as usize;
+		let absorbing_layer_conductivities = (0 .. len)
+			.map(|address| {
+				get_absorbing_layer_conductivity(grid_spacing, &boundary_conditions, get_coord(size, address))
+			})
+			.collect();
+
+		Self {
+			size,
+			grid_spacing: grid_spacing.clone(),
+			materials: medium
+				.get_materials()
+				.iter()
+				.map(|m| [m.permittivity, m.permeability, m.conductivity, m.magnetic_conductivity])
+				.collect(),
+			material_ids: medium.get_material_ids().to_vec(),
+			boundary_conditions,
+			spatial_stencil: SpatialStencil::SecondOrder,
+			second_order_box: None,
+			simulation_mode: SimulationMode::Full,
+			absorbing_layer_conductivities,
+		}
+	}
+
+	pub fn set_spatial_stencil(&mut self, spatial_stencil: SpatialStencil)
+	{
+		self.spatial_stencil = spatial_stencil;
+	}
+
+	pub fn get_spatial_stencil(&self) -> SpatialStencil
+	{
+		self.spatial_stencil
+	}
+
+	// Use second-order stencil in box [min; max) of cells, even if fourth-order stencil is enabled.
+	pub fn set_second_order_box(&mut self, second_order_box: Option<([u32; 3], [u32; 3])>)
+	{
+		self.second_order_box = second_order_box;
+	}
+
+	pub fn get_second_order_box(&self) -> Option<([u32; 3], [u32; 3])>
+	{
+		self.second_order_box
+	}
+
+	pub fn set_simulation_mode(&mut self, simulation_mode: SimulationMode)
+	{
+		simulation_mode.validate(self.size, &self.boundary_conditions);
+		self.simulation_mode = simulation_mode;
+	}
+
+	// Perform single update step. Magnetic field is considered to be half step ahead.
+	pub fn update(&self, field: &mut CpuElectromagneticField, time_delta_s: f32)
+	{
+		self.update_with_current_density(field, None, time_delta_s);
+	}
+
+	// Perform update step with external current density, which should be given at half step.
+	pub fn update_with_current_density(
+		&self,
+		field: &mut CpuElectromagneticField,
+		current_density: Option<&[[f32; 4]]>,
+		time_delta_s: f32,
+	)
+	{
+		self.update_electric_field(field, current_density, time_delta_s);
+		self.update_magnetic_field(field, time_delta_s);
+	}
+
+	// Perform only electric field part of update step. Together with following "update_magnetic_field" call it's
+	// equivalent to "update_with_current_density".
+	pub fn update_electric_field(
+		&self,
+		field: &mut CpuElectromagneticField,
+		current_density: Option<&[[f32; 4]]>,
+		time_delta_s: f32,
+	)
+	{
+		assert_eq!(field.get_size(), self.size);
+		if let Some(current_density) = current_density
+		{
+			assert_eq!(current_density.len(), field.electric_field.len());
+		}
+
+		let dt = time_delta_s;
+		let (component_mask, _) = self.simulation_mode.get_component_masks();
+
+		// Electric field update reads only magnetic field, so it may be performed in place.
+		for address in 0 .. field.electric_field.len()
+		{
+			let coord = get_coord(self.size, address);
+			let curl = self.calculate_curl(&field.magnetic_field, coord);
+
+			let material = self.materials[self.material_ids[address] as usize];
+			let permittivity = material[0];
+			let conductivity = material[2] + self.absorbing_layer_conductivities[address] * permittivity;
+
+			// Use semi-implicit scheme for conductivity.
+			let half_loss = 0.5 * conductivity * dt;
+			let self_factor = (permittivity - half_loss) / (permittivity + half_loss);
+			let inverse_factor = 1.0 / (permittivity + half_loss);
+
+			let mut delta = curl * dt;
+			if let Some(current_density) = current_density
+			{
+				let j = current_density[address];
+				delta -= Vec3f::new(j[0], j[1], j[2]) * dt;
+			}
+
+			let e = field.electric_field[address];
+			let new_electric_vec = Vec3f::new(e[0], e[1], e[2]) * self_factor + delta * inverse_factor;
+			// Components, which aren't used in reduced simulation mode, are kept zero.
+			field.electric_field[address] = new_electric_vec
+				.mul_element_wise(Vec3f::from(component_mask))
+				.extend(0.0)
+				.into();
+		}
+	}
+
+	pub fn update_magnetic_field(&self, field: &mut CpuElectromagneticField, time_delta_s: f32)
+	{
+		assert_eq!(field.get_size(), self.size);
+
+		let dt = time_delta_s;
+		let (_, component_mask) = self.simulation_mode.get_component_masks();
+		for address in 0 .. field.magnetic_field.len()
+		{
+			let coord = get_coord(self.size, address);
+			let curl = self.calculate_curl(&field.electric_field, coord);
+
+			let material = self.materials[self.material_ids[address] as usize];
+			let permeability = material[1];
+			// Absorbing layer is matched - both conductivities are scaled in order to keep layer impedance equal to
+			// impedance of medium.
+			let magnetic_conductivity = material[3] + self.absorbing_layer_conductivities[address] * permeability;
+
+			let half_loss = 0.5 * magnetic_conductivity * dt;
+			let self_factor = (permeability - half_loss) / (permeability + half_loss);
+			let inverse_factor = 1.0 / (permeability + half_loss);
+
+			let h = field.magnetic_field[address];
+			let new_magnetic_vec = Vec3f::new(h[0], h[1], h[2]) * self_factor - curl * (dt * inverse_factor);
+			field.magnetic_field[address] = new_magnetic_vec
+				.mul_element_wise(Vec3f::from(component_mask))
+				.extend(0.0)
+				.into();
+		}
+	}
+
+	// Wrap coordinates along periodic axes.
+	fn wrap_coord(&self, coord: [i32; 3]) -> [i32; 3]
+	{
+		let mut result = coord;
+		for i in 0 .. 3
+		{
+			if self.boundary_conditions[i] == BoundaryCondition::Periodic
+			{
+				let size = self.size[i] as i32;
+				result[i] = (coord[i] + size) % size;
+			}
+		}
+		result
+	}
+
+	// Get address of cell, coordinates outside field are clamped.
+	fn get_address(&self, coord: [i32; 3]) -> usize
+	{
+		let c = self.wrap_coord(coord);
+		let c = [0, 1, 2].map(|i| c[i].clamp(0, self.size[i] as i32 - 1) as u32);
+		(c[0] + c[1] * self.size[0] + c[2] * (self.size[0] * self.size[1])) as usize
+	}
+
+	// Calculate curl of given field using central differences in cell index space, divided by cell sizes.
+	// Fourth-order correction is faded out near non-periodic borders and second-order box (see
+	// "FOURTH_ORDER_FADE_WIDTH").
+	fn calculate_curl(&self, data: &[[f32; 4]], coord: [i32; 3]) -> Vec3f
+	{
+		// Difference of neighbors, multiplied by their weights of fourth-order correction.
+		let get_difference = |axis: usize, offset: i32, weighted: bool| {
+			let mut coord_minus = coord;
+			coord_minus[axis] -= offset;
+			let mut coord_plus = coord;
+			coord_plus[axis] += offset;
+			let [weight_minus, weight_plus] = if weighted
+			{
+				[coord_minus, coord_plus].map(|c| self.get_fourth_order_weight(self.wrap_coord(c), axis))
+			}
+			else
+			{
+				[1.0; 2]
+			};
+			let v_minus = data[self.get_address(coord_minus)];
+			let v_plus = data[self.get_address(coord_plus)];
+			Vec3f::new(v_plus[0], v_plus[1], v_plus[2]) * weight_plus -
+				Vec3f::new(v_minus[0], v_minus[1], v_minus[2]) * weight_minus
+		};
+
+		let mut derivatives = [Vec3f::zero(); 3];
+		for (axis, derivative) in derivatives.iter_mut().enumerate()
+		{
+			let mut index_space_derivative = get_difference(axis, 1, false) * 0.5;
+			let weight = if self.spatial_stencil == SpatialStencil::FourthOrder
+			{
+				self.get_fourth_order_weight(coord, axis)
+			}
+			else
+			{
+				0.0
+			};
+			// Cells with non-zero weight and their neighbors with non-zero weights are inside field.
+			if weight > 0.0
+			{
+				index_space_derivative += (get_difference(axis, 1, true) * (1.0 / 6.0) -
+					get_difference(axis, 2, true) * (1.0 / 12.0)) *
+					weight;
+			}
+			*derivative = index_space_derivative / self.grid_spacing.get_spacing(axis, coord[axis] as u32);
+		}
+
+		Vec3f::new(
+			derivatives[1].z - derivatives[2].y,
+			derivatives[2].x - derivatives[0].z,
+			derivatives[0].y - derivatives[1].x,
+		)
+	}
+
+	// Weight of fourth-order correction of derivative along given axis for given cell, see "FOURTH_ORDER_FADE_WIDTH".
+	fn get_fourth_order_weight(&self, coord: [i32; 3], axis: usize) -> f32
+	{
+		// Distance from border cells, where weight is zero.
+		let mut distance = if self.boundary_conditions[axis] == BoundaryCondition::Periodic
+		{
+			FOURTH_ORDER_FADE_WIDTH
+		}
+		else
+		{
+			coord[axis].min(self.size[axis] as i32 - 1 - coord[axis]) - 2
+		};
+		if let Some((box_min, box_max)) = self.second_order_box
+		{
+			let box_distance = (0 .. 3)
+				.map(|i| (box_min[i] as i32 - coord[i]).max(coord[i] + 1 - box_max[i] as i32))
+				.max()
+				.unwrap();
+			distance = distance.min(box_distance);
+		}
+		get_fourth_order_weight(distance)
+	}
+}
+
+fn get_coord(size: [u32; 3], address: usize) -> [i32; 3]
+{
+	let address = address as u32;
+	[
+		(address % size[0]) as i32,
+		(address / size[0] % size[1]) as i32,
+		(address / (size[0] * size[1])) as i32,
+	]
+}
+
+// Calculate conductivity of absorbing layer (relative to medium permittivity or permeability).
+// Depth and width of layer are measured in length units, between cell centers.
+fn get_absorbing_layer_conductivity(
+	grid_spacing: &GridSpacing,
+	boundary_conditions: &[BoundaryCondition; 3],
+	coord: [i32; 3],
+) -> f32
+{
+	let size = grid_spacing.get_size();
+	let mut result = 0.0;
+	for i in 0 .. 3
+	{
+		let width = if let BoundaryCondition::Absorbing { width } = boundary_conditions[i]
+		{
+			width
+		}
+		else
+		{
+			continue;
+		};
+		if width == 0
+		{
+			continue;
+		}
+		let center = |index: u32| grid_spacing.get_center(i, index);
+		let position = center(coord[i] as u32);
+		let last = size[i] - 1;
+		let (depth, layer_width) = if coord[i] < width as i32
+		{
+			let inner = center(width.min(last));
+			(inner - position, inner - center(0))
+		}
+		else
+		{
+			let inner = center(last.saturating_sub(width));
+			(position - inner, center(last) - inner)
+		};
+		result += get_graded_conductivity(depth, layer_width);
+	}
+	result
+}
+
+// Conductivity of absorbing layer with given width at given depth inside it (zero outside of layer).
+pub fn get_graded_conductivity(depth: f32, layer_width: f32) -> f32
+{
+	// Use cubic grading of conductivity, maximum value is chosen for reflection coefficient about 1e-6.
+	const GRADING_POWER: f32 = 3.0;
+	const LOG_REFLECTION: f32 = 13.8;
+
+	if depth <= 0.0
+	{
+		return 0.0;
+	}
+	let max_conductivity = (GRADING_POWER + 1.0) * LOG_REFLECTION / (2.0 * layer_width);
+	max_conductivity * (depth / layer_width).powf(GRADING_POWER)
+}
@@ -0,0 +1,359 @@
+use super::{
+	cpu_electromagnetic_field_updater::*, electromagnetic_field_updater::*, math_types::*, medium::*, subgrid::*,
+};
+
+// CPU implementation of subgrid, which is equivalent to Subgrid. It's used for validation of coupling scheme.
+pub struct CpuSubgrid
+{
+	params: SubgridParams,
+	coarse_size: [u32; 3],
+	// Two values for each cell of region, see "SubgridParams::make_interface_data".
+	interface_data: Vec<[f32; 4]>,
+	fine_field: CpuElectromagneticField,
+	fine_updater: CpuElectromagneticFieldUpdater,
+	// Coarse magnetic field of region and fine electric field before coarse magnetic field update.
+	previous_magnetic_field: Vec<[f32; 4]>,
+	previous_fine_electric_field: Vec<[f32; 4]>,
+}
+
+impl CpuSubgrid
+{
+	// Fine medium should be created with "SubgridParams::make_fine_medium".
+	pub fn new(
+		params: SubgridParams,
+		coarse_medium: &Medium,
+		coarse_boundary_conditions: [BoundaryCondition; 3],
+		fine_medium: &Medium,
+	) -> Self
+	{
+		let coarse_size = coarse_medium.get_size();
+		params.validate(coarse_size);
+		let fine_size = params.get_fine_size();
+		assert_eq!(fine_medium.get_size(), fine_size);
+
+		let region_size = params.get_region_size();
+		let region_cell_count = (region_size[0] * region_size[1] * region_size[2]) as usize;
+		let fine_cell_count = (fine_size[0] * fine_size[1] * fine_size[2]) as usize;
+
+		Self {
+			params,
+			coarse_size,
+			interface_data: params.make_interface_data(coarse_medium),
+			fine_field: CpuElectromagneticField::new(fine_size),
+			fine_updater: CpuElectromagneticFieldUpdater::new(
+				fine_medium,
+				params.get_fine_boundary_conditions(coarse_size, coarse_boundary_conditions),
+			),
+			previous_magnetic_field: vec![[0.0; 4]; region_cell_count],
+			previous_fine_electric_field: vec![[0.0; 4]; fine_cell_count],
+		}
+	}
+
+	// Perform coarse update step with given updater and external current density, which should be given at half step,
+	// and corresponding fine substeps.
+	pub fn update_with_current_density(
+		&mut self,
+		field: &mut CpuElectromagneticField,
+		updater: &CpuElectromagneticFieldUpdater,
+		current_density: Option<&[[f32; 4]]>,
+		time_delta_s: f32,
+	)
+	{
+		assert_eq!(field.get_size(), self.coarse_size);
+		self.params.check_coarse_spatial_stencil(updater.get_spatial_stencil(), updater.get_second_order_box());
+
+		let refinement = self.params.refinement;
+		let mut operations = CpuSubgridUpdate {
+			subgrid: self,
+			field,
+			updater,
+			current_density,
+		};
+		// CPU updater doesn't depend on time.
+		update_subgrid(&mut operations, refinement, 0.0, time_delta_s);
+	}
+
+	pub fn get_params(&self) -> &SubgridParams
+	{
+		&self.params
+	}
+
+	pub fn get_fine_field(&self) -> &CpuElectromagneticField
+	{
+		&self.fine_field
+	}
+
+	// Coarse update uses covered cells as neighbors of interface cells, replace them by fine cells of adjacent face.
+	fn correct_interface_field(
+		&self,
+		field: &mut CpuElectromagneticField,
+		source: SubgridFineField,
+		time_delta_s: f32,
+		weight: f32,
+	)
+	{
+		let magnetic = source != SubgridFineField::Magnetic;
+		let fine_data = match source
+		{
+			SubgridFineField::Electric => &self.fine_field.electric_field,
+			SubgridFineField::PreviousElectric => &self.previous_fine_electric_field,
+			SubgridFineField::Magnetic => &self.fine_field.magnetic_field,
+		};
+		let region_size = self.params.get_region_size();
+		for region_address in 0 .. self.previous_magnetic_field.len()
+		{
+			let region_coord = get_coord(region_size, region_address);
+			let (axis, side) = match self.get_covered_neighbor(region_coord)
+			{
+				Some(neighbor) => neighbor,
+				None => continue,
+			};
+
+			let coord = [0, 1, 2].map(|i| region_coord[i] + self.params.region_min[i]);
+			let mut covered_coord = coord;
+			covered_coord[axis] = (coord[axis] as i32 + side) as u32;
+			let (coarse_data, target) = if magnetic
+			{
+				(&field.electric_field, &mut field.magnetic_field)
+			}
+			else
+			{
+				(&field.magnetic_field, &mut field.electric_field)
+			};
+			let covered = coarse_data[get_address(self.coarse_size, covered_coord)];
+			let face_average = self.get_face_average(fine_data, region_coord, axis, side);
+
+			let material = self.interface_data[region_address * 2];
+			let spacing = self.interface_data[region_address * 2 + 1][axis];
+			// Electric field is updated by curl of magnetic field, magnetic field - by negative curl of electric field.
+			let inverse_factor = if magnetic
+			{
+				-1.0 / (material[1] + 0.5 * material[3] * time_delta_s)
+			}
+			else
+			{
+				1.0 / (material[0] + 0.5 * material[2] * time_delta_s)
+			};
+
+			let mut direction = Vec3f::zero();
+			direction[axis] = 1.0;
+			let derivative_delta =
+				(face_average - Vec3f::new(covered[0], covered[1], covered[2])) * (side as f32 * 0.5 / spacing);
+			let delta = direction.cross(derivative_delta) * (time_delta_s * inverse_factor * weight);
+
+			let value = &mut target[get_address(self.coarse_size, coord)];
+			for c in 0 .. 3
+			{
+				value[c] += delta[c];
+			}
+		}
+	}
+
+	fn store_previous_fields(&mut self, field: &CpuElectromagneticField)
+	{
+		let region_size = self.params.get_region_size();
+		let region_min = self.params.region_min;
+		for (region_address, value) in self.previous_magnetic_field.iter_mut().enumerate()
+		{
+			let region_coord = get_coord(region_size, region_address);
+			let coord = [0, 1, 2].map(|i| region_coord[i] + region_min[i]);
+			*value = field.magnetic_field[get_address(self.coarse_size, coord)];
+		}
+		self.previous_fine_electric_field
+			.copy_from_slice(&self.fine_field.electric_field);
+	}
+
+	// Covered cells are coarse cells inside of interface layer, field of them is average of fine cells.
+	fn restrict_covered_cells(&self, field: &mut CpuElectromagneticField)
+	{
+		let r = self.params.refinement;
+		let fine_size = self.params.get_fine_size();
+		let region_size = self.params.get_region_size();
+		let scale = 1.0 / (r * r * r) as f32;
+		for region_address in 0 .. self.previous_magnetic_field.len()
+		{
+			let region_coord = get_coord(region_size, region_address);
+			if !self.is_covered(region_coord)
+			{
+				continue;
+			}
+
+			let mut electric_sum = [0.0; 4];
+			let mut magnetic_sum = [0.0; 4];
+			for fine_offset_address in 0 .. (r * r * r) as usize
+			{
+				let fine_offset = get_coord([r; 3], fine_offset_address);
+				let fine_coord = [0, 1, 2].map(|i| region_coord[i] * r + fine_offset[i]);
+				let fine_address = get_address(fine_size, fine_coord);
+				let electric = self.fine_field.electric_field[fine_address];
+				let magnetic = self.fine_field.magnetic_field[fine_address];
+				for c in 0 .. 4
+				{
+					electric_sum[c] += electric[c];
+					magnetic_sum[c] += magnetic[c];
+				}
+			}
+
+			let coord = [0, 1, 2].map(|i| region_coord[i] + self.params.region_min[i]);
+			let address = get_address(self.coarse_size, coord);
+			field.electric_field[address] = electric_sum.map(|s| s * scale);
+			field.magnetic_field[address] = magnetic_sum.map(|s| s * scale);
+		}
+	}
+
+	// Set fine cells of interface layer to field of coarse cell, which contains them: electric field after step and
+	// magnetic field at the same time, which is average of magnetic field before and after step.
+	// Only fine cells adjacent to covered cells are used by fine update, other ones are set for visualization.
+	fn set_border_fields(&mut self, field: &CpuElectromagneticField)
+	{
+		let r = self.params.refinement;
+		let fine_size = self.params.get_fine_size();
+		let region_size = self.params.get_region_size();
+		let coupled_axes = self.params.get_coupled_axes(self.coarse_size);
+		for fine_address in 0 .. self.fine_field.electric_field.len()
+		{
+			let fine_coord = get_coord(fine_size, fine_address);
+			if (0 .. 3).all(|i| !coupled_axes[i] || (fine_coord[i] >= r && fine_coord[i] < fine_size[i] - r))
+			{
+				continue;
+			}
+
+			let region_coord = fine_coord.map(|c| c / r);
+			let address = get_address(
+				self.coarse_size,
+				[0, 1, 2].map(|i| region_coord[i] + self.params.region_min[i]),
+			);
+			let previous_magnetic = self.previous_magnetic_field[get_address(region_size, region_coord)];
+			let magnetic = field.magnetic_field[address];
+			self.fine_field.electric_field[fine_address] = field.electric_field[address];
+			self.fine_field.magnetic_field[fine_address] =
+				[0, 1, 2, 3].map(|c| 0.5 * (previous_magnetic[c] + magnetic[c]));
+		}
+	}
+
+	// Average of fine cells of covered cell, adjacent to given interface cell.
+	fn get_face_average(&self, fine_data: &[[f32; 4]], region_coord: [u32; 3], axis: usize, side: i32) -> Vec3f
+	{
+		let r = self.params.refinement;
+		let fine_size = self.params.get_fine_size();
+		let mut sum = Vec3f::zero();
+		for fine_offset_address in 0 .. (r * r) as usize
+		{
+			let fine_offset = get_coord([r, r, 1], fine_offset_address);
+			let mut fine_coord = [0; 3];
+			fine_coord[axis] = if side > 0 { r } else { fine_size[axis] - r - 1 };
+			fine_coord[(axis + 1) % 3] = region_coord[(axis + 1) % 3] * r + fine_offset[0];
+			fine_coord[(axis + 2) % 3] = region_coord[(axis + 2) % 3] * r + fine_offset[1];
+			let value = fine_data[get_address(fine_size, fine_coord)];
+			sum += Vec3f::new(value[0], value[1], value[2]);
+		}
+		sum / ((r * r) as f32)
+	}
+
+	fn is_covered(&self, region_coord: [u32; 3]) -> bool
+	{
+		let coord = [0, 1, 2].map(|i| region_coord[i] + self.params.region_min[i]);
+		self.params.is_covered(self.coarse_size, coord)
+	}
+
+	// Interface cell is cell of interface layer with covered neighbor. Returns axis and direction of this neighbor.
+	fn get_covered_neighbor(&self, region_coord: [u32; 3]) -> Option<(usize, i32)>
+	{
+		let region_size = self.params.get_region_size();
+		let coupled_axes = self.params.get_coupled_axes(self.coarse_size);
+		for axis in (0 .. 3).filter(|i| coupled_axes[*i])
+		{
+			let mut neighbor_coord = region_coord;
+			if region_coord[axis] == 0
+			{
+				neighbor_coord[axis] += 1;
+				if self.is_covered(neighbor_coord)
+				{
+					return Some((axis, 1));
+				}
+			}
+			else if region_coord[axis] + 1 == region_size[axis]
+			{
+				neighbor_coord[axis] -= 1;
+				if self.is_covered(neighbor_coord)
+				{
+					return Some((axis, -1));
+				}
+			}
+		}
+		None
+	}
+}
+
+// Operations of single update step, performed on coarse field with given updater.
+struct CpuSubgridUpdate<'a>
+{
+	subgrid: &'a mut CpuSubgrid,
+	field: &'a mut CpuElectromagneticField,
+	updater: &'a CpuElectromagneticFieldUpdater,
+	current_density: Option<&'a [[f32; 4]]>,
+}
+
+impl SubgridOperations for CpuSubgridUpdate<'_>
+{
+	fn update_coarse_electric_field(&mut self, _time: f32, time_delta_s: f32)
+	{
+		self.updater
+			.update_electric_field(self.field, self.current_density, time_delta_s);
+	}
+
+	fn update_coarse_magnetic_field(&mut self, _electric_time: f32, time_delta_s: f32)
+	{
+		self.updater.update_magnetic_field(self.field, time_delta_s);
+	}
+
+	fn update_fine_electric_field(&mut self, _time: f32, time_delta_s: f32)
+	{
+		self.subgrid
+			.fine_updater
+			.update_electric_field(&mut self.subgrid.fine_field, None, time_delta_s);
+	}
+
+	fn update_fine_magnetic_field(&mut self, _electric_time: f32, time_delta_s: f32)
+	{
+		self.subgrid
+			.fine_updater
+			.update_magnetic_field(&mut self.subgrid.fine_field, time_delta_s);
+	}
+
+	fn correct_interface_field(&mut self, source: SubgridFineField, time_delta_s: f32, weight: f32)
+	{
+		self.subgrid
+			.correct_interface_field(self.field, source, time_delta_s, weight);
+	}
+
+	fn store_previous_fields(&mut self)
+	{
+		self.subgrid.store_previous_fields(self.field);
+	}
+
+	fn set_border_fields(&mut self)
+	{
+		self.subgrid.set_border_fields(self.field);
+	}
+
+	fn restrict_covered_cells(&mut self)
+	{
+		self.subgrid.restrict_covered_cells(self.field);
+	}
+}
+
+fn get_coord(size: [u32; 3], address: usize) -> [u32; 3]
+{
+	let address = address as u32;
+	[
+		address % size[0],
+		address / size[0] % size[1],
+		address / (size[0] * size[1]),
+	]
+}
+
+fn get_address(size: [u32; 3], coord: [u32; 3]) -> usize
+{
+	(coord[0] + coord[1] * size[0] + coord[2] * (size[0] * size[1])) as usize
+}
@@ -0,0 +1,385 @@
+use super::{
+	complex_number::*, dft_field_monitor::*, electromagnetic_field::*, electromagnetic_field_updater::*, math_types::*,
+	medium::*, near_to_far_field::*, plane_wave_source::*, vector_field::*, waveform::*,
+};
+use std::io::Write;
+
+// Setup for calculation of scattering, absorption and extinction cross sections of an object.
+// Object is illuminated by plane wave, injected via total-field/scattered-field box.
+// Absorbed power is net power flowing into closed box inside total field region around object, scattered power is
+// net power flowing out of closed box in scattered field region (outside plane wave source box).
+pub struct CrossSectionSetup
+{
+	pub field_size: [u32; 3],
+	pub medium: Medium,
+	pub source: PlaneWaveSource,
+	pub absorption_box_min: [u32; 3],
+	pub absorption_box_max: [u32; 3],
+	pub scattering_box_min: [u32; 3],
+	pub scattering_box_max: [u32; 3],
+	pub absorbing_layer_width: u32,
+	pub spatial_stencil: SpatialStencil,
+	pub frequencies: Vec<f32>,
+	pub time_step: f32,
+	pub num_steps: u32,
+}
+
+// Cross sections (in cells squared) for each frequency.
+pub struct CrossSections
+{
+	pub frequencies: Vec<f32>,
+	pub scattering: Vec<f64>,
+	pub absorption: Vec<f64>,
+	pub extinction: Vec<f64>,
+}
+
+// Homogeneous sphere in vacuum, for which cross sections are known exactly (Mie solution).
+pub struct MieSphere
+{
+	pub radius: f64,
+	pub permittivity: f32,
+	pub conductivity: f32,
+}
+
+impl MieSphere
+{
+	// Complex permittivity for "exp(-i * w * t)" time dependence.
+	pub fn get_permittivity(&self, frequency: f32) -> Complex
+	{
+		let angular_frequency = 2.0 * std::f64::consts::PI * (frequency as f64);
+		Complex::new(self.permittivity as f64, (self.conductivity as f64) / angular_frequency)
+	}
+
+	pub fn calculate_cross_sections(&self, frequencies: &[f32]) -> CrossSections
+	{
+		let mut result = CrossSections {
+			frequencies: frequencies.to_vec(),
+			scattering: Vec::with_capacity(frequencies.len()),
+			absorption: Vec::with_capacity(frequencies.len()),
+			extinction: Vec::with_capacity(frequencies.len()),
+		};
+		for frequency in frequencies
+		{
+			let (scattering, extinction) =
+				calculate_mie_cross_sections(self.radius, self.get_permittivity(*frequency), *frequency as f64);
+			result.scattering.push(scattering);
+			result.absorption.push(extinction - scattering);
+			result.extinction.push(extinction);
+		}
+		result
+	}
+}
+
+// Run automatically reference simulation (with empty domain) in order to get incident wave intensity and device
+// simulation in order to get scattered and absorbed power.
+pub fn compute_cross_sections(display: &glium::Display, setup: &CrossSectionSetup) -> CrossSections
+{
+	let reference_result = run_simulation(display, setup, &setup.medium.make_vacuum_copy());
+	let device_result = run_simulation(display, setup, &setup.medium);
+
+	// Incident wave intensity - time-averaged Poynting vector component along propagation direction.
+	let incident_intensity = reference_result
+		.incident_samples
+		.iter()
+		.map(|s| {
+			let electric = s.electric.map(|c| Complex::new(c[0] as f64, c[1] as f64));
+			let magnetic = s.magnetic.map(|c| Complex::new(c[0] as f64, c[1] as f64));
+			(0 .. 3)
+				.map(|i| {
+					let b = (i + 1) % 3;
+					let c = (i + 2) % 3;
+					let poynting_vector =
+						0.5 * (electric[b] * magnetic[c].conj() - electric[c] * magnetic[b].conj()).re;
+					poynting_vector * (setup.source.direction[i] as f64)
+				})
+				.sum::<f64>()
+		})
+		.collect::<Vec<_>>();
+
+	let normalize = |power: f64, intensity: f64| if intensity != 0.0 { power / intensity } else { 0.0 };
+
+	let mut result = CrossSections {
+		frequencies: setup.frequencies.clone(),
+		scattering: Vec::with_capacity(setup.frequencies.len()),
+		absorption: Vec::with_capacity(setup.frequencies.len()),
+		extinction: Vec::with_capacity(setup.frequencies.len()),
+	};
+	for (i, intensity) in incident_intensity.iter().enumerate()
+	{
+		let scattering = normalize(device_result.scattered_power[i], *intensity);
+		// Absorbed power flows into absorption box.
+		let absorption = normalize(-device_result.absorption_box_power[i], *intensity);
+		result.scattering.push(scattering);
+		result.absorption.push(absorption);
+		result.extinction.push(scattering + absorption);
+	}
+	result
+}
+
+// Write simulated cross sections, optionally along with reference values.
+pub fn write_cross_sections_table(
+	cross_sections: &CrossSections,
+	reference: Option<&CrossSections>,
+	file_path: &std::path::Path,
+) -> std::io::Result<()>
+{
+	let mut file = std::io::BufWriter::new(std::fs::File::create(file_path)?);
+	write!(file, "frequency,scattering,absorption,extinction")?;
+	if reference.is_some()
+	{
+		write!(file, ",reference_scattering,reference_absorption,reference_extinction")?;
+	}
+	writeln!(file)?;
+
+	for i in 0 .. cross_sections.frequencies.len()
+	{
+		write!(
+			file,
+			"{},{},{},{}",
+			cross_sections.frequencies[i],
+			cross_sections.scattering[i],
+			cross_sections.absorption[i],
+			cross_sections.extinction[i]
+		)?;
+		if let Some(reference) = reference
+		{
+			write!(
+				file,
+				",{},{},{}",
+				reference.scattering[i], reference.absorption[i], reference.extinction[i]
+			)?;
+		}
+		writeln!(file)?;
+	}
+	file.flush()
+}
+
+// Calculate (scattering, extinction) cross sections of sphere with given radius and complex permittivity, using
+// Mie series (algorithm of Bohren and Huffman).
+pub fn calculate_mie_cross_sections(radius: f64, permittivity: Complex, frequency: f64) -> (f64, f64)
+{
+	let x = 2.0 * std::f64::consts::PI * frequency * radius;
+	// Refractive index is principal square root of permittivity.
+	let m = Complex::from_polar(permittivity.abs().sqrt(), 0.5 * permittivity.arg());
+	let mx = m.scale(x);
+
+	let num_terms = (x + 4.0 * x.cbrt() + 2.0).ceil() as usize;
+	let num_log_derivatives = num_terms.max(mx.abs().ceil() as usize) + 16;
+
+	// Logarithmic derivative of Riccati-Bessel function for argument "m * x", calculated by downward recurrence.
+	let mut log_derivatives = vec![Complex::ZERO; num_log_derivatives + 1];
+	for n in (1 ..= num_log_derivatives).rev()
+	{
+		let n_over_mx = Complex::from_real(n as f64) / mx;
+		log_derivatives[n - 1] = n_over_mx - Complex::ONE / (log_derivatives[n] + n_over_mx);
+	}
+
+	// Riccati-Bessel functions "psi" and "chi" for real argument, calculated by upward recurrence.
+	let mut psi_prev = x.cos();
+	let mut psi_cur = x.sin();
+	let mut chi_prev = -x.sin();
+	let mut chi_cur = x.cos();
+
+	let mut scattering_sum = 0.0;
+	let mut extinction_sum = 0.0;
+	for (n, log_derivative) in log_derivatives.iter().enumerate().take(num_terms + 1).skip(1)
+	{
+		let n_f = n as f64;
+		let psi = (2.0 * n_f - 1.0) * psi_cur / x - psi_prev;
+		let chi = (2.0 * n_f - 1.0) * chi_cur / x - chi_prev;
+		let xi = Complex::new(psi, -chi);
+		let xi_cur = Complex::new(psi_cur, -chi_cur);
+
+		let n_over_x = Complex::from_real(n_f / x);
+		let a_factor = *log_derivative / m + n_over_x;
+		let b_factor = m * *log_derivative + n_over_x;
+		let a = (a_factor.scale(psi) - Complex::from_real(psi_cur)) / (a_factor * xi - xi_cur);
+		let b = (b_factor.scale(psi) - Complex::from_real(psi_cur)) / (b_factor * xi - xi_cur);
+
+		scattering_sum += (2.0 * n_f + 1.0) * (a.norm_sqr() + b.norm_sqr());
+		extinction_sum += (2.0 * n_f + 1.0) * (a + b).re;
+
+		psi_prev = psi_cur;
+		psi_cur = psi;
+		chi_prev = chi_cur;
+		chi_cur = chi;
+	}
+
+	// Cross section is efficiency multiplied by geometric cross section.
+	let scale = 2.0 / (x * x) * std::f64::consts::PI * radius * radius;
+	(scattering_sum * scale, extinction_sum * scale)
+}
+
+// Lossy dielectric sphere, for which Mie solution is used as reference.
+pub fn create_test_mie_sphere_setup(spatial_stencil: SpatialStencil) -> (CrossSectionSetup, MieSphere)
+{
+	let field_size = [72, 72, 72];
+	let absorbing_layer_width = 12;
+	let radius = 8.0;
+	let permittivity = 4.0;
+	let conductivity = 0.02;
+
+	let center = Vec3f::new(field_size[0] as f32, field_size[1] as f32, field_size[2] as f32) * 0.5;
+	let mut medium = Medium::new(field_size);
+	let sphere_material_id = medium.add_material(Material {
+		conductivity,
+		..Material::dielectric(permittivity)
+	});
+	medium.fill_sphere(center, radius, sphere_material_id);
+
+	// Use radius of ball with same volume as staircased sphere.
+	let num_sphere_cells = medium
+		.get_material_ids()
+		.iter()
+		.filter(|id| **id == sphere_material_id)
+		.count();
+	let equivalent_radius = (3.0 * (num_sphere_cells as f64) / (4.0 * std::f64::consts::PI)).cbrt();
+
+	let make_box = |half_size: u32| {
+		(
+			[0, 1, 2].map(|i| field_size[i] / 2 - half_size),
+			[0, 1, 2].map(|i| field_size[i] / 2 + half_size),
+		)
+	};
+	let (source_box_min, source_box_max) = make_box(14);
+	let (absorption_box_min, absorption_box_max) = make_box(11);
+	let (scattering_box_min, scattering_box_max) = make_box(17);
+
+	let num_frequencies = 41;
+	let frequencies = (0 .. num_frequencies)
+		.map(|i| 0.01 + 0.05 * (i as f32) / ((num_frequencies - 1) as f32))
+		.collect();
+
+	let setup = CrossSectionSetup {
+		field_size,
+		medium,
+		source: PlaneWaveSource {
+			box_min: source_box_min,
+			box_max: source_box_max,
+			direction: Vec3f::unit_x(),
+			polarization: Vec3f::unit_z(),
+			amplitude: 1.0,
+			waveform: Waveform::modulated_gaussian_pulse(0.035, 12.0),
+		},
+		absorption_box_min,
+		absorption_box_max,
+		scattering_box_min,
+		scattering_box_max,
+		absorbing_layer_width,
+		spatial_stencil,
+		frequencies,
+		time_step: 0.5,
+		num_steps: 6000,
+	};
+	let sphere = MieSphere {
+		radius: equivalent_radius,
+		permittivity,
+		conductivity,
+	};
+	(setup, sphere)
+}
+
+struct SimulationResult
+{
+	// DFT of incident field in center of absorption box (meaningful only for empty medium).
+	incident_samples: Vec<DftFieldSample>,
+	absorption_box_power: Vec<f64>,
+	scattered_power: Vec<f64>,
+}
+
+fn run_simulation(display: &glium::Display, setup: &CrossSectionSetup, medium: &Medium) -> SimulationResult
+{
+	let mut field = ElectromagneticField {
+		electric_field: VectorField::new(display, setup.field_size),
+		magnetic_field: VectorField::new(display, setup.field_size),
+	};
+
+	let mut updater = ElectromagneticFieldUpdater::new(
+		display,
+		medium,
+		[BoundaryCondition::Absorbing {
+			width: setup.absorbing_layer_width,
+		}; 3],
+	);
+	updater.set_spatial_stencil(setup.spatial_stencil);
+	updater.set_plane_wave_source(display, Some(setup.source.clone()));
+
+	let mut incident_monitor = DftFieldMonitor::new(
+		display,
+		&DftFieldMonitorParams {
+			region_offset: [0, 1, 2].map(|i| (setup.absorption_box_min[i] + setup.absorption_box_max[i]) / 2),
+			region_size: [1, 1, 1],
+			frequencies: setup.frequencies.clone(),
+		},
+	);
+	let mut absorption_box = HuygensSurface::new(
+		display,
+		setup.absorption_box_min,
+		setup.absorption_box_max,
+		&setup.frequencies,
+		medium.get_grid_spacing(),
+	);
+	let mut scattering_box = HuygensSurface::new(
+		display,
+		setup.scattering_box_min,
+		setup.scattering_box_max,
+		&setup.frequencies,
+		medium.get_grid_spacing(),
+	);
+
+	let mut time = 0.0;
+	for _i in 0 .. setup.num_steps
+	{
+		updater.update(&mut field, time, setup.time_step);
+		time += setup.time_step;
+
+		incident_monitor.accumulate(&field, time, setup.time_step);
+		absorption_box.accumulate(&field, time, setup.time_step);
+		scattering_box.accumulate(&field, time, setup.time_step);
+	}
+
+	SimulationResult {
+		incident_samples: incident_monitor.read_samples(),
+		absorption_box_power: absorption_box.calculate_flux_spectrum(),
+		scattered_power: scattering_box.calculate_flux_spectrum(),
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn mie_rayleigh_limit()
+	{
+		// For sphere much smaller than wavelength cross sections are given by polarizability of sphere.
+		let radius = 0.01;
+		let frequency = 1.0;
+		let permittivity = Complex::new(4.0, 0.5);
+		let (scattering, extinction) = calculate_mie_cross_sections(radius, permittivity, frequency);
+
+		let k = 2.0 * std::f64::consts::PI * frequency;
+		let polarizability_factor = (permittivity - Complex::ONE) / (permittivity + Complex::from_real(2.0));
+		let expected_scattering =
+			8.0 / 3.0 * std::f64::consts::PI * k.powi(4) * radius.powi(6) * polarizability_factor.norm_sqr();
+		let expected_absorption = 4.0 * std::f64::consts::PI * k * radius.powi(3) * polarizability_factor.im;
+		let expected_extinction = expected_absorption + expected_scattering;
+
+		assert!((scattering / expected_scattering - 1.0).abs() < 0.01, "{} {}", scattering, expected_scattering);
+		assert!((extinction / expected_extinction - 1.0).abs() < 0.01, "{} {}", extinction, expected_extinction);
+	}
+
+	#[test]
+	fn mie_reference_values()
+	{
+		// Example from Bohren and Huffman: refractive index 1.55, radius 0.525, wavelength 0.6328 (size parameter
+		// 5.213), efficiencies of scattering and extinction are both 3.10543.
+		let radius = 0.525;
+		let (scattering, extinction) =
+			calculate_mie_cross_sections(radius, Complex::from_real(1.55 * 1.55), 1.0 / 0.6328);
+		let geometric_cross_section = std::f64::consts::PI * radius * radius;
+		assert!((scattering / geometric_cross_section - 3.10543).abs() < 1.0e-4, "{}", scattering);
+		assert!((extinction / geometric_cross_section - 3.10543).abs() < 1.0e-4, "{}", extinction);
+	}
+}
@@ -0,0 +1,302 @@
+use super::{electromagnetic_field::*, grid_spacing::*, vector_field::*};
+use std::io::Write;
+
+// Monitor, that accumulates running discrete Fourier transform of electric and magnetic field over some region.
+// Region may be a plane (with one size component equal to 1) or a volume.
+pub struct DftFieldMonitor
+{
+	region_offset: [u32; 3],
+	region_size: [u32; 3],
+	// Frequencies (not angular).
+	frequencies: Vec<f32>,
+	// For each frequency and each cell four vectors are stored - real and imaginary parts of electric and magnetic
+	// fields.
+	buffer: BufferType,
+	shader_accumulate: glium::program::ComputeShader,
+	shader_extract: glium::program::ComputeShader,
+}
+
+type BufferType = glium::buffer::Buffer<[[f32; 4]]>;
+
+#[derive(Clone)]
+pub struct DftFieldMonitorParams
+{
+	pub region_offset: [u32; 3],
+	pub region_size: [u32; 3],
+	pub frequencies: Vec<f32>,
+}
+
+// Complex field vector, components are stored as (real, imaginary) pairs.
+pub type ComplexVector = [[f32; 2]; 3];
+
+#[derive(Copy, Clone)]
+pub struct DftFieldSample
+{
+	pub electric: ComplexVector,
+	pub magnetic: ComplexVector,
+}
+
+impl DftFieldMonitor
+{
+	pub fn new(display: &glium::Display, params: &DftFieldMonitorParams) -> Self
+	{
+		let cell_count = get_cell_count(&params.region_size);
+		let data = vec![[0.0; 4]; cell_count * params.frequencies.len() * 4];
+
+		Self {
+			region_offset: params.region_offset,
+			region_size: params.region_size,
+			frequencies: params.frequencies.clone(),
+			buffer: glium::buffer::Buffer::new(
+				display,
+				&data[..],
+				glium::buffer::BufferType::ShaderStorageBuffer,
+				glium::buffer::BufferMode::Default,
+			)
+			.unwrap(),
+			shader_accumulate: glium::program::ComputeShader::from_source(display, SHADER_ACCUMULATE).unwrap(),
+			shader_extract: glium::program::ComputeShader::from_source(display, SHADER_EXTRACT).unwrap(),
+		}
+	}
+
+	pub fn get_region_offset(&self) -> [u32; 3]
+	{
+		self.region_offset
+	}
+
+	pub fn get_region_size(&self) -> [u32; 3]
+	{
+		self.region_size
+	}
+
+	pub fn get_frequencies(&self) -> &[f32]
+	{
+		&self.frequencies
+	}
+
+	// Should be called after each field update step.
+	// "time" is time of electric field after the step, magnetic field is considered to be half step ahead.
+	pub fn accumulate(&mut self, field: &ElectromagneticField, time: f32, time_delta_s: f32)
+	{
+		let field_size = field.electric_field.get_size();
+		for ((offset, size), field_size) in self.region_offset.iter().zip(&self.region_size).zip(&field_size)
+		{
+			assert!(offset + size <= *field_size);
+		}
+
+		let cell_count = get_cell_count(&self.region_size) as u32;
+
+		for (frequency_index, frequency) in self.frequencies.iter().enumerate()
+		{
+			let uniforms = glium::uniform! {
+				dt: time_delta_s,
+				electric_time: time,
+				magnetic_time: time + 0.5 * time_delta_s,
+				angular_frequency: frequency * (2.0 * std::f32::consts::PI),
+				data_offset: (frequency_index as u32) * cell_count * 4,
+				field_size: field_size,
+				region_offset: self.region_offset,
+				region_size: self.region_size,
+				electric_field_data: field.electric_field.get_buffer(),
+				magnetic_field_data: field.magnetic_field.get_buffer(),
+				dft_data: &self.buffer,
+			};
+
+			self.shader_accumulate
+				.execute(uniforms, self.region_size[0], self.region_size[1], self.region_size[2]);
+		}
+	}
+
+	// Reconstruct real field "Re(F * exp(i * phase))" for given frequency. Result fields have size of monitor region.
+	pub fn extract_field(
+		&self,
+		frequency_index: usize,
+		phase: f32,
+		scale: f32,
+		out_electric_field: &mut VectorField,
+		out_magnetic_field: &mut VectorField,
+	)
+	{
+		assert!(frequency_index < self.frequencies.len());
+		assert_eq!(out_electric_field.get_size(), self.region_size);
+		assert_eq!(out_magnetic_field.get_size(), self.region_size);
+
+		let cell_count = get_cell_count(&self.region_size) as u32;
+
+		let uniforms = glium::uniform! {
+			phase: phase,
+			scale: scale,
+			data_offset: (frequency_index as u32) * cell_count * 4,
+			region_size: self.region_size,
+			dft_data: &self.buffer,
+			electric_field_data: out_electric_field.get_buffer(),
+			magnetic_field_data: out_magnetic_field.get_buffer(),
+		};
+
+		self.shader_extract
+			.execute(uniforms, self.region_size[0], self.region_size[1], self.region_size[2]);
+	}
+
+	// Read accumulated data. Result contains samples for each frequency, for each cell inside each frequency.
+	pub fn read_samples(&self) -> Vec<DftFieldSample>
+	{
+		let data = self.buffer.read().unwrap();
+		data.chunks(4)
+			.map(|s| DftFieldSample {
+				electric: make_complex_vector(&s[0], &s[1]),
+				magnetic: make_complex_vector(&s[2], &s[3]),
+			})
+			.collect()
+	}
+
+	// Get maximum magnitude of electric field for given frequency. Useful for visualization normalization.
+	pub fn get_max_electric_field_magnitude(&self, samples: &[DftFieldSample], frequency_index: usize) -> f32
+	{
+		let cell_count = get_cell_count(&self.region_size);
+		samples[frequency_index * cell_count .. (frequency_index + 1) * cell_count]
+			.iter()
+			.map(|s| get_complex_vector_magnitude(&s.electric))
+			.fold(0.0, f32::max)
+	}
+
+	// Export accumulated complex fields into CSV file. Cell centers are written in length units.
+	pub fn export(&self, file_path: &std::path::Path, grid_spacing: &GridSpacing) -> std::io::Result<()>
+	{
+		let samples = self.read_samples();
+		let cell_count = get_cell_count(&self.region_size);
+
+		let mut file = std::io::BufWriter::new(std::fs::File::create(file_path)?);
+		writeln!(
+			file,
+			"frequency,x,y,z,ex_re,ex_im,ey_re,ey_im,ez_re,ez_im,hx_re,hx_im,hy_re,hy_im,hz_re,hz_im"
+		)?;
+
+		for (frequency_index, frequency) in self.frequencies.iter().enumerate()
+		{
+			for z in 0 .. self.region_size[2]
+			{
+				for y in 0 .. self.region_size[1]
+				{
+					for x in 0 .. self.region_size[0]
+					{
+						let address = (x + y * self.region_size[0] + z * self.region_size[0] * self.region_size[1])
+							as usize + frequency_index * cell_count;
+						let sample = &samples[address];
+						let position = grid_spacing.get_cell_center([
+							x + self.region_offset[0],
+							y + self.region_offset[1],
+							z + self.region_offset[2],
+						]);
+
+						write!(file, "{},{},{},{}", frequency, position.x, position.y, position.z)?;
+						for v in sample.electric.iter().chain(sample.magnetic.iter())
+						{
+							write!(file, ",{},{}", v[0], v[1])?;
+						}
+						writeln!(file)?;
+					}
+				}
+			}
+		}
+
+		file.flush()
+	}
+}
+
+pub fn get_complex_vector_magnitude(v: &ComplexVector) -> f32
+{
+	v.iter().map(|c| c[0] * c[0] + c[1] * c[1]).sum::<f32>().sqrt()
+}
+
+fn make_complex_vector(re: &[f32; 4], im: &[f32; 4]) -> ComplexVector
+{
+	[[re[0], im[0]], [re[1], im[1]], [re[2], im[2]]]
+}
+
+fn get_cell_count(size: &[u32; 3]) -> usize
+{
+	(size[0] * size[1] * size[2]) as usize
+}
+
+const SHADER_ACCUMULATE: &str = r#"
+	#version 430
+	layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
+
+	uniform float dt;
+	uniform float electric_time;
+	uniform float magnetic_time;
+	uniform float angular_frequency;
+	uniform uint data_offset;
+	uniform uvec3 field_size;
+	uniform uvec3 region_offset;
+	uniform uvec3 region_size;
+	layout(std430) buffer electric_field_data
+	{
+		vec4 electric_vecs[];
+	};
+	layout(std430) buffer magnetic_field_data
+	{
+		vec4 magnetic_vecs[];
+	};
+	layout(std430) buffer dft_data
+	{
+		vec4 dft_vecs[];
+	};
+
+	void main()
+	{
+		uvec3 region_coord = gl_GlobalInvocationID;
+		uvec3 coord = region_coord + region_offset;
+
+		uint field_address = coord.x + coord.y * field_size.x + coord.z * (field_size.x * field_size.y);
+		uint dft_address =
+			data_offset +
+			4 * (region_coord.x + region_coord.y * region_size.x + region_coord.z * (region_size.x * region_size.y));
+
+		// Multiply by exp(-i * w * t).
+		float electric_phase = angular_frequency * electric_time;
+		float magnetic_phase = angular_frequency * magnetic_time;
+		vec4 electric_vec = electric_vecs[field_address] * dt;
+		vec4 magnetic_vec = magnetic_vecs[field_address] * dt;
+
+		dft_vecs[dft_address + 0] += electric_vec * cos(electric_phase);
+		dft_vecs[dft_address + 1] -= electric_vec * sin(electric_phase);
+		dft_vecs[dft_address + 2] += magnetic_vec * cos(magnetic_phase);
+		dft_vecs[dft_address + 3] -= magnetic_vec * sin(magnetic_phase);
+	}
+"#;
+
+const SHADER_EXTRACT: &str = r#"
+	#version 430
+	layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
+
+	uniform float phase;
+	uniform float scale;
+	uniform uint data_offset;
+	uniform uvec3 region_size;
+	layout(std430) buffer dft_data
+	{
+		vec4 dft_vecs[];
+	};
+	layout(std430) buffer electric_field_data
+	{
+		vec4 electric_vecs[];
+	};
+	layout(std430) buffer magnetic_field_data
+	{
+		vec4 magnetic_vecs[];
+	};
+
+	void main()
+	{
+		uvec3 coord = gl_GlobalInvocationID;
+		uint address = coord.x + coord.y * region_size.x + coord.z * (region_size.x * region_size.y);
+		uint dft_address = data_offset + 4 * address;
+
+		// Re((re + i * im) * (cos(phase) + i * sin(phase))).
+		float c = cos(phase) * scale;
+		float s = sin(phase) * scale;
+		electric_vecs[address] = dft_vecs[dft_address + 0] * c - dft_vecs[dft_address + 1] * s;
+		magnetic_vecs[address] = dft_vecs[dft_address + 2] * c - dft_vecs[dft_address + 3] * s;
+	}
+"#;
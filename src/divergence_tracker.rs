@@ -0,0 +1,449 @@
+use super::{electromagnetic_field::*, grid_spacing::*, medium::*, scalar_grid::*, vector_field::*};
+use std::io::Write;
+
+// Tracker of Gauss law violation (divergence of electric displacement minus charge density) and divergence of
+// magnetic induction. Divergence is calculated with central differences (divided by cell sizes), like curl in field
+// update.
+// Charge density is updated via continuity equation with given current density, so any spurious charge build-up
+// is detected. Polarization of dispersive and nonlinear media and charge relaxation in conductors are not taken
+// into account, so violation is expected to be nonzero inside such media.
+pub struct DivergenceTracker
+{
+	shader_accumulate_charge: glium::program::ComputeShader,
+	shader_reduce_cells: glium::program::ComputeShader,
+	shader_reduce_partial_sums: glium::program::ComputeShader,
+	// Cells near borders are not checked, since boundary conditions do not preserve divergence.
+	ignored_border_width: u32,
+	charge_density_buffer: glium::buffer::Buffer<[f32]>,
+	// Gauss law violation and magnetic divergence (as x and y components) of last recorded field.
+	divergence_field: VectorField,
+	partial_sums_buffer: glium::buffer::Buffer<[[f32; 4]]>,
+	// Results of reduction, which are not yet read back from GPU.
+	pending_samples_buffer: glium::buffer::Buffer<[[f32; 4]]>,
+	pending_samples_times: Vec<f32>,
+	samples: Vec<DivergenceSample>,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct DivergenceSample
+{
+	pub time: f32,
+	pub max_gauss_law_violation: f32,
+	pub rms_gauss_law_violation: f32,
+	pub max_magnetic_divergence: f32,
+	pub rms_magnetic_divergence: f32,
+}
+
+impl DivergenceTracker
+{
+	// Initial charge density should be consistent with initial electric field.
+	pub fn new(display: &glium::Display, charge_density: &ScalarGrid, ignored_border_width: u32) -> Self
+	{
+		let field_size = charge_density.get_size();
+		let cell_count = field_size[0] * field_size[1] * field_size[2];
+		let group_count = cell_count.div_ceil(GROUP_SIZE) as usize;
+
+		Self {
+			shader_accumulate_charge: glium::program::ComputeShader::from_source(
+				display,
+				&[SHADER_ACCUMULATE_CHARGE_HEADER, SHADER_GRID_SPACING, SHADER_ACCUMULATE_CHARGE].concat(),
+			)
+			.unwrap(),
+			shader_reduce_cells: glium::program::ComputeShader::from_source(
+				display,
+				&[SHADER_REDUCE_CELLS_HEADER, SHADER_GRID_SPACING, SHADER_REDUCE_CELLS].concat(),
+			)
+			.unwrap(),
+			shader_reduce_partial_sums: glium::program::ComputeShader::from_source(display, SHADER_REDUCE_PARTIAL_SUMS)
+				.unwrap(),
+			ignored_border_width,
+			charge_density_buffer: glium::buffer::Buffer::new(
+				display,
+				charge_density.get_values(),
+				glium::buffer::BufferType::ShaderStorageBuffer,
+				glium::buffer::BufferMode::Default,
+			)
+			.unwrap(),
+			divergence_field: VectorField::new(display, field_size),
+			partial_sums_buffer: glium::buffer::Buffer::empty_array(
+				display,
+				glium::buffer::BufferType::ShaderStorageBuffer,
+				group_count,
+				glium::buffer::BufferMode::Default,
+			)
+			.unwrap(),
+			pending_samples_buffer: glium::buffer::Buffer::empty_array(
+				display,
+				glium::buffer::BufferType::ShaderStorageBuffer,
+				MAX_PENDING_SAMPLES,
+				glium::buffer::BufferMode::Default,
+			)
+			.unwrap(),
+			pending_samples_times: Vec::with_capacity(MAX_PENDING_SAMPLES),
+			samples: Vec::new(),
+		}
+	}
+
+	// Update charge density using current density, which was used in field update with given time step.
+	pub fn accumulate_current_density(
+		&mut self,
+		current_density: &VectorField,
+		medium_buffers: &MediumBuffers,
+		time_delta_s: f32,
+	)
+	{
+		let field_size = current_density.get_size();
+		assert_eq!(field_size, self.divergence_field.get_size());
+		assert_eq!(field_size, medium_buffers.get_size());
+
+		let uniforms = glium::uniform! {
+			dt: time_delta_s,
+			field_size: field_size,
+			grid_spacing_data: medium_buffers.get_grid_spacing_buffer(),
+			current_density_data: current_density.get_buffer(),
+			charge_density_data: &self.charge_density_buffer,
+		};
+		self.shader_accumulate_charge
+			.execute(uniforms, field_size[0], field_size[1], field_size[2]);
+	}
+
+	// Calculate divergence of field at given time. Result is available after flush.
+	pub fn record(&mut self, field: &ElectromagneticField, medium_buffers: &MediumBuffers, time: f32)
+	{
+		if self.pending_samples_times.len() == MAX_PENDING_SAMPLES
+		{
+			self.flush();
+		}
+
+		let field_size = field.electric_field.get_size();
+		assert_eq!(field_size, medium_buffers.get_size());
+		assert_eq!(field_size, self.divergence_field.get_size());
+
+		let cell_count = field_size[0] * field_size[1] * field_size[2];
+		let group_count = self.partial_sums_buffer.len() as u32;
+
+		// Normalize visualized divergence using last known maximum values.
+		let visualization_scale = if let Some(s) = self.samples.last()
+		{
+			[
+				1.0 / s.max_gauss_law_violation.max(1.0e-20),
+				1.0 / s.max_magnetic_divergence.max(1.0e-20),
+			]
+		}
+		else
+		{
+			[0.0, 0.0]
+		};
+
+		let uniforms = glium::uniform! {
+			field_size: field_size,
+			cell_count: cell_count,
+			ignored_border_width: self.ignored_border_width,
+			visualization_scale: visualization_scale,
+			grid_spacing_data: medium_buffers.get_grid_spacing_buffer(),
+			electric_field_data: field.electric_field.get_buffer(),
+			magnetic_field_data: field.magnetic_field.get_buffer(),
+			material_ids_data: medium_buffers.get_material_ids_buffer(),
+			materials_data: medium_buffers.get_materials_buffer(),
+			charge_density_data: &self.charge_density_buffer,
+			divergence_field_data: self.divergence_field.get_buffer(),
+			partial_sums_data: &self.partial_sums_buffer,
+		};
+		self.shader_reduce_cells.execute(uniforms, group_count, 1, 1);
+
+		let uniforms = glium::uniform! {
+			partial_sums_count: group_count,
+			result_index: self.pending_samples_times.len() as u32,
+			partial_sums_data: &self.partial_sums_buffer,
+			result_data: &self.pending_samples_buffer,
+		};
+		self.shader_reduce_partial_sums.execute(uniforms, 1, 1, 1);
+
+		self.pending_samples_times.push(time);
+	}
+
+	// Read pending results from GPU.
+	pub fn flush(&mut self)
+	{
+		if self.pending_samples_times.is_empty()
+		{
+			return;
+		}
+
+		let data = self
+			.pending_samples_buffer
+			.slice(0 .. self.pending_samples_times.len())
+			.unwrap()
+			.read()
+			.unwrap();
+
+		let field_size = self.divergence_field.get_size();
+		let checked_cell_count = (0 .. 3)
+			.map(|i| field_size[i].saturating_sub(2 * self.ignored_border_width.max(1)))
+			.product::<u32>()
+			.max(1) as f32;
+
+		for (time, values) in self.pending_samples_times.iter().zip(data.iter())
+		{
+			self.samples.push(DivergenceSample {
+				time: *time,
+				max_gauss_law_violation: values[0],
+				rms_gauss_law_violation: (values[1] / checked_cell_count).sqrt(),
+				max_magnetic_divergence: values[2],
+				rms_magnetic_divergence: (values[3] / checked_cell_count).sqrt(),
+			});
+		}
+		self.pending_samples_times.clear();
+	}
+
+	pub fn get_samples(&self) -> &[DivergenceSample]
+	{
+		&self.samples
+	}
+
+	// Get normalized Gauss law violation and magnetic divergence (as x and y components) of last recorded field.
+	pub fn get_divergence_field(&self) -> &VectorField
+	{
+		&self.divergence_field
+	}
+
+	// Export recorded time series into CSV file.
+	pub fn export(&self, file_path: &std::path::Path) -> std::io::Result<()>
+	{
+		let mut file = std::io::BufWriter::new(std::fs::File::create(file_path)?);
+		writeln!(
+			file,
+			"time,max_gauss_law_violation,rms_gauss_law_violation,max_magnetic_divergence,rms_magnetic_divergence"
+		)?;
+		for s in &self.samples
+		{
+			writeln!(
+				file,
+				"{},{},{},{},{}",
+				s.time,
+				s.max_gauss_law_violation,
+				s.rms_gauss_law_violation,
+				s.max_magnetic_divergence,
+				s.rms_magnetic_divergence
+			)?;
+		}
+		file.flush()
+	}
+}
+
+const GROUP_SIZE: u32 = 256;
+const MAX_PENDING_SAMPLES: usize = 64;
+
+const SHADER_ACCUMULATE_CHARGE_HEADER: &str = r#"
+	#version 430
+	layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
+
+	uniform uvec3 field_size;
+"#;
+
+// Charge density changes according to continuity equation "d(rho)/dt = -div(J)".
+const SHADER_ACCUMULATE_CHARGE: &str = r#"
+	uniform float dt;
+	layout(std430) buffer current_density_data
+	{
+		vec4 current_density_vecs[];
+	};
+	layout(std430) buffer charge_density_data
+	{
+		float charge_densities[];
+	};
+
+	uint get_address(ivec3 coord)
+	{
+		uvec3 c = uvec3(clamp(coord, ivec3(0, 0, 0), ivec3(field_size) - ivec3(1, 1, 1)));
+		return c.x + c.y * field_size.x + c.z * (field_size.x * field_size.y);
+	}
+
+	void main()
+	{
+		ivec3 coord = ivec3(gl_GlobalInvocationID);
+		float divergence = 0.0;
+		for(int i = 0; i < 3; ++i)
+		{
+			ivec3 offset = ivec3(0, 0, 0);
+			offset[i] = 1;
+			divergence += 0.5 / get_cell_spacing(i, coord[i]) *
+				(current_density_vecs[get_address(coord + offset)][i] -
+				current_density_vecs[get_address(coord - offset)][i]);
+		}
+		charge_densities[get_address(coord)] -= dt * divergence;
+	}
+"#;
+
+const SHADER_REDUCE_CELLS_HEADER: &str = r#"
+	#version 430
+	layout(local_size_x = 256, local_size_y = 1, local_size_z = 1) in;
+
+	uniform uvec3 field_size;
+"#;
+
+// Each work group calculates divergence for its cells and reduces it into
+// (max Gauss law violation, sum of squares of Gauss law violation, max magnetic divergence, sum of squares of magnetic
+// divergence).
+const SHADER_REDUCE_CELLS: &str = r#"
+	uniform uint cell_count;
+	uniform uint ignored_border_width;
+	uniform vec2 visualization_scale;
+	layout(std430) buffer electric_field_data
+	{
+		vec4 electric_vecs[];
+	};
+	layout(std430) buffer magnetic_field_data
+	{
+		vec4 magnetic_vecs[];
+	};
+	layout(std430) buffer material_ids_data
+	{
+		uint material_ids[];
+	};
+	layout(std430) buffer materials_data
+	{
+		vec4 materials[];
+	};
+	layout(std430) buffer charge_density_data
+	{
+		float charge_densities[];
+	};
+	layout(std430) buffer divergence_field_data
+	{
+		vec4 divergence_vecs[];
+	};
+	layout(std430) buffer partial_sums_data
+	{
+		vec4 partial_sums[];
+	};
+
+	shared vec4 shared_values[256];
+
+	uint get_address(uvec3 coord)
+	{
+		return coord.x + coord.y * field_size.x + coord.z * (field_size.x * field_size.y);
+	}
+
+	// Electric displacement and magnetic induction.
+	vec3 get_displacement(uvec3 coord)
+	{
+		uint address = get_address(coord);
+		return materials[material_ids[address]].x * electric_vecs[address].xyz;
+	}
+
+	vec3 get_induction(uvec3 coord)
+	{
+		uint address = get_address(coord);
+		return materials[material_ids[address]].y * magnetic_vecs[address].xyz;
+	}
+
+	void main()
+	{
+		uint address = gl_GlobalInvocationID.x;
+		uint local_index = gl_LocalInvocationID.x;
+
+		vec4 value = vec4(0.0, 0.0, 0.0, 0.0);
+		if(address < cell_count)
+		{
+			uint layer_size = field_size.x * field_size.y;
+			uvec3 coord = uvec3(address % field_size.x, (address % layer_size) / field_size.x, address / layer_size);
+
+			// At least one cell is required for central differences.
+			uint border_width = max(ignored_border_width, 1u);
+			vec2 divergence = vec2(0.0, 0.0);
+			if(all(greaterThanEqual(coord, uvec3(border_width))) &&
+				all(lessThan(coord + uvec3(border_width), field_size)))
+			{
+				float displacement_divergence = 0.0;
+				float induction_divergence = 0.0;
+				for(int i = 0; i < 3; ++i)
+				{
+					uvec3 offset = uvec3(0u, 0u, 0u);
+					offset[i] = 1u;
+					float scale = 0.5 / get_cell_spacing(i, int(coord[i]));
+					displacement_divergence +=
+						scale * (get_displacement(coord + offset)[i] - get_displacement(coord - offset)[i]);
+					induction_divergence +=
+						scale * (get_induction(coord + offset)[i] - get_induction(coord - offset)[i]);
+				}
+				divergence = vec2(displacement_divergence - charge_densities[address], induction_divergence);
+			}
+			divergence_vecs[address] = vec4(divergence * visualization_scale, 0.0, 0.0);
+
+			value =
+				vec4(
+					abs(divergence.x),
+					divergence.x * divergence.x,
+					abs(divergence.y),
+					divergence.y * divergence.y);
+		}
+		shared_values[local_index] = value;
+		barrier();
+
+		for(uint step = 128; step > 0; step >>= 1)
+		{
+			if(local_index < step)
+			{
+				vec4 a = shared_values[local_index];
+				vec4 b = shared_values[local_index + step];
+				shared_values[local_index] = vec4(max(a.x, b.x), a.y + b.y, max(a.z, b.z), a.w + b.w);
+			}
+			barrier();
+		}
+
+		if(local_index == 0)
+		{
+			partial_sums[gl_WorkGroupID.x] = shared_values[0];
+		}
+	}
+"#;
+
+// Single work group reduces all partial sums into one value.
+const SHADER_REDUCE_PARTIAL_SUMS: &str = r#"
+	#version 430
+	layout(local_size_x = 256, local_size_y = 1, local_size_z = 1) in;
+
+	uniform uint partial_sums_count;
+	uniform uint result_index;
+	layout(std430) buffer partial_sums_data
+	{
+		vec4 partial_sums[];
+	};
+	layout(std430) buffer result_data
+	{
+		vec4 results[];
+	};
+
+	shared vec4 shared_values[256];
+
+	void main()
+	{
+		uint local_index = gl_LocalInvocationID.x;
+
+		vec4 value = vec4(0.0, 0.0, 0.0, 0.0);
+		for(uint i = local_index; i < partial_sums_count; i += 256)
+		{
+			vec4 s = partial_sums[i];
+			value = vec4(max(value.x, s.x), value.y + s.y, max(value.z, s.z), value.w + s.w);
+		}
+		shared_values[local_index] = value;
+		barrier();
+
+		for(uint step = 128; step > 0; step >>= 1)
+		{
+			if(local_index < step)
+			{
+				vec4 a = shared_values[local_index];
+				vec4 b = shared_values[local_index + step];
+				shared_values[local_index] = vec4(max(a.x, b.x), a.y + b.y, max(a.z, b.z), a.w + b.w);
+			}
+			barrier();
+		}
+
+		if(local_index == 0)
+		{
+			results[result_index] = shared_values[0];
+		}
+	}
+"#;
@@ -1,49 +1,605 @@
-use super::electromagnetic_field::*;
+use super::{
+	electromagnetic_field::*, grid_spacing::*, lumped_element::*, medium::*, plane_wave_source::*, thin_wire::*,
+	vector_field::*,
+};
 
 pub struct ElectromagneticFieldUpdater
 {
 	shader_electric_field_update: glium::program::ComputeShader,
 	shader_magnetic_field_update: glium::program::ComputeShader,
+	medium_buffers: MediumBuffers,
+	boundary_conditions: [BoundaryCondition; 3],
+	spatial_stencil: SpatialStencil,
+	// Box of cells, where second-order stencil is used, see "set_second_order_box".
+	second_order_box: Option<([u32; 3], [u32; 3])>,
+	simulation_mode: SimulationMode,
+	plane_wave_source: Option<PlaneWaveSource>,
+	// Samples of plane wave source waveform. Contains dummy value if there is no source.
+	waveform_buffer: glium::buffer::Buffer<[f32]>,
+	// Polarization of dispersive materials - current and previous values for each pole of each cell.
+	// Contains dummy value if there are no dispersive materials.
+	polarization_buffer: glium::buffer::Buffer<[[f32; 4]]>,
+	// Used if there is no current density.
+	dummy_current_density: VectorField,
+	lumped_elements: Vec<LumpedElement>,
+	// Index of lumped element plus one for each cell (zero if there is no element).
+	// All lumped element buffers contain dummy values if there are no elements.
+	lumped_element_ids_buffer: glium::buffer::Buffer<[u32]>,
+	// Two values for each element - (axis, conductance, capacitance, inverse inductance), scaled by ratio of cell
+	// length along axis to cell face area, and (cell length, cell face area, unused, unused).
+	lumped_elements_buffer: glium::buffer::Buffer<[[f32; 4]]>,
+	lumped_element_source_currents_buffer: glium::buffer::Buffer<[f32]>,
+	// (inductor current density, voltage, current, unused) for each element, voltage and current are given at half
+	// step.
+	lumped_element_states_buffer: glium::buffer::Buffer<[[f32; 4]]>,
+	has_thin_wires: bool,
+	// Index of thin wire cell data plus one for each cell (zero if cell is not affected by wires).
+	// Both buffers contain dummy values if there are no wires.
+	thin_wire_cell_ids_buffer: glium::buffer::Buffer<[u32]>,
+	thin_wire_cells_buffer: glium::buffer::Buffer<[[f32; 4]]>,
+}
+
+// Boundary condition for each side of the field box along specific axis.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum BoundaryCondition
+{
+	// Field outside the box is equal to field in border cells.
+	// Body-of-revolution updater treats it as perfectly conducting wall instead (see "BodyOfRevolutionUpdater").
+	Reflecting,
+	// Field is wrapped around to the opposite side.
+	Periodic,
+	// Graded lossy layer with given width (in cells) near box borders absorbs outgoing waves.
+	// Conductivity is graded along distance, so on graded mesh wider (in length units) layers absorb better.
+	Absorbing
+	{
+		width: u32
+	},
+}
+
+// Finite-difference stencil for spatial derivatives in curl.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SpatialStencil
+{
+	// Central difference "(f(x + 1) - f(x - 1)) / 2".
+	SecondOrder,
+	// Central difference "(8 * (f(x + 1) - f(x - 1)) - (f(x + 2) - f(x - 2))) / 12". It has much lower numerical
+	// dispersion on coarse grids, but stability limit of time step is about 1.37 times lower.
+	// Second-order stencil is still used near non-periodic borders (where wider stencil doesn't fit into field), in
+	// cells, affected by thin wires (since subcell correction is derived for second-order stencil), and in box, given
+	// by "set_second_order_box" (around subgrid), with gradual transition (see "FOURTH_ORDER_FADE_WIDTH").
+	FourthOrder,
+}
+
+// Fourth-order stencil is second-order one plus correction "(f(x + 1) - f(x - 1)) / 6 - (f(x + 2) - f(x - 2)) / 12".
+// Correction is faded out: derivative along each axis in cell is corrected by difference of neighbor values, multiplied
+// by weights of neighbors and of cell itself for this axis. These weights are symmetric, so correction conserves energy
+// (unlike abrupt switch of stencil). Weight grows to one at given distance (in cells) from second-order box (see
+// "ElectromagneticFieldUpdater::set_second_order_box") and from three border cells of non-periodic axis, where it's
+// zero. Wider stencil doesn't fit into field in two border cells, zero weight of third one keeps update with reflecting
+// borders stable. Cells, affected by thin wires, have zero weight too. Subcell correction of thin wires itself scales
+// only derivatives of electric field (in magnetic field update), so it doesn't conserve energy exactly.
+pub const FOURTH_ORDER_FADE_WIDTH: i32 = 4;
+
+// Weight of fourth-order correction for cell at given distance from second-order box or from border cells (zero or
+// negative in them).
+pub fn get_fourth_order_weight(distance: i32) -> f32
+{
+	let t = (distance as f32 / FOURTH_ORDER_FADE_WIDTH as f32).clamp(0.0, 1.0);
+	t * t * (3.0 - 2.0 * t)
+}
+
+impl SpatialStencil
+{
+	pub fn get_order(&self) -> u32
+	{
+		match self
+		{
+			SpatialStencil::SecondOrder => 2,
+			SpatialStencil::FourthOrder => 4,
+		}
+	}
+}
+
+// Dimensionality of simulation. Field doesn't change along reduced axes, so field should have single cell along them
+// with periodic or reflecting boundary condition (derivatives along these axes are zero then). Reduced modes aren't
+// native reduced-component solvers, they only mask field components: field is still stored and updated as
+// three-dimensional one (all components of each cell), after that components, which aren't coupled to used ones, are
+// zeroed. So cost per cell is same as in full simulation, and result is same as of full simulation, where only used
+// components are excited.
+// Plane wave source should propagate along non-reduced axes, its box should cover whole field along reduced axes.
+// Lumped elements should be oriented along used electric field components. These requirements are checked. Thin wires
+// should go only along used electric field components too, current density along unused components (including
+// current of charged particles) is ignored. Field monitors and probes see unused components as zero.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SimulationMode
+{
+	// Field depends on all three coordinates.
+	Full,
+	// Field doesn't depend on Z.
+	TwoDimensional
+	{
+		polarization: TwoDimensionalPolarization
+	},
+	// Field depends only on Z - TEM wave of transmission line, polarized along X (Ex and Hy are used).
+	OneDimensional,
+}
+
+// Set of independent field components of two-dimensional simulation.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum TwoDimensionalPolarization
+{
+	// Ez, Hx and Hy are used.
+	TransverseMagnetic,
+	// Hz, Ex and Ey are used.
+	TransverseElectric,
+}
+
+impl SimulationMode
+{
+	// Axes, along which field doesn't change.
+	pub fn get_reduced_axes(&self) -> [bool; 3]
+	{
+		match self
+		{
+			SimulationMode::Full => [false, false, false],
+			SimulationMode::TwoDimensional { .. } => [false, false, true],
+			SimulationMode::OneDimensional => [true, true, false],
+		}
+	}
+
+	// Masks of used components of electric and magnetic field.
+	pub fn get_component_masks(&self) -> ([f32; 3], [f32; 3])
+	{
+		match self
+		{
+			SimulationMode::Full => ([1.0, 1.0, 1.0], [1.0, 1.0, 1.0]),
+			SimulationMode::TwoDimensional {
+				polarization: TwoDimensionalPolarization::TransverseMagnetic,
+			} => ([0.0, 0.0, 1.0], [1.0, 1.0, 0.0]),
+			SimulationMode::TwoDimensional {
+				polarization: TwoDimensionalPolarization::TransverseElectric,
+			} => ([1.0, 1.0, 0.0], [0.0, 0.0, 1.0]),
+			SimulationMode::OneDimensional => ([1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+		}
+	}
+
+	pub fn validate(&self, field_size: [u32; 3], boundary_conditions: &[BoundaryCondition; 3])
+	{
+		let reduced_axes = self.get_reduced_axes();
+		for i in (0 .. 3).filter(|i| reduced_axes[*i])
+		{
+			assert_eq!(field_size[i], 1, "field should have single cell along reduced axes");
+			assert!(
+				!matches!(boundary_conditions[i], BoundaryCondition::Absorbing { .. }),
+				"absorbing boundary condition can't be used along reduced axes"
+			);
+		}
+	}
 }
 
 impl ElectromagneticFieldUpdater
 {
-	pub fn new(display: &glium::Display) -> Self
+	pub fn new(display: &glium::Display, medium: &Medium, boundary_conditions: [BoundaryCondition; 3]) -> Self
 	{
+		let size = medium.get_size();
+		let polarization_buffer_size = if medium.has_dispersive_materials()
+		{
+			(size[0] * size[1] * size[2]) as usize * MAX_DISPERSION_POLES * 2
+		}
+		else
+		{
+			1
+		};
+
 		Self {
-			shader_electric_field_update: glium::program::ComputeShader::from_source(display, SHADER_ELECTRIC_UPDATE)
-				.unwrap(),
-			shader_magnetic_field_update: glium::program::ComputeShader::from_source(display, SHADER_MAGNETIC_UPDATE)
-				.unwrap(),
+			shader_electric_field_update: glium::program::ComputeShader::from_source(
+				display,
+				&[SHADER_HEADER, SHADER_GRID_SPACING, SHADER_COMMON, SHADER_ELECTRIC_UPDATE].concat(),
+			)
+			.unwrap(),
+			shader_magnetic_field_update: glium::program::ComputeShader::from_source(
+				display,
+				&[SHADER_HEADER, SHADER_GRID_SPACING, SHADER_COMMON, SHADER_MAGNETIC_UPDATE].concat(),
+			)
+			.unwrap(),
+			medium_buffers: MediumBuffers::new(display, medium),
+			boundary_conditions,
+			spatial_stencil: SpatialStencil::SecondOrder,
+			second_order_box: None,
+			simulation_mode: SimulationMode::Full,
+			plane_wave_source: None,
+			waveform_buffer: create_waveform_buffer(display, &[0.0]),
+			polarization_buffer: glium::buffer::Buffer::new(
+				display,
+				&vec![[0.0; 4]; polarization_buffer_size][..],
+				glium::buffer::BufferType::ShaderStorageBuffer,
+				glium::buffer::BufferMode::Default,
+			)
+			.unwrap(),
+			dummy_current_density: VectorField::new(display, [1, 1, 1]),
+			lumped_elements: Vec::new(),
+			lumped_element_ids_buffer: create_storage_buffer(display, &[0][..]),
+			lumped_elements_buffer: create_storage_buffer(display, &[[0.0; 4]; 2][..]),
+			lumped_element_source_currents_buffer: create_storage_buffer(display, &[0.0][..]),
+			lumped_element_states_buffer: create_storage_buffer(display, &[[0.0; 4]][..]),
+			has_thin_wires: false,
+			thin_wire_cell_ids_buffer: create_storage_buffer(display, &[0][..]),
+			thin_wire_cells_buffer: create_storage_buffer(display, &[[1.0; 4]][..]),
 		}
 	}
 
-	pub fn update(&self, field: &mut ElectromagneticField, time_delta_s: f32)
+	pub fn set_spatial_stencil(&mut self, spatial_stencil: SpatialStencil)
+	{
+		self.spatial_stencil = spatial_stencil;
+	}
+
+	pub fn get_spatial_stencil(&self) -> SpatialStencil
+	{
+		self.spatial_stencil
+	}
+
+	// Use second-order stencil in box [min; max) of cells, even if fourth-order stencil is enabled.
+	pub fn set_second_order_box(&mut self, second_order_box: Option<([u32; 3], [u32; 3])>)
+	{
+		self.second_order_box = second_order_box;
+	}
+
+	pub fn get_second_order_box(&self) -> Option<([u32; 3], [u32; 3])>
+	{
+		self.second_order_box
+	}
+
+	pub fn set_simulation_mode(&mut self, simulation_mode: SimulationMode)
+	{
+		simulation_mode.validate(self.medium_buffers.get_size(), &self.boundary_conditions);
+		self.simulation_mode = simulation_mode;
+		self.validate_sources();
+	}
+
+	pub fn set_plane_wave_source(&mut self, display: &glium::Display, plane_wave_source: Option<PlaneWaveSource>)
+	{
+		self.waveform_buffer = create_waveform_buffer(
+			display,
+			plane_wave_source
+				.as_ref()
+				.map(|s| s.waveform.get_samples())
+				.unwrap_or(&[0.0]),
+		);
+		self.plane_wave_source = plane_wave_source;
+		self.validate_sources();
+	}
+
+	// Set lumped elements. Each cell may contain only one element. State of elements is reset.
+	pub fn set_lumped_elements(&mut self, display: &glium::Display, lumped_elements: Vec<LumpedElement>)
+	{
+		let size = self.medium_buffers.get_size();
+		let num_cells = if lumped_elements.is_empty()
+		{
+			1
+		}
+		else
+		{
+			(size[0] * size[1] * size[2]) as usize
+		};
+		let num_elements = lumped_elements.len().max(1);
+		let grid_spacing = self.medium_buffers.get_grid_spacing();
+		let mut lumped_element_ids = vec![0; num_cells];
+		let mut lumped_elements_data = vec![[0.0; 4]; num_elements * 2];
+		for (index, element) in lumped_elements.iter().enumerate()
+		{
+			let p = element.position;
+			assert!(p[0] < size[0] && p[1] < size[1] && p[2] < size[2]);
+			assert!(element.axis < 3);
+			let address = (p[0] + p[1] * size[0] + p[2] * (size[0] * size[1])) as usize;
+			assert_eq!(lumped_element_ids[address], 0, "Only one lumped element per cell is allowed");
+			lumped_element_ids[address] = (index + 1) as u32;
+
+			// Element is converted into conductivity, permittivity and inverse inductance of cell material.
+			let [conductance, capacitance, inverse_inductance] = element.get_parallel_circuit_params();
+			let length = grid_spacing.get_spacing(element.axis, p[element.axis]);
+			let area = grid_spacing.get_face_area(p, element.axis);
+			let scale = length / area;
+			lumped_elements_data[index * 2] = [
+				element.axis as f32,
+				conductance * scale,
+				capacitance * scale,
+				inverse_inductance * scale,
+			];
+			lumped_elements_data[index * 2 + 1] = [length, area, 0.0, 0.0];
+		}
+
+		self.lumped_element_ids_buffer = create_storage_buffer(display, &lumped_element_ids[..]);
+		self.lumped_elements_buffer = create_storage_buffer(display, &lumped_elements_data[..]);
+		self.lumped_element_source_currents_buffer = create_storage_buffer(display, &vec![0.0; num_elements][..]);
+		self.lumped_element_states_buffer = create_storage_buffer(display, &vec![[0.0; 4]; num_elements][..]);
+		self.lumped_elements = lumped_elements;
+		self.validate_sources();
+	}
+
+	pub fn set_thin_wires(&mut self, display: &glium::Display, thin_wires: &[ThinWire])
+	{
+		let cells = build_thin_wire_cells(self.medium_buffers.get_size(), thin_wires);
+		self.has_thin_wires = !cells.cells_data.is_empty();
+		if self.has_thin_wires
+		{
+			self.thin_wire_cell_ids_buffer = create_storage_buffer(display, &cells.cell_ids[..]);
+			self.thin_wire_cells_buffer = create_storage_buffer(display, &cells.cells_data[..]);
+		}
+		else
+		{
+			self.thin_wire_cell_ids_buffer = create_storage_buffer(display, &[0][..]);
+			self.thin_wire_cells_buffer = create_storage_buffer(display, &[[1.0; 4]][..]);
+		}
+	}
+
+	pub fn get_lumped_elements(&self) -> &[LumpedElement]
+	{
+		&self.lumped_elements
+	}
+
+	pub fn get_lumped_element_states_buffer(&self) -> &glium::buffer::Buffer<[[f32; 4]]>
+	{
+		&self.lumped_element_states_buffer
+	}
+
+	pub fn get_medium_buffers(&self) -> &MediumBuffers
+	{
+		&self.medium_buffers
+	}
+
+	// Perform single update step. "time" is time of electric field before update.
+	// Magnetic field is considered to be half step ahead.
+	pub fn update(&self, field: &mut ElectromagneticField, time: f32, time_delta_s: f32)
+	{
+		self.update_with_current_density(field, None, time, time_delta_s);
+	}
+
+	// Perform update step with external current density, which should be given at half step.
+	pub fn update_with_current_density(
+		&self,
+		field: &mut ElectromagneticField,
+		current_density: Option<&VectorField>,
+		time: f32,
+		time_delta_s: f32,
+	)
+	{
+		self.execute_update(field, current_density, time, time_delta_s, true, true);
+	}
+
+	// Perform only electric field part of update step. Together with following "update_magnetic_field" call it's
+	// equivalent to "update_with_current_density", but allows to modify fields between the two halves (used by
+	// subgrids).
+	pub fn update_electric_field(
+		&self,
+		field: &mut ElectromagneticField,
+		current_density: Option<&VectorField>,
+		time: f32,
+		time_delta_s: f32,
+	)
+	{
+		self.execute_update(field, current_density, time, time_delta_s, true, false);
+	}
+
+	// Perform only magnetic field part of update step. "time" is time of electric field before the whole step.
+	pub fn update_magnetic_field(&self, field: &mut ElectromagneticField, time: f32, time_delta_s: f32)
+	{
+		self.execute_update(field, None, time, time_delta_s, false, true);
+	}
+
+	// Check that sources are compatible with simulation mode.
+	fn validate_sources(&self)
+	{
+		if let Some(source) = &self.plane_wave_source
+		{
+			let reduced_axes = self.simulation_mode.get_reduced_axes();
+			for i in (0 .. 3).filter(|i| reduced_axes[*i])
+			{
+				assert!(
+					source.box_min[i] == 0 && source.box_max[i] >= 1 && source.direction[i] == 0.0,
+					"plane wave source should propagate along non-reduced axes and cover field along reduced axes"
+				);
+			}
+		}
+
+		let (electric_component_mask, _) = self.simulation_mode.get_component_masks();
+		assert!(
+			self.lumped_elements.iter().all(|element| electric_component_mask[element.axis] != 0.0),
+			"lumped elements should be oriented along electric field components, which are used in simulation mode"
+		);
+	}
+
+	fn execute_update(
+		&self,
+		field: &mut ElectromagneticField,
+		current_density: Option<&VectorField>,
+		time: f32,
+		time_delta_s: f32,
+		update_electric_field: bool,
+		update_magnetic_field: bool,
+	)
 	{
 		assert_eq!(field.electric_field.get_size(), field.magnetic_field.get_size());
+		if let Some(current_density) = current_density
+		{
+			assert_eq!(field.electric_field.get_size(), current_density.get_size());
+		}
+		assert_eq!(field.electric_field.get_size(), self.medium_buffers.get_size());
 
 		let field_size = field.electric_field.get_size();
 
+		let mut periodic_axes = [0u32; 3];
+		let mut absorbing_layer_width = [0u32; 3];
+		for (i, boundary_condition) in self.boundary_conditions.iter().enumerate()
+		{
+			match boundary_condition
+			{
+				BoundaryCondition::Reflecting =>
+				{},
+				BoundaryCondition::Periodic =>
+				{
+					periodic_axes[i] = 1;
+				},
+				BoundaryCondition::Absorbing { width } =>
+				{
+					absorbing_layer_width[i] = *width;
+				},
+			}
+		}
+
+		let (tfsf_box_min, tfsf_box_max, incident_direction, incident_polarization, incident_reference_point) =
+			if let Some(source) = &self.plane_wave_source
+			{
+				(
+					source.box_min,
+					source.box_max,
+					source.direction.into(),
+					(source.polarization * source.amplitude).into(),
+					source.get_reference_point(self.medium_buffers.get_grid_spacing()).into(),
+				)
+			}
+			else
+			{
+				([0; 3], [0; 3], [0.0; 3], [0.0; 3], [0.0; 3])
+			};
+		if update_electric_field && !self.lumped_elements.is_empty()
+		{
+			// Sources are evaluated at half step, like current density.
+			let source_currents = self
+				.lumped_elements
+				.iter()
+				.map(|e| e.get_source_current(time + 0.5 * time_delta_s))
+				.collect::<Vec<_>>();
+			self.lumped_element_source_currents_buffer.write(&source_currents[..]);
+		}
+
+		let waveform_time_step = self
+			.plane_wave_source
+			.as_ref()
+			.map(|s| s.waveform.get_time_step())
+			.unwrap_or(1.0);
+		let (electric_component_mask, magnetic_component_mask) = self.simulation_mode.get_component_masks();
+		let (second_order_box_min, second_order_box_max) = self.second_order_box.unwrap_or(([0; 3], [0; 3]));
+
 		let uniforms = glium::uniform! {
 			dt: time_delta_s,
 			field_size: field_size,
+			periodic_axes: periodic_axes,
+			absorbing_layer_width: absorbing_layer_width,
+			fourth_order_enabled: self.spatial_stencil == SpatialStencil::FourthOrder,
+			second_order_box_enabled: self.second_order_box.is_some(),
+			second_order_box_min: second_order_box_min,
+			second_order_box_max: second_order_box_max,
+			fourth_order_fade_width: FOURTH_ORDER_FADE_WIDTH,
+			electric_component_mask: electric_component_mask,
+			magnetic_component_mask: magnetic_component_mask,
+			tfsf_enabled: self.plane_wave_source.is_some(),
+			tfsf_box_min: tfsf_box_min,
+			tfsf_box_max: tfsf_box_max,
+			incident_direction: incident_direction,
+			incident_polarization: incident_polarization,
+			incident_reference_point: incident_reference_point,
+			// Electric field update uses magnetic field at half step, magnetic field update uses electric field at
+			// full step.
+			incident_magnetic_time: time + 0.5 * time_delta_s,
+			incident_electric_time: time + time_delta_s,
+			waveform_time_step: waveform_time_step,
+			waveform_samples_count: self.waveform_buffer.len() as u32,
+			waveform_data: &self.waveform_buffer,
 			electric_field_data: field.electric_field.get_buffer(),
 			magnetic_field_data: field.magnetic_field.get_buffer(),
+			material_ids_data: self.medium_buffers.get_material_ids_buffer(),
+			materials_data: self.medium_buffers.get_materials_buffer(),
+			grid_spacing_data: self.medium_buffers.get_grid_spacing_buffer(),
+			dispersion_enabled: self.medium_buffers.has_dispersive_materials(),
+			material_poles_data: self.medium_buffers.get_material_poles_buffer(),
+			polarization_data: &self.polarization_buffer,
+			nonlinearity_enabled: self.medium_buffers.has_nonlinear_materials(),
+			material_nonlinearities_data: self.medium_buffers.get_material_nonlinearities_buffer(),
+			anisotropy_enabled: self.medium_buffers.has_anisotropic_materials(),
+			material_tensors_data: self.medium_buffers.get_material_tensors_buffer(),
+			current_density_enabled: current_density.is_some(),
+			current_density_data: current_density.unwrap_or(&self.dummy_current_density).get_buffer(),
+			lumped_elements_enabled: !self.lumped_elements.is_empty(),
+			lumped_element_ids_data: &self.lumped_element_ids_buffer,
+			lumped_elements_data: &self.lumped_elements_buffer,
+			lumped_element_source_currents_data: &self.lumped_element_source_currents_buffer,
+			lumped_element_states_data: &self.lumped_element_states_buffer,
+			thin_wires_enabled: self.has_thin_wires,
+			thin_wire_cell_ids_data: &self.thin_wire_cell_ids_buffer,
+			thin_wire_cells_data: &self.thin_wire_cells_buffer,
 		};
 
-		self.shader_electric_field_update
-			.execute(uniforms, field_size[0], field_size[1], field_size[2]);
-		self.shader_magnetic_field_update
-			.execute(uniforms, field_size[0], field_size[1], field_size[2]);
+		if update_electric_field
+		{
+			self.shader_electric_field_update
+				.execute(uniforms, field_size[0], field_size[1], field_size[2]);
+		}
+		if update_magnetic_field
+		{
+			self.shader_magnetic_field_update
+				.execute(uniforms, field_size[0], field_size[1], field_size[2]);
+		}
 	}
 }
 
-const SHADER_ELECTRIC_UPDATE: &str = r#"
+fn create_waveform_buffer(display: &glium::Display, samples: &[f32]) -> glium::buffer::Buffer<[f32]>
+{
+	glium::buffer::Buffer::new(
+		display,
+		samples,
+		glium::buffer::BufferType::ShaderStorageBuffer,
+		glium::buffer::BufferMode::Default,
+	)
+	.unwrap()
+}
+
+fn create_storage_buffer<T: Copy>(display: &glium::Display, data: &[T]) -> glium::buffer::Buffer<[T]>
+{
+	glium::buffer::Buffer::new(
+		display,
+		data,
+		glium::buffer::BufferType::ShaderStorageBuffer,
+		glium::buffer::BufferMode::Default,
+	)
+	.unwrap()
+}
+
+const SHADER_HEADER: &str = r#"
 	#version 430
 	layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
 
 	uniform float dt;
 	uniform uvec3 field_size;
+"#;
+
+// Common part of update shaders - uniforms, buffers and helper functions.
+const SHADER_COMMON: &str = r#"
+	uniform uvec3 periodic_axes;
+	uniform uvec3 absorbing_layer_width;
+	uniform bool fourth_order_enabled;
+	uniform bool second_order_box_enabled;
+	uniform uvec3 second_order_box_min;
+	uniform uvec3 second_order_box_max;
+	uniform int fourth_order_fade_width;
+	// Masks of field components, which are used in reduced simulation modes.
+	uniform vec3 electric_component_mask;
+	uniform vec3 magnetic_component_mask;
+
+	// Total-field/scattered-field plane wave source parameters.
+	uniform bool tfsf_enabled;
+	uniform uvec3 tfsf_box_min;
+	uniform uvec3 tfsf_box_max;
+	uniform vec3 incident_direction;
+	uniform vec3 incident_polarization;
+	uniform vec3 incident_reference_point;
+	uniform float incident_magnetic_time;
+	uniform float incident_electric_time;
+	uniform float waveform_time_step;
+	uniform uint waveform_samples_count;
+	layout(std430) buffer waveform_data
+	{
+		float waveform_samples[];
+	};
 	layout(std430) buffer electric_field_data
 	{
 		vec4 electric_vecs[];
@@ -52,93 +608,533 @@ const SHADER_ELECTRIC_UPDATE: &str = r#"
 	{
 		vec4 magnetic_vecs[];
 	};
+	layout(std430) buffer material_ids_data
+	{
+		uint material_ids[];
+	};
+	// Each material is (permittivity, permeability, conductivity, magnetic conductivity).
+	layout(std430) buffer materials_data
+	{
+		vec4 materials[];
+	};
 
-	void main()
+	// Wrap coordinates along periodic axes.
+	ivec3 wrap_coord(ivec3 coord)
 	{
-		uvec3 coord = gl_GlobalInvocationID;
-
-		uint layer_size = field_size.x * field_size.y;
-		uint address_center  = coord.x + coord.y * field_size.x + coord.z * layer_size;
-		uint address_x_minus = (max(coord.x, 1) - 1) + coord.y * field_size.x + coord.z * layer_size;
-		uint address_y_minus = coord.x + (max(coord.y, 1) - 1) * field_size.x + coord.z * layer_size;
-		uint address_z_minus = coord.x + coord.y * field_size.x + (max(coord.z, 1) - 1) * layer_size;
-		uint address_x_plus = (min(coord.x, field_size.x - 2) + 1) + coord.y * field_size.x + coord.z * layer_size;
-		uint address_y_plus = coord.x + (min(coord.y, field_size.y - 2) + 1) * field_size.x + coord.z * layer_size;
-		uint address_z_plus = coord.x + coord.y * field_size.x + (min(coord.z, field_size.z - 2) + 1) * layer_size;
-
-		vec3 vec_center  = magnetic_vecs[address_center ].xyz;
-		vec3 vec_x_minus = magnetic_vecs[address_x_minus].xyz;
-		vec3 vec_y_minus = magnetic_vecs[address_y_minus].xyz;
-		vec3 vec_z_minus = magnetic_vecs[address_z_minus].xyz;
-		vec3 vec_x_plus = magnetic_vecs[address_x_plus].xyz;
-		vec3 vec_y_plus = magnetic_vecs[address_y_plus].xyz;
-		vec3 vec_z_plus = magnetic_vecs[address_z_plus].xyz;
-
-		vec3 x_derivative = (vec_x_plus - vec_x_minus) * 0.5;
-		vec3 y_derivative = (vec_y_plus - vec_y_minus) * 0.5;
-		vec3 z_derivative = (vec_z_plus - vec_z_minus) * 0.5;
-
-		vec3 curl =
-			vec3(
-				y_derivative.z - z_derivative.y,
-				z_derivative.x - x_derivative.z,
-				x_derivative.y - y_derivative.x );
+		ivec3 result = coord;
+		for(int i = 0; i < 3; ++i)
+		{
+			if(periodic_axes[i] != 0)
+			{
+				int size = int(field_size[i]);
+				result[i] = (coord[i] + size) % size;
+			}
+		}
+		return result;
+	}
 
-		// TODO - use also current density here.
-		// TODO - ajust this value with medium electric constant.
-		vec3 electric_field_change = curl * dt;
-		electric_vecs[address_center] += vec4(electric_field_change, 0.0);
+	uint get_address(ivec3 coord)
+	{
+		uvec3 c = uvec3(clamp(wrap_coord(coord), ivec3(0, 0, 0), ivec3(field_size) - ivec3(1, 1, 1)));
+		return c.x + c.y * field_size.x + c.z * (field_size.x * field_size.y);
 	}
-"#;
 
-const SHADER_MAGNETIC_UPDATE: &str = r#"
-	#version 430
-	layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
+	// Dispersion poles - MAX_DISPERSION_POLES values for each material.
+	// Each value is (pole type, pole parameters), pole type is 0 for absent pole, 1 for Drude, 2 for Lorentz, 3 for Debye.
+	const uint c_max_dispersion_poles = 3u;
+	uniform bool dispersion_enabled;
+	layout(std430) buffer material_poles_data
+	{
+		vec4 material_poles[];
+	};
+	// Current and previous polarization for each pole of each cell.
+	layout(std430) buffer polarization_data
+	{
+		vec4 polarization_vecs[];
+	};
 
-	uniform float dt;
-	uniform uvec3 field_size;
-	layout(std430) buffer electric_field_data
+	// Each material is (chi2, chi3, inverse square of saturation field, unused).
+	uniform bool nonlinearity_enabled;
+	layout(std430) buffer material_nonlinearities_data
 	{
-		vec4 electric_vecs[];
+		vec4 material_nonlinearities[];
 	};
-	layout(std430) buffer magnetic_field_data
+
+	// Six values for each material - rows of permittivity and permeability tensors.
+	// Last component of first row of each tensor is 1 if tensor is present and 0 otherwise.
+	uniform bool anisotropy_enabled;
+	layout(std430) buffer material_tensors_data
 	{
-		vec4 magnetic_vecs[];
+		vec4 material_tensors[];
 	};
 
-	void main()
+	uniform bool current_density_enabled;
+	layout(std430) buffer current_density_data
+	{
+		vec4 current_density_vecs[];
+	};
+
+	mat3 make_diagonal_matrix(vec3 diagonal)
+	{
+		return mat3(diagonal.x, 0.0, 0.0, 0.0, diagonal.y, 0.0, 0.0, 0.0, diagonal.z);
+	}
+
+	// Thin wires - index of cell data plus one for each cell, zero if cell is not affected by wires.
+	// Cell data is (scales of derivatives along X, Y, Z axes, mask of electric field components).
+	uniform bool thin_wires_enabled;
+	layout(std430) buffer thin_wire_cell_ids_data
+	{
+		uint thin_wire_cell_ids[];
+	};
+	layout(std430) buffer thin_wire_cells_data
+	{
+		vec4 thin_wire_cells[];
+	};
+
+	// Calculate coefficients of semi-implicit update "new = self_matrix * old + inverse_matrix * (curl * dt)".
+	// Tensor index is 0 for permittivity and 1 for permeability. Scalar value is used if there is no tensor.
+	// Extra values and conductivities (like ones of lumped elements) are added to diagonal.
+	void get_update_matrices(
+		uint material_id,
+		uint tensor_index,
+		float scalar_value,
+		float conductivity,
+		vec3 extra_value,
+		vec3 extra_conductivity,
+		out mat3 self_matrix,
+		out mat3 inverse_matrix)
+	{
+		vec3 half_loss = 0.5 * (conductivity + extra_conductivity) * dt;
+		if(anisotropy_enabled)
+		{
+			uint address = (material_id * 2u + tensor_index) * 3u;
+			vec4 row0 = material_tensors[address];
+			if(row0.w != 0.0)
+			{
+				// Matrix constructor takes columns.
+				vec3 row1 = material_tensors[address + 1].xyz;
+				vec3 row2 = material_tensors[address + 2].xyz;
+				mat3 value_matrix = transpose(mat3(row0.xyz, row1, row2)) + make_diagonal_matrix(extra_value);
+				mat3 loss_matrix = make_diagonal_matrix(half_loss);
+				inverse_matrix = inverse(value_matrix + loss_matrix);
+				self_matrix = inverse_matrix * (value_matrix - loss_matrix);
+				return;
+			}
+		}
+
+		vec3 value = scalar_value + extra_value;
+		inverse_matrix = make_diagonal_matrix(1.0 / (value + half_loss));
+		self_matrix = make_diagonal_matrix((value - half_loss) / (value + half_loss));
+	}
+
+	// Calculate conductivity of absorbing layer (relative to medium permittivity or permeability).
+	// Depth and width of layer are measured in length units, between cell centers.
+	float get_absorbing_layer_conductivity(ivec3 coord)
+	{
+		// Use cubic grading of conductivity, maximum value is chosen for reflection coefficient about 1e-6.
+		const float c_grading_power = 3.0;
+		const float c_log_reflection = 13.8;
+
+		float result = 0.0;
+		for(int i = 0; i < 3; ++i)
+		{
+			int width = int(absorbing_layer_width[i]);
+			if(width == 0)
+			{
+				continue;
+			}
+			int last = int(field_size[i]) - 1;
+			float position = get_cell_center(i, coord[i]);
+			float depth, layer_width;
+			if(coord[i] < width)
+			{
+				float inner = get_cell_center(i, min(width, last));
+				depth = inner - position;
+				layer_width = inner - get_cell_center(i, 0);
+			}
+			else
+			{
+				float inner = get_cell_center(i, max(last - width, 0));
+				depth = position - inner;
+				layer_width = get_cell_center(i, last) - inner;
+			}
+			if(depth > 0.0)
+			{
+				float max_conductivity = (c_grading_power + 1.0) * c_log_reflection / (2.0 * layer_width);
+				result += max_conductivity * pow(depth / layer_width, c_grading_power);
+			}
+		}
+		return result;
+	}
+
+	bool is_inside_total_field_region(ivec3 coord)
+	{
+		return all(greaterThanEqual(coord, ivec3(tfsf_box_min))) && all(lessThan(coord, ivec3(tfsf_box_max)));
+	}
+
+	float sample_waveform(float time)
+	{
+		float position = time / waveform_time_step;
+		if(position < 0.0 || position > float(waveform_samples_count - 1))
+		{
+			return 0.0;
+		}
+		uint index = min(uint(position), max(waveform_samples_count, 2) - 2);
+		float k = position - float(index);
+		return mix(waveform_samples[index], waveform_samples[min(index + 1, waveform_samples_count - 1)], k);
+	}
+
+	vec3 get_incident_field(ivec3 coord, bool use_electric_field)
+	{
+		vec3 position = get_cell_position(coord);
+		float delay = dot(incident_direction, position - incident_reference_point);
+		if(use_electric_field)
+		{
+			return incident_polarization * sample_waveform(incident_electric_time - delay);
+		}
+		else
+		{
+			return cross(incident_direction, incident_polarization) * sample_waveform(incident_magnetic_time - delay);
+		}
+	}
+
+	// Fetch field value of neighbor cell.
+	// Value is converted into the same type (total or scattered) as field in center cell.
+	vec3 get_neighbor_value(ivec3 coord, ivec3 neighbor_coord, bool use_electric_field)
+	{
+		uint address = get_address(neighbor_coord);
+		vec3 value = use_electric_field ? electric_vecs[address].xyz : magnetic_vecs[address].xyz;
+
+		if(tfsf_enabled)
+		{
+			bool center_inside = is_inside_total_field_region(coord);
+			bool neighbor_inside = is_inside_total_field_region(wrap_coord(neighbor_coord));
+			if(center_inside && !neighbor_inside)
+			{
+				value += get_incident_field(neighbor_coord, use_electric_field);
+			}
+			else if(!center_inside && neighbor_inside)
+			{
+				value -= get_incident_field(neighbor_coord, use_electric_field);
+			}
+		}
+
+		return value;
+	}
+
+	// Weight of fourth-order correction of derivative along given axis for given cell (see "FOURTH_ORDER_FADE_WIDTH").
+	float get_fourth_order_weight(ivec3 coord, int axis)
+	{
+		if(thin_wires_enabled && thin_wire_cell_ids[get_address(coord)] != 0u)
+		{
+			return 0.0;
+		}
+		// Distance from border cells, where weight is zero.
+		int distance =
+			periodic_axes[axis] != 0 ?
+			fourth_order_fade_width :
+			min(coord[axis], int(field_size[axis]) - 1 - coord[axis]) - 2;
+		if(second_order_box_enabled)
+		{
+			ivec3 distances = max(ivec3(second_order_box_min) - coord, coord + 1 - ivec3(second_order_box_max));
+			distance = min(distance, max(distances.x, max(distances.y, distances.z)));
+		}
+		return smoothstep(0.0, float(fourth_order_fade_width), float(distance));
+	}
+
+	// Difference of neighbor values, multiplied by their weights of fourth-order correction.
+	vec3 get_weighted_difference(ivec3 coord, int axis, int distance, bool use_electric_field)
+	{
+		ivec3 offset = ivec3(0, 0, 0);
+		offset[axis] = distance;
+		return
+			get_neighbor_value(coord, coord + offset, use_electric_field) *
+				get_fourth_order_weight(wrap_coord(coord + offset), axis) -
+			get_neighbor_value(coord, coord - offset, use_electric_field) *
+				get_fourth_order_weight(wrap_coord(coord - offset), axis);
+	}
+
+	// Calculate derivative of given field along given axis using central differences in cell index space, divided by
+	// cell size. Fourth-order correction is faded out near non-periodic borders, thin wires and second-order box.
+	vec3 calculate_derivative(ivec3 coord, int axis, bool use_electric_field)
+	{
+		ivec3 offset = ivec3(0, 0, 0);
+		offset[axis] = 1;
+		vec3 derivative =
+			(get_neighbor_value(coord, coord + offset, use_electric_field) -
+			get_neighbor_value(coord, coord - offset, use_electric_field)) * 0.5;
+		float weight = fourth_order_enabled ? get_fourth_order_weight(coord, axis) : 0.0;
+
+		// Cells with non-zero weight and their neighbors with non-zero weights are inside field.
+		if(weight > 0.0)
+		{
+			derivative +=
+				(get_weighted_difference(coord, axis, 1, use_electric_field) * (1.0 / 6.0) -
+				get_weighted_difference(coord, axis, 2, use_electric_field) * (1.0 / 12.0)) * weight;
+		}
+		return derivative / get_cell_spacing(axis, coord[axis]);
+	}
+
+	// Calculate curl of given field using central differences.
+	// Derivative along each axis of each component is multiplied by corresponding scale.
+	vec3 calculate_curl(ivec3 coord, bool use_electric_field, mat3 derivative_scale)
 	{
-		uvec3 coord = gl_GlobalInvocationID;
-
-		uint layer_size = field_size.x * field_size.y;
-		uint address_center  = coord.x + coord.y * field_size.x + coord.z * layer_size;
-		uint address_x_minus = (max(coord.x, 1) - 1) + coord.y * field_size.x + coord.z * layer_size;
-		uint address_y_minus = coord.x + (max(coord.y, 1) - 1) * field_size.x + coord.z * layer_size;
-		uint address_z_minus = coord.x + coord.y * field_size.x + (max(coord.z, 1) - 1) * layer_size;
-		uint address_x_plus = (min(coord.x, field_size.x - 2) + 1) + coord.y * field_size.x + coord.z * layer_size;
-		uint address_y_plus = coord.x + (min(coord.y, field_size.y - 2) + 1) * field_size.x + coord.z * layer_size;
-		uint address_z_plus = coord.x + coord.y * field_size.x + (min(coord.z, field_size.z - 2) + 1) * layer_size;
-
-		vec3 vec_center  = electric_vecs[address_center ].xyz;
-		vec3 vec_x_minus = electric_vecs[address_x_minus].xyz;
-		vec3 vec_y_minus = electric_vecs[address_y_minus].xyz;
-		vec3 vec_z_minus = electric_vecs[address_z_minus].xyz;
-		vec3 vec_x_plus = electric_vecs[address_x_plus].xyz;
-		vec3 vec_y_plus = electric_vecs[address_y_plus].xyz;
-		vec3 vec_z_plus = electric_vecs[address_z_plus].xyz;
-
-		vec3 x_derivative = (vec_x_plus - vec_x_minus) * 0.5;
-		vec3 y_derivative = (vec_y_plus - vec_y_minus) * 0.5;
-		vec3 z_derivative = (vec_z_plus - vec_z_minus) * 0.5;
-
-		vec3 curl =
+		vec3 x_derivative = calculate_derivative(coord, 0, use_electric_field) * derivative_scale[0];
+		vec3 y_derivative = calculate_derivative(coord, 1, use_electric_field) * derivative_scale[1];
+		vec3 z_derivative = calculate_derivative(coord, 2, use_electric_field) * derivative_scale[2];
+
+		return
 			vec3(
 				y_derivative.z - z_derivative.y,
 				z_derivative.x - x_derivative.z,
 				x_derivative.y - y_derivative.x );
+	}
+"#;
+
+const SHADER_ELECTRIC_UPDATE: &str = r#"
+	// Lumped elements - parallel connections of conductance, capacitance, inductance and current source.
+	uniform bool lumped_elements_enabled;
+	// Index of element plus one for each cell, zero if there is no element.
+	layout(std430) buffer lumped_element_ids_data
+	{
+		uint lumped_element_ids[];
+	};
+	// Two values for each element - (axis, conductance, capacitance, inverse inductance), scaled by ratio of cell
+	// length along axis to cell face area, and (cell length, cell face area, unused, unused).
+	// So current is converted into current density and voltage into electric field.
+	layout(std430) buffer lumped_elements_data
+	{
+		vec4 lumped_elements[];
+	};
+	layout(std430) buffer lumped_element_source_currents_data
+	{
+		float lumped_element_source_currents[];
+	};
+	// Each element is (inductor current density, voltage, current, unused).
+	layout(std430) buffer lumped_element_states_data
+	{
+		vec4 lumped_element_states[];
+	};
+
+	// Calculate nonlinear polarization and its derivatives (for each component by the same component).
+	vec3 get_nonlinear_polarization(vec3 electric_vec, vec4 nonlinearity, out vec3 derivative)
+	{
+		float magnitude2 = dot(electric_vec, electric_vec);
+		float saturation_denominator = 1.0 / (1.0 + magnitude2 * nonlinearity.z);
+		float kerr_coefficient = nonlinearity.y * magnitude2 * saturation_denominator;
+		float kerr_coefficient_derivative = nonlinearity.y * saturation_denominator * saturation_denominator;
+
+		derivative =
+			2.0 * nonlinearity.x * electric_vec +
+			kerr_coefficient +
+			2.0 * kerr_coefficient_derivative * electric_vec * electric_vec;
+		return nonlinearity.x * electric_vec * electric_vec + kerr_coefficient * electric_vec;
+	}
+
+	void main()
+	{
+		ivec3 coord = ivec3(gl_GlobalInvocationID);
+		uint address_center = get_address(coord);
+
+		const vec3 c_ones = vec3(1.0, 1.0, 1.0);
+		vec3 curl = calculate_curl(coord, false, mat3(c_ones, c_ones, c_ones));
+
+		vec4 material = materials[material_ids[address_center]];
+		float permittivity = material.x;
+		float conductivity = material.z + get_absorbing_layer_conductivity(coord) * permittivity;
+
+		// Capacitance and conductance of lumped element are added to permittivity and conductivity along its axis.
+		uint lumped_element_index = 0u;
+		vec4 lumped_element = vec4(0.0, 0.0, 0.0, 0.0);
+		vec3 lumped_element_mask = vec3(0.0, 0.0, 0.0);
+		if(lumped_elements_enabled)
+		{
+			lumped_element_index = lumped_element_ids[address_center];
+			if(lumped_element_index != 0u)
+			{
+				lumped_element = lumped_elements[(lumped_element_index - 1u) * 2u];
+				lumped_element_mask[int(lumped_element.x)] = 1.0;
+			}
+		}
+
+		// Use semi-implicit scheme for conductivity.
+		mat3 self_matrix, inverse_matrix;
+		get_update_matrices(
+			material_ids[address_center],
+			0u,
+			permittivity,
+			conductivity,
+			lumped_element_mask * lumped_element.z,
+			lumped_element_mask * lumped_element.y,
+			self_matrix,
+			inverse_matrix);
+
+		vec3 electric_vec = electric_vecs[address_center].xyz;
+		vec3 new_electric_vec = self_matrix * electric_vec + inverse_matrix * (curl * dt);
+		if(current_density_enabled)
+		{
+			new_electric_vec -= inverse_matrix * (current_density_vecs[address_center].xyz * dt);
+		}
+
+		// Inductor current density (at half step) is advanced using current electric field, source current is given at
+		// half step.
+		float inductor_current = 0.0;
+		float source_current = 0.0;
+		if(lumped_element_index != 0u)
+		{
+			int axis = int(lumped_element.x);
+			inductor_current =
+				lumped_element_states[lumped_element_index - 1u].x + dt * lumped_element.w * electric_vec[axis];
+			source_current =
+				lumped_element_source_currents[lumped_element_index - 1u] /
+				lumped_elements[(lumped_element_index - 1u) * 2u + 1u].y;
+			new_electric_vec -= inverse_matrix * (lumped_element_mask * ((inductor_current - source_current) * dt));
+		}
+
+		if(dispersion_enabled)
+		{
+			// Advance polarization of each pole using current electric field, then subtract polarization current.
+			vec3 polarization_delta = vec3(0.0, 0.0, 0.0);
+			for(uint i = 0u; i < c_max_dispersion_poles; ++i)
+			{
+				vec4 pole = material_poles[material_ids[address_center] * c_max_dispersion_poles + i];
+				uint polarization_address = (address_center * c_max_dispersion_poles + i) * 2;
+				vec3 polarization = polarization_vecs[polarization_address].xyz;
+				vec3 prev_polarization = polarization_vecs[polarization_address + 1].xyz;
+
+				vec3 new_polarization;
+				if(pole.x == 1.0 || pole.x == 2.0)
+				{
+					// Drude is Lorentz with zero resonance frequency.
+					// Solve "P'' + g * P' + w0^2 * P = de * w0^2 * E" using central differences.
+					float resonance_frequency = pole.x == 1.0 ? 0.0 : pole.z;
+					float damping = pole.x == 1.0 ? pole.z : pole.w;
+					float source_coefficient = pole.x == 1.0 ? pole.y * pole.y : pole.y * pole.z * pole.z;
+					float half_damping = 0.5 * damping * dt;
+					new_polarization =
+						(
+							(2.0 - resonance_frequency * resonance_frequency * dt * dt) * polarization +
+							(half_damping - 1.0) * prev_polarization +
+							(source_coefficient * dt * dt) * electric_vec
+						) / (1.0 + half_damping);
+				}
+				else if(pole.x == 3.0)
+				{
+					// Solve "t * P' + P = de * E" using trapezoidal rule for polarization.
+					float relaxation = pole.z / dt;
+					new_polarization = ((relaxation - 0.5) * polarization + pole.y * electric_vec) / (relaxation + 0.5);
+				}
+				else
+				{
+					continue;
+				}
+
+				polarization_vecs[polarization_address] = vec4(new_polarization, 0.0);
+				polarization_vecs[polarization_address + 1] = vec4(polarization, 0.0);
+				polarization_delta += new_polarization - polarization;
+			}
+			new_electric_vec -= inverse_matrix * polarization_delta;
+		}
+
+		if(nonlinearity_enabled)
+		{
+			vec4 nonlinearity = material_nonlinearities[material_ids[address_center]];
+			if(nonlinearity.x != 0.0 || nonlinearity.y != 0.0)
+			{
+				// Nonlinear polarization change is subtracted like polarization of dispersive materials.
+				// Solve resulting equation for new electric field using Newton's method.
+				const int c_num_iterations = 3;
+				// Use only diagonal part of inverse matrix for derivative.
+				vec3 inverse_diagonal = vec3(inverse_matrix[0][0], inverse_matrix[1][1], inverse_matrix[2][2]);
+				vec3 linear_electric_vec = new_electric_vec;
+				vec3 derivative;
+				vec3 polarization = get_nonlinear_polarization(electric_vec, nonlinearity, derivative);
+				for(int i = 0; i < c_num_iterations; ++i)
+				{
+					vec3 new_polarization = get_nonlinear_polarization(new_electric_vec, nonlinearity, derivative);
+					vec3 residual =
+						new_electric_vec - linear_electric_vec + inverse_matrix * (new_polarization - polarization);
+					// Limit derivative in order to avoid divergence for strong negative second-order polarization.
+					new_electric_vec -=
+						residual / max(vec3(1.0, 1.0, 1.0) + derivative * inverse_diagonal, vec3(0.1, 0.1, 0.1));
+				}
+			}
+		}
+
+		// Components, which aren't used in reduced simulation mode, are kept zero.
+		new_electric_vec *= electric_component_mask;
+
+		// Tangential electric field is zero on thin wires.
+		if(thin_wires_enabled)
+		{
+			uint thin_wire_cell_id = thin_wire_cell_ids[address_center];
+			if(thin_wire_cell_id != 0u)
+			{
+				new_electric_vec *= thin_wire_cells[(thin_wire_cell_id - 1u) * 4u + 3u].xyz;
+			}
+		}
+
+		if(lumped_element_index != 0u)
+		{
+			int axis = int(lumped_element.x);
+			vec4 cell_geometry = lumped_elements[(lumped_element_index - 1u) * 2u + 1u];
+			float electric_value = 0.5 * (electric_vec[axis] + new_electric_vec[axis]);
+			float current_density =
+				lumped_element.y * electric_value +
+				lumped_element.z * (new_electric_vec[axis] - electric_vec[axis]) / dt +
+				inductor_current -
+				source_current;
+			lumped_element_states[lumped_element_index - 1u] =
+				vec4(inductor_current, electric_value * cell_geometry.x, current_density * cell_geometry.y, 0.0);
+		}
+
+		electric_vecs[address_center] = vec4(new_electric_vec, 0.0);
+	}
+"#;
+
+const SHADER_MAGNETIC_UPDATE: &str = r#"
+	void main()
+	{
+		ivec3 coord = ivec3(gl_GlobalInvocationID);
+		uint address_center = get_address(coord);
+
+		// Near thin wires derivatives of electric field are corrected.
+		const vec3 c_ones = vec3(1.0, 1.0, 1.0);
+		mat3 derivative_scale = mat3(c_ones, c_ones, c_ones);
+		if(thin_wires_enabled)
+		{
+			uint thin_wire_cell_id = thin_wire_cell_ids[address_center];
+			if(thin_wire_cell_id != 0u)
+			{
+				uint offset = (thin_wire_cell_id - 1u) * 4u;
+				derivative_scale =
+					mat3(
+						thin_wire_cells[offset].xyz,
+						thin_wire_cells[offset + 1u].xyz,
+						thin_wire_cells[offset + 2u].xyz);
+			}
+		}
+		vec3 curl = calculate_curl(coord, true, derivative_scale);
+
+		vec4 material = materials[material_ids[address_center]];
+		float permeability = material.y;
+		// Absorbing layer is matched - both conductivities are scaled in order to keep layer impedance equal to
+		// impedance of medium.
+		float magnetic_conductivity = material.w + get_absorbing_layer_conductivity(coord) * permeability;
+
+		mat3 self_matrix, inverse_matrix;
+		get_update_matrices(
+			material_ids[address_center],
+			1u,
+			permeability,
+			magnetic_conductivity,
+			vec3(0.0, 0.0, 0.0),
+			vec3(0.0, 0.0, 0.0),
+			self_matrix,
+			inverse_matrix);
 
-		// TODO - ajust this value with medium magnetic constant.
-		vec3 magnetic_field_change = -curl * dt;
-		magnetic_vecs[address_center] += vec4(magnetic_field_change, 0.0);
+		vec3 magnetic_vec = magnetic_vecs[address_center].xyz;
+		vec3 new_magnetic_vec = self_matrix * magnetic_vec - inverse_matrix * (curl * dt);
+		magnetic_vecs[address_center] = vec4(new_magnetic_vec * magnetic_component_mask, 0.0);
 	}
 "#;
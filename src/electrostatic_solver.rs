@@ -0,0 +1,67 @@
+use super::{poisson_solver::*, scalar_grid::*};
+
+// Description of electrostatic problem. Medium is assumed to be vacuum, potential outside grid is zero.
+pub struct ElectrostaticSetup
+{
+	pub charge_density: ScalarGrid,
+	// Potentials of cells, occupied by conductors.
+	pub conductor_potentials: Vec<Option<f32>>,
+}
+
+impl ElectrostaticSetup
+{
+	// Create setup without charges and conductors.
+	pub fn new(size: [u32; 3]) -> Self
+	{
+		Self {
+			charge_density: ScalarGrid::new(size),
+			conductor_potentials: vec![None; (size[0] * size[1] * size[2]) as usize],
+		}
+	}
+
+	// Fill box [min; max) with conductor with given potential.
+	pub fn add_conductor_box(&mut self, min: [u32; 3], max: [u32; 3], potential: f32)
+	{
+		let size = self.charge_density.get_size();
+		for z in min[2] .. max[2].min(size[2])
+		{
+			for y in min[1] .. max[1].min(size[1])
+			{
+				for x in min[0] .. max[0].min(size[0])
+				{
+					let address = self.charge_density.get_address(x, y, z);
+					self.conductor_potentials[address] = Some(potential);
+				}
+			}
+		}
+	}
+}
+
+// Calculate electrostatic field (as data for vector field).
+// Field is calculated as negative central-difference gradient of potential, so its central-difference divergence
+// (used in field update) is exactly equal to charge density outside conductors.
+// Charge density should be smooth on grid scale (like one produced by particles), otherwise field has checkerboard
+// pattern, which is inherent for grid with all field components in cell centers.
+pub fn solve_electrostatic_field(setup: &ElectrostaticSetup) -> Vec<[f32; 4]>
+{
+	let size = setup.charge_density.get_size();
+	let potential = solve_central_difference_poisson(&setup.charge_density, &setup.conductor_potentials);
+
+	let mut result = vec![[0.0; 4]; potential.get_values().len()];
+	for z in 0 .. size[2] as i32
+	{
+		for y in 0 .. size[1] as i32
+		{
+			for x in 0 .. size[0] as i32
+			{
+				result[potential.get_address(x as u32, y as u32, z as u32)] = [
+					0.5 * (potential.get(x - 1, y, z) - potential.get(x + 1, y, z)),
+					0.5 * (potential.get(x, y - 1, z) - potential.get(x, y + 1, z)),
+					0.5 * (potential.get(x, y, z - 1) - potential.get(x, y, z + 1)),
+					0.0,
+				];
+			}
+		}
+	}
+	result
+}
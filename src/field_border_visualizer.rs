@@ -52,9 +52,10 @@ impl FieldBorderVisualizer
 		}
 	}
 
-	pub fn visualize<S: glium::Surface>(&self, surface: &mut S, view_matrix: &Mat4f, size: &[u32; 3])
+	// Draw box with given size (in length units).
+	pub fn visualize<S: glium::Surface>(&self, surface: &mut S, view_matrix: &Mat4f, extent: Vec3f)
 	{
-		let matrix = view_matrix * Mat4f::from_nonuniform_scale(size[0] as f32, size[1] as f32, size[2] as f32);
+		let matrix = view_matrix * Mat4f::from_nonuniform_scale(extent.x, extent.y, extent.z);
 
 		let uniforms = glium::uniform! {
 			matrix: make_uniform_matrix(&matrix)
@@ -0,0 +1,295 @@
+use super::{electromagnetic_field::*, grid_spacing::*, medium::*};
+use std::io::Write;
+
+// Tracker of total electromagnetic energy and maximum field magnitudes. Energy density is multiplied by cell volumes.
+// Values are calculated via GPU reduction and accumulated into time series.
+// In lossless closed domain total energy should stay constant, so it may be used for stability checks.
+pub struct FieldEnergyTracker
+{
+	shader_reduce_cells: glium::program::ComputeShader,
+	shader_reduce_partial_sums: glium::program::ComputeShader,
+	partial_sums_buffer: glium::buffer::Buffer<[[f32; 4]]>,
+	// Results of reduction, which are not yet read back from GPU.
+	pending_samples_buffer: glium::buffer::Buffer<[[f32; 4]]>,
+	pending_samples_times: Vec<f32>,
+	samples: Vec<FieldEnergySample>,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct FieldEnergySample
+{
+	pub time: f32,
+	pub electric_energy: f32,
+	pub magnetic_energy: f32,
+	pub max_electric_field: f32,
+	pub max_magnetic_field: f32,
+}
+
+impl FieldEnergySample
+{
+	pub fn get_total_energy(&self) -> f32
+	{
+		self.electric_energy + self.magnetic_energy
+	}
+}
+
+impl FieldEnergyTracker
+{
+	pub fn new(display: &glium::Display, field_size: [u32; 3]) -> Self
+	{
+		let cell_count = field_size[0] * field_size[1] * field_size[2];
+		let group_count = cell_count.div_ceil(GROUP_SIZE) as usize;
+
+		Self {
+			shader_reduce_cells: glium::program::ComputeShader::from_source(
+				display,
+				&[SHADER_REDUCE_CELLS_HEADER, SHADER_GRID_SPACING, SHADER_REDUCE_CELLS].concat(),
+			)
+			.unwrap(),
+			shader_reduce_partial_sums: glium::program::ComputeShader::from_source(
+				display,
+				SHADER_REDUCE_PARTIAL_SUMS,
+			)
+			.unwrap(),
+			partial_sums_buffer: glium::buffer::Buffer::empty_array(
+				display,
+				glium::buffer::BufferType::ShaderStorageBuffer,
+				group_count,
+				glium::buffer::BufferMode::Default,
+			)
+			.unwrap(),
+			pending_samples_buffer: glium::buffer::Buffer::empty_array(
+				display,
+				glium::buffer::BufferType::ShaderStorageBuffer,
+				MAX_PENDING_SAMPLES,
+				glium::buffer::BufferMode::Default,
+			)
+			.unwrap(),
+			pending_samples_times: Vec::with_capacity(MAX_PENDING_SAMPLES),
+			samples: Vec::new(),
+		}
+	}
+
+	// Calculate energy of field at given time. Result is available after flush.
+	pub fn record(&mut self, field: &ElectromagneticField, medium_buffers: &MediumBuffers, time: f32)
+	{
+		if self.pending_samples_times.len() == MAX_PENDING_SAMPLES
+		{
+			self.flush();
+		}
+
+		let field_size = field.electric_field.get_size();
+		assert_eq!(field_size, medium_buffers.get_size());
+
+		let cell_count = field_size[0] * field_size[1] * field_size[2];
+		let group_count = self.partial_sums_buffer.len() as u32;
+
+		let uniforms = glium::uniform! {
+			field_size: field_size,
+			cell_count: cell_count,
+			grid_spacing_data: medium_buffers.get_grid_spacing_buffer(),
+			electric_field_data: field.electric_field.get_buffer(),
+			magnetic_field_data: field.magnetic_field.get_buffer(),
+			material_ids_data: medium_buffers.get_material_ids_buffer(),
+			materials_data: medium_buffers.get_materials_buffer(),
+			partial_sums_data: &self.partial_sums_buffer,
+		};
+		self.shader_reduce_cells.execute(uniforms, group_count, 1, 1);
+
+		let uniforms = glium::uniform! {
+			partial_sums_count: group_count,
+			result_index: self.pending_samples_times.len() as u32,
+			partial_sums_data: &self.partial_sums_buffer,
+			result_data: &self.pending_samples_buffer,
+		};
+		self.shader_reduce_partial_sums.execute(uniforms, 1, 1, 1);
+
+		self.pending_samples_times.push(time);
+	}
+
+	// Read pending results from GPU.
+	pub fn flush(&mut self)
+	{
+		if self.pending_samples_times.is_empty()
+		{
+			return;
+		}
+
+		let data = self
+			.pending_samples_buffer
+			.slice(0 .. self.pending_samples_times.len())
+			.unwrap()
+			.read()
+			.unwrap();
+
+		for (time, values) in self.pending_samples_times.iter().zip(data.iter())
+		{
+			self.samples.push(FieldEnergySample {
+				time: *time,
+				electric_energy: values[0],
+				magnetic_energy: values[1],
+				max_electric_field: values[2],
+				max_magnetic_field: values[3],
+			});
+		}
+		self.pending_samples_times.clear();
+	}
+
+	pub fn get_samples(&self) -> &[FieldEnergySample]
+	{
+		&self.samples
+	}
+
+	// Export recorded time series into CSV file.
+	pub fn export(&self, file_path: &std::path::Path) -> std::io::Result<()>
+	{
+		let mut file = std::io::BufWriter::new(std::fs::File::create(file_path)?);
+		writeln!(
+			file,
+			"time,electric_energy,magnetic_energy,total_energy,max_electric_field,max_magnetic_field"
+		)?;
+		for s in &self.samples
+		{
+			writeln!(
+				file,
+				"{},{},{},{},{},{}",
+				s.time,
+				s.electric_energy,
+				s.magnetic_energy,
+				s.get_total_energy(),
+				s.max_electric_field,
+				s.max_magnetic_field
+			)?;
+		}
+		file.flush()
+	}
+}
+
+const GROUP_SIZE: u32 = 256;
+const MAX_PENDING_SAMPLES: usize = 64;
+
+const SHADER_REDUCE_CELLS_HEADER: &str = r#"
+	#version 430
+	layout(local_size_x = 256, local_size_y = 1, local_size_z = 1) in;
+
+	uniform uvec3 field_size;
+"#;
+
+// Each work group calculates sums of energies and maximum field magnitudes for its cells.
+const SHADER_REDUCE_CELLS: &str = r#"
+	uniform uint cell_count;
+	layout(std430) buffer electric_field_data
+	{
+		vec4 electric_vecs[];
+	};
+	layout(std430) buffer magnetic_field_data
+	{
+		vec4 magnetic_vecs[];
+	};
+	layout(std430) buffer material_ids_data
+	{
+		uint material_ids[];
+	};
+	layout(std430) buffer materials_data
+	{
+		vec4 materials[];
+	};
+	// (electric energy, magnetic energy, max electric field, max magnetic field) for each group.
+	layout(std430) buffer partial_sums_data
+	{
+		vec4 partial_sums[];
+	};
+
+	shared vec4 shared_values[256];
+
+	void main()
+	{
+		uint address = gl_GlobalInvocationID.x;
+		uint local_index = gl_LocalInvocationID.x;
+
+		vec4 value = vec4(0.0, 0.0, 0.0, 0.0);
+		if(address < cell_count)
+		{
+			vec3 electric_vec = electric_vecs[address].xyz;
+			vec3 magnetic_vec = magnetic_vecs[address].xyz;
+			vec4 material = materials[material_ids[address]];
+			uint layer_size = field_size.x * field_size.y;
+			ivec3 coord = ivec3(address % field_size.x, (address % layer_size) / field_size.x, address / layer_size);
+			vec3 cell_size = get_cell_size(coord);
+			float volume = cell_size.x * cell_size.y * cell_size.z;
+			value =
+				vec4(
+					0.5 * volume * material.x * dot(electric_vec, electric_vec),
+					0.5 * volume * material.y * dot(magnetic_vec, magnetic_vec),
+					length(electric_vec),
+					length(magnetic_vec));
+		}
+		shared_values[local_index] = value;
+		barrier();
+
+		for(uint step = 128; step > 0; step >>= 1)
+		{
+			if(local_index < step)
+			{
+				vec4 a = shared_values[local_index];
+				vec4 b = shared_values[local_index + step];
+				shared_values[local_index] = vec4(a.xy + b.xy, max(a.zw, b.zw));
+			}
+			barrier();
+		}
+
+		if(local_index == 0)
+		{
+			partial_sums[gl_WorkGroupID.x] = shared_values[0];
+		}
+	}
+"#;
+
+// Single work group reduces all partial sums into one value.
+const SHADER_REDUCE_PARTIAL_SUMS: &str = r#"
+	#version 430
+	layout(local_size_x = 256, local_size_y = 1, local_size_z = 1) in;
+
+	uniform uint partial_sums_count;
+	uniform uint result_index;
+	layout(std430) buffer partial_sums_data
+	{
+		vec4 partial_sums[];
+	};
+	layout(std430) buffer result_data
+	{
+		vec4 results[];
+	};
+
+	shared vec4 shared_values[256];
+
+	void main()
+	{
+		uint local_index = gl_LocalInvocationID.x;
+
+		vec4 value = vec4(0.0, 0.0, 0.0, 0.0);
+		for(uint i = local_index; i < partial_sums_count; i += 256)
+		{
+			vec4 s = partial_sums[i];
+			value = vec4(value.xy + s.xy, max(value.zw, s.zw));
+		}
+		shared_values[local_index] = value;
+		barrier();
+
+		for(uint step = 128; step > 0; step >>= 1)
+		{
+			if(local_index < step)
+			{
+				vec4 a = shared_values[local_index];
+				vec4 b = shared_values[local_index + step];
+				shared_values[local_index] = vec4(a.xy + b.xy, max(a.zw, b.zw));
+			}
+			barrier();
+		}
+
+		if(local_index == 0)
+		{
+			results[result_index] = shared_values[0];
+		}
+	}
+"#;
@@ -0,0 +1,146 @@
+use super::{grid_spacing::*, medium::*, vector_field::*};
+
+// Draws one component of field slice as heatmap on top of the screen. Intended for reduced-dimension simulations,
+// where whole field is single slice (two-dimensional) or single line (one-dimensional). Positive values are red,
+// negative ones are blue.
+pub struct FieldHeatmapVisualizer
+{
+	program: glium::Program,
+}
+
+impl FieldHeatmapVisualizer
+{
+	pub fn new(display: &glium::Display) -> Self
+	{
+		let program = glium::Program::from_source(
+			display,
+			VERTEX_SHADER,
+			&[FRAGMENT_SHADER_HEADER, SHADER_GRID_SPACING, FRAGMENT_SHADER].concat(),
+			None,
+		)
+		.unwrap();
+		Self { program }
+	}
+
+	// Draw given field component inside rectangle (in normalized device coordinates - x, y, width, height).
+	// Values are scaled with given scale and clamped to [-1, 1].
+	// See "get_plane_axes" for axes of rectangle. Slice with zero index along remaining axis is shown. Rectangle is
+	// filled by whole extent of grid along its axes, cells are scaled by grid spacing.
+	pub fn visualize<S: glium::Surface>(
+		&self,
+		surface: &mut S,
+		vector_field: &VectorField,
+		medium_buffers: &MediumBuffers,
+		component_index: u32,
+		value_scale: f32,
+		rect: [f32; 4],
+	)
+	{
+		assert!(component_index < 3);
+		let field_size = vector_field.get_size();
+		assert_eq!(field_size, medium_buffers.get_size());
+
+		let plane_axes = get_plane_axes(field_size);
+		let extent = medium_buffers.get_grid_spacing().get_extent();
+
+		let uniforms = glium::uniform! {
+			field_size: field_size,
+			plane_axes: plane_axes.map(|axis| axis as u32),
+			plane_extent: plane_axes.map(|axis| extent[axis]),
+			component_index: component_index,
+			value_scale: value_scale,
+			rect: rect,
+			grid_spacing_data: medium_buffers.get_grid_spacing_buffer(),
+			field_data: vector_field.get_buffer(),
+		};
+
+		surface
+			.draw(
+				glium::vertex::EmptyVertexAttributes { len: 4 },
+				glium::index::IndicesSource::NoIndices {
+					primitives: glium::index::PrimitiveType::TriangleStrip,
+				},
+				&self.program,
+				&uniforms,
+				&Default::default(),
+			)
+			.unwrap();
+	}
+}
+
+// Horizontal and vertical axes of heatmap of field with given size. Axes with more than one cell are used first, so
+// field of two-dimensional simulation fills rectangle and field of one-dimensional simulation ("[1, 1, N]") is drawn
+// as horizontal strip.
+pub fn get_plane_axes(field_size: [u32; 3]) -> [usize; 2]
+{
+	let mut axes = (0 .. 3).filter(|i| field_size[*i] > 1).chain((0 .. 3).filter(|i| field_size[*i] <= 1));
+	[axes.next().unwrap(), axes.next().unwrap()]
+}
+
+const VERTEX_SHADER: &str = r#"
+	#version 430
+
+	uniform vec4 rect;
+
+	out vec2 tex_coord;
+
+	void main()
+	{
+		tex_coord = vec2(float(gl_VertexID & 1), float(gl_VertexID >> 1));
+		gl_Position = vec4(rect.xy + rect.zw * tex_coord, 0.0, 1.0);
+	}
+"#;
+
+const FRAGMENT_SHADER_HEADER: &str = r#"
+	#version 430
+
+	uniform uvec3 field_size;
+"#;
+
+const FRAGMENT_SHADER: &str = r#"
+	uniform uvec2 plane_axes;
+	uniform vec2 plane_extent;
+	uniform uint component_index;
+	uniform float value_scale;
+
+	layout(std430) buffer field_data
+	{
+		vec4 vecs[];
+	};
+
+	in vec2 tex_coord;
+	out vec4 f_color;
+
+	// Find index of cell along given axis, which contains given position (in length units).
+	int find_cell(int axis, float position)
+	{
+		int low = 0;
+		int high = int(field_size[axis]) - 1;
+		while(low < high)
+		{
+			int middle = (low + high + 1) / 2;
+			if(get_cell_center(axis, middle) - 0.5 * get_cell_spacing(axis, middle) <= position)
+			{
+				low = middle;
+			}
+			else
+			{
+				high = middle - 1;
+			}
+		}
+		return low;
+	}
+
+	void main()
+	{
+		uvec3 coord = uvec3(0, 0, 0);
+		for(int i = 0; i < 2; ++i)
+		{
+			int axis = int(plane_axes[i]);
+			coord[axis] = uint(find_cell(axis, tex_coord[i] * plane_extent[i]));
+		}
+		uint address = coord.x + coord.y * field_size.x + coord.z * (field_size.x * field_size.y);
+		float value = clamp(vecs[address][component_index] * value_scale, -1.0, 1.0);
+		f_color = vec4(max(value, 0.0), 0.0, max(-value, 0.0), 1.0);
+	}
+"#;
@@ -0,0 +1,145 @@
+use super::electromagnetic_field::*;
+use std::io::Write;
+
+// Point probe, that records electric and magnetic field vectors in one cell each step.
+pub struct FieldProbe
+{
+	position: [u32; 3],
+	shader_record: glium::program::ComputeShader,
+	// Values, which are not yet read back from GPU - pairs of electric and magnetic vectors.
+	pending_samples_buffer: glium::buffer::Buffer<[[f32; 4]]>,
+	pending_samples_times: Vec<f32>,
+	samples: Vec<FieldProbeSample>,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct FieldProbeSample
+{
+	pub time: f32,
+	pub electric: [f32; 3],
+	pub magnetic: [f32; 3],
+}
+
+impl FieldProbe
+{
+	pub fn new(display: &glium::Display, position: [u32; 3]) -> Self
+	{
+		Self {
+			position,
+			shader_record: glium::program::ComputeShader::from_source(display, SHADER_RECORD).unwrap(),
+			pending_samples_buffer: glium::buffer::Buffer::empty_array(
+				display,
+				glium::buffer::BufferType::ShaderStorageBuffer,
+				MAX_PENDING_SAMPLES * 2,
+				glium::buffer::BufferMode::Default,
+			)
+			.unwrap(),
+			pending_samples_times: Vec::with_capacity(MAX_PENDING_SAMPLES),
+			samples: Vec::new(),
+		}
+	}
+
+	pub fn get_position(&self) -> [u32; 3]
+	{
+		self.position
+	}
+
+	// Record field values at given time. Result is available after flush.
+	pub fn record(&mut self, field: &ElectromagneticField, time: f32)
+	{
+		if self.pending_samples_times.len() == MAX_PENDING_SAMPLES
+		{
+			self.flush();
+		}
+
+		let field_size = field.electric_field.get_size();
+		let uniforms = glium::uniform! {
+			field_size: field_size,
+			probe_position: self.position,
+			result_index: self.pending_samples_times.len() as u32,
+			electric_field_data: field.electric_field.get_buffer(),
+			magnetic_field_data: field.magnetic_field.get_buffer(),
+			result_data: &self.pending_samples_buffer,
+		};
+		self.shader_record.execute(uniforms, 1, 1, 1);
+
+		self.pending_samples_times.push(time);
+	}
+
+	// Read pending results from GPU.
+	pub fn flush(&mut self)
+	{
+		if self.pending_samples_times.is_empty()
+		{
+			return;
+		}
+
+		let data = self
+			.pending_samples_buffer
+			.slice(0 .. self.pending_samples_times.len() * 2)
+			.unwrap()
+			.read()
+			.unwrap();
+
+		for (time, values) in self.pending_samples_times.iter().zip(data.chunks(2))
+		{
+			self.samples.push(FieldProbeSample {
+				time: *time,
+				electric: [values[0][0], values[0][1], values[0][2]],
+				magnetic: [values[1][0], values[1][1], values[1][2]],
+			});
+		}
+		self.pending_samples_times.clear();
+	}
+
+	pub fn get_samples(&self) -> &[FieldProbeSample]
+	{
+		&self.samples
+	}
+
+	// Export recorded time series into CSV file.
+	pub fn export(&self, file_path: &std::path::Path) -> std::io::Result<()>
+	{
+		let mut file = std::io::BufWriter::new(std::fs::File::create(file_path)?);
+		writeln!(file, "time,ex,ey,ez,hx,hy,hz")?;
+		for s in &self.samples
+		{
+			writeln!(
+				file,
+				"{},{},{},{},{},{},{}",
+				s.time, s.electric[0], s.electric[1], s.electric[2], s.magnetic[0], s.magnetic[1], s.magnetic[2]
+			)?;
+		}
+		file.flush()
+	}
+}
+
+const MAX_PENDING_SAMPLES: usize = 64;
+
+const SHADER_RECORD: &str = r#"
+	#version 430
+	layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
+
+	uniform uvec3 field_size;
+	uniform uvec3 probe_position;
+	uniform uint result_index;
+	layout(std430) buffer electric_field_data
+	{
+		vec4 electric_vecs[];
+	};
+	layout(std430) buffer magnetic_field_data
+	{
+		vec4 magnetic_vecs[];
+	};
+	layout(std430) buffer result_data
+	{
+		vec4 results[];
+	};
+
+	void main()
+	{
+		uint address = probe_position.x + probe_position.y * field_size.x + probe_position.z * (field_size.x * field_size.y);
+		results[result_index * 2 + 0] = electric_vecs[address];
+		results[result_index * 2 + 1] = magnetic_vecs[address];
+	}
+"#;
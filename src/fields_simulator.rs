@@ -1,32 +1,208 @@
 use super::{
-	electromagnetic_field::*, electromagnetic_field_updater, field_border_visualizer::*, math_types::*,
-	vector_field::*, vector_field_visualizer,
+	charged_particles::*, dft_field_monitor::*, divergence_tracker::*, electromagnetic_field::*,
+	electromagnetic_field_updater, electrostatic_solver::*, field_border_visualizer::*, field_energy_tracker::*,
+	field_heatmap_visualizer::*, field_probe::*, harmonic_inversion::*, lumped_element::*, magnetostatic_solver::*,
+	material_library::*, math_types::*, medium::*, near_to_far_field::*, numerical_dispersion::*,
+	particles_visualizer::*, plane_wave_source::*, radiation_pattern_visualizer::*, subgrid::*, thin_wire::*,
+	time_series_visualizer::*, vector_field::*, vector_field_visualizer, waveform::*,
 };
+use glium::glutin::event::VirtualKeyCode;
 
 pub struct FieldsSimulator
 {
 	electromagnetic_field: ElectromagneticField,
 	vector_field_visualizer: vector_field_visualizer::VectorFieldVisualizer,
 	field_updater: electromagnetic_field_updater::ElectromagneticFieldUpdater,
+	// Refined subgrid around dipole feed, lumped elements and thin wires are placed on it.
+	subgrid: Subgrid,
+	// Show field of subgrid instead of coarse field.
+	show_fine_field: bool,
 	field_border_visualizer: FieldBorderVisualizer,
+	dft_field_monitors: Vec<DftFieldMonitor>,
+	dft_view: Option<DftView>,
+	field_energy_tracker: FieldEnergyTracker,
+	divergence_tracker: DivergenceTracker,
+	// Show divergence of fields instead of fields themselves.
+	show_divergence: bool,
+	field_probe: FieldProbe,
+	lumped_elements_recorder: LumpedElementsRecorder,
+	huygens_surface: HuygensSurface,
+	radiation_pattern_visualizer: RadiationPatternVisualizer,
+	time_series_visualizer: TimeSeriesVisualizer,
+	charged_particles: ChargedParticles,
+	particles_visualizer: ParticlesVisualizer,
+	simulation_time: f32,
+	// Two-dimensional simulation of scatterer cross-section, it's updated only while shown.
+	cross_section_field: ElectromagneticField,
+	cross_section_updater: electromagnetic_field_updater::ElectromagneticFieldUpdater,
+	cross_section_time: f32,
+	show_cross_section: bool,
+	field_heatmap_visualizer: FieldHeatmapVisualizer,
+}
+
+// Frame time is used as time step, it's limited in main loop.
+const MAX_TIME_STEP: f32 = 0.1;
+// Fraction of maximum of source spectrum, which is considered significant for resolution check.
+const SOURCE_SPECTRUM_THRESHOLD: f32 = 0.01;
+
+// State of visualization of one of DFT monitors.
+struct DftView
+{
+	monitor_index: usize,
+	frequency_index: usize,
+	scale: f32,
+	electromagnetic_field: ElectromagneticField,
 }
 
 impl FieldsSimulator
 {
 	pub fn new(display: &glium::Display) -> Self
 	{
-		let electromagnetic_field = create_test_wave_field(&display);
-		let vector_field_visualizer = vector_field_visualizer::VectorFieldVisualizer::new(&display);
+		// Simulate scattering of plane wave pulse by dielectric sphere.
+		let field_size = [72, 192, 72];
 
-		let field_updater = electromagnetic_field_updater::ElectromagneticFieldUpdater::new(&display);
+		// Start with electrostatic field of particles.
+		let particles = create_test_particle_beam(field_size);
+		let charge_density = calculate_charge_density(field_size, &particles);
+		let mut electrostatic_setup = ElectrostaticSetup::new(field_size);
+		electrostatic_setup.charge_density = charge_density.clone();
+		let electromagnetic_field = ElectromagneticField {
+			electric_field: VectorField::new_with_data(
+				display,
+				field_size,
+				&solve_electrostatic_field(&electrostatic_setup),
+			),
+			magnetic_field: VectorField::new(display, field_size),
+		};
+		let vector_field_visualizer = vector_field_visualizer::VectorFieldVisualizer::new(display);
+
+		let medium = create_test_scatterer_medium(field_size);
+		let plane_wave_source = create_test_plane_wave_source(field_size);
+		// Dipole is placed on subgrid, so its elements are given in fine cells.
+		let lumped_elements = create_test_lumped_elements(
+			create_test_dipole_subgrid_params(field_size).get_fine_cell(get_test_dipole_feed(field_size)),
+		);
+		let max_frequency = lumped_elements
+			.iter()
+			.filter_map(|element| element.get_waveform())
+			.chain(std::iter::once(&plane_wave_source.waveform))
+			.map(|waveform| waveform.get_max_frequency(SOURCE_SPECTRUM_THRESHOLD))
+			.fold(0.0, f32::max);
+		warn_if_under_resolved(
+			&medium,
+			max_frequency,
+			MAX_TIME_STEP,
+			electromagnetic_field_updater::SpatialStencil::SecondOrder,
+		);
+
+		let absorbing_layer_width = 12;
+		let boundary_conditions =
+			[electromagnetic_field_updater::BoundaryCondition::Absorbing { width: absorbing_layer_width }; 3];
+		let mut field_updater =
+			electromagnetic_field_updater::ElectromagneticFieldUpdater::new(display, &medium, boundary_conditions);
+		field_updater.set_plane_wave_source(display, Some(plane_wave_source));
+
+		let subgrid_params = create_test_dipole_subgrid_params(field_size);
+		field_updater.set_second_order_box(Some((subgrid_params.region_min, subgrid_params.region_max)));
+		let mut subgrid = Subgrid::new(
+			display,
+			subgrid_params,
+			&medium,
+			boundary_conditions,
+			&subgrid_params.make_fine_medium(&medium),
+		);
+		let fine_feed = subgrid_params.get_fine_cell(get_test_dipole_feed(field_size));
+		subgrid.set_fine_lumped_elements(display, lumped_elements);
+		subgrid.set_fine_thin_wires(
+			display,
+			&create_test_dipole_wires(fine_feed, subgrid_params.refinement),
+		);
 
 		let field_border_visualizer = FieldBorderVisualizer::new(display);
 
+		let dft_field_monitors = create_test_dft_field_monitors_params(&electromagnetic_field.electric_field.get_size())
+			.iter()
+			.map(|params| DftFieldMonitor::new(display, params))
+			.collect();
+
+		let field_energy_tracker = FieldEnergyTracker::new(display, electromagnetic_field.electric_field.get_size());
+		let divergence_tracker = DivergenceTracker::new(display, &charge_density, absorbing_layer_width);
+		let field_probe = FieldProbe::new(display, [field_size[0] / 2, field_size[1] * 3 / 4, field_size[2] / 2]);
+
+		let lumped_elements_recorder =
+			LumpedElementsRecorder::new(display, subgrid.get_fine_updater().get_lumped_elements().len());
+
+		let (huygens_surface_min, huygens_surface_max) = get_test_dipole_huygens_surface_box(field_size);
+		let huygens_surface = HuygensSurface::new(
+			display,
+			huygens_surface_min,
+			huygens_surface_max,
+			&[1.0 / 12.0],
+			medium.get_grid_spacing(),
+		);
+		let radiation_pattern_visualizer = RadiationPatternVisualizer::new(display);
+
+		let time_series_visualizer = TimeSeriesVisualizer::new(display);
+
+		let charged_particles = ChargedParticles::new(display, field_size, &particles);
+		let particles_visualizer = ParticlesVisualizer::new(display);
+
+		// Cross-section through center of scatterer, illuminated by plane wave with electric field along Z.
+		let cross_section_size = [field_size[0], field_size[1], 1];
+		let cross_section_field = ElectromagneticField {
+			electric_field: VectorField::new(display, cross_section_size),
+			magnetic_field: VectorField::new(display, cross_section_size),
+		};
+		let mut cross_section_updater = electromagnetic_field_updater::ElectromagneticFieldUpdater::new(
+			display,
+			&create_test_scatterer_medium(cross_section_size),
+			[
+				boundary_conditions[0],
+				boundary_conditions[1],
+				electromagnetic_field_updater::BoundaryCondition::Periodic,
+			],
+		);
+		cross_section_updater.set_simulation_mode(electromagnetic_field_updater::SimulationMode::TwoDimensional {
+			polarization: electromagnetic_field_updater::TwoDimensionalPolarization::TransverseMagnetic,
+		});
+		// Total-field region should cover whole field along reduced axis.
+		let cross_section_source = create_test_plane_wave_source(field_size);
+		cross_section_updater.set_plane_wave_source(
+			display,
+			Some(PlaneWaveSource {
+				box_min: [cross_section_source.box_min[0], cross_section_source.box_min[1], 0],
+				box_max: [cross_section_source.box_max[0], cross_section_source.box_max[1], 1],
+				polarization: Vec3f::unit_z(),
+				..cross_section_source
+			}),
+		);
+		let field_heatmap_visualizer = FieldHeatmapVisualizer::new(display);
+
 		Self {
 			electromagnetic_field,
 			vector_field_visualizer,
 			field_updater,
+			subgrid,
+			show_fine_field: false,
 			field_border_visualizer,
+			dft_field_monitors,
+			dft_view: None,
+			field_energy_tracker,
+			divergence_tracker,
+			show_divergence: false,
+			field_probe,
+			lumped_elements_recorder,
+			huygens_surface,
+			radiation_pattern_visualizer,
+			time_series_visualizer,
+			charged_particles,
+			particles_visualizer,
+			simulation_time: 0.0,
+			cross_section_field,
+			cross_section_updater,
+			cross_section_time: 0.0,
+			show_cross_section: false,
+			field_heatmap_visualizer,
 		}
 	}
 
@@ -35,7 +211,327 @@ impl FieldsSimulator
 		let time_scaled = time_delta_s;
 		for _i in 0 .. 4
 		{
-			self.field_updater.update(&mut self.electromagnetic_field, time_scaled);
+			self.charged_particles
+				.update(&self.electromagnetic_field, time_scaled);
+			self.subgrid.update_with_current_density(
+				&mut self.electromagnetic_field,
+				&self.field_updater,
+				Some(self.charged_particles.get_current_density()),
+				self.simulation_time,
+				time_scaled,
+			);
+			self.simulation_time += time_scaled;
+			self.divergence_tracker.accumulate_current_density(
+				self.charged_particles.get_current_density(),
+				self.field_updater.get_medium_buffers(),
+				time_scaled,
+			);
+
+			for monitor in &mut self.dft_field_monitors
+			{
+				monitor.accumulate(&self.electromagnetic_field, self.simulation_time, time_scaled);
+			}
+			self.huygens_surface
+				.accumulate(&self.electromagnetic_field, self.simulation_time, time_scaled);
+
+			self.field_energy_tracker.record(
+				&self.electromagnetic_field,
+				self.field_updater.get_medium_buffers(),
+				self.simulation_time,
+			);
+			self.divergence_tracker.record(
+				&self.electromagnetic_field,
+				self.field_updater.get_medium_buffers(),
+				self.simulation_time,
+			);
+			self.field_probe.record(&self.electromagnetic_field, self.simulation_time);
+			self.lumped_elements_recorder.record(
+				self.subgrid.get_fine_updater(),
+				self.simulation_time - 0.5 * time_scaled,
+			);
+		}
+		self.field_energy_tracker.flush();
+		self.divergence_tracker.flush();
+		self.field_probe.flush();
+		self.lumped_elements_recorder.flush();
+
+		if self.show_cross_section
+		{
+			for _i in 0 .. 4
+			{
+				self.cross_section_updater.update(
+					&mut self.cross_section_field,
+					self.cross_section_time,
+					time_scaled,
+				);
+				self.cross_section_time += time_scaled;
+			}
+		}
+
+		if let Some(dft_view) = &mut self.dft_view
+		{
+			let monitor = &self.dft_field_monitors[dft_view.monitor_index];
+			// Animate phase in order to show oscillation of steady-state field.
+			let phase = self.simulation_time * monitor.get_frequencies()[dft_view.frequency_index] * 2.0 *
+				std::f32::consts::PI;
+			let field = &mut dft_view.electromagnetic_field;
+			monitor.extract_field(
+				dft_view.frequency_index,
+				phase,
+				dft_view.scale,
+				&mut field.electric_field,
+				&mut field.magnetic_field,
+			);
+		}
+	}
+
+	pub fn process_key_press(&mut self, display: &glium::Display, key: VirtualKeyCode)
+	{
+		match key
+		{
+			// Toggle between live fields and DFT monitors view.
+			VirtualKeyCode::M =>
+			{
+				self.dft_view = if self.dft_view.is_none() && !self.dft_field_monitors.is_empty()
+				{
+					Some(self.create_dft_view(display, 0, 0))
+				}
+				else
+				{
+					None
+				};
+			},
+			// Switch monitor.
+			VirtualKeyCode::Tab =>
+			{
+				if let Some(dft_view) = &self.dft_view
+				{
+					let monitor_index = (dft_view.monitor_index + 1) % self.dft_field_monitors.len();
+					self.dft_view = Some(self.create_dft_view(display, monitor_index, 0));
+				}
+			},
+			// Switch frequency.
+			VirtualKeyCode::PageUp | VirtualKeyCode::PageDown =>
+			{
+				if let Some(dft_view) = &self.dft_view
+				{
+					let num_frequencies = self.dft_field_monitors[dft_view.monitor_index].get_frequencies().len();
+					let frequency_index = if key == VirtualKeyCode::PageUp
+					{
+						(dft_view.frequency_index + 1) % num_frequencies
+					}
+					else
+					{
+						(dft_view.frequency_index + num_frequencies - 1) % num_frequencies
+					};
+					self.dft_view = Some(self.create_dft_view(display, dft_view.monitor_index, frequency_index));
+				}
+			},
+			// Export all monitors.
+			VirtualKeyCode::F5 =>
+			{
+				for (index, monitor) in self.dft_field_monitors.iter().enumerate()
+				{
+					let file_name = format!("dft_monitor_{}.csv", index);
+					match monitor.export(
+						std::path::Path::new(&file_name),
+						self.field_updater.get_medium_buffers().get_grid_spacing(),
+					)
+					{
+						Ok(()) => println!("Exported DFT monitor {} into \"{}\"", index, file_name),
+						Err(e) => println!("Failed to export DFT monitor {}: {}", index, e),
+					}
+				}
+			},
+			// Export energy time series.
+			VirtualKeyCode::F6 =>
+			{
+				let file_name = "field_energy.csv";
+				match self.field_energy_tracker.export(std::path::Path::new(file_name))
+				{
+					Ok(()) => println!("Exported field energy into \"{}\"", file_name),
+					Err(e) => println!("Failed to export field energy: {}", e),
+				}
+			},
+			// Toggle divergence view.
+			VirtualKeyCode::V =>
+			{
+				self.show_divergence = !self.show_divergence;
+			},
+			// Toggle order of spatial stencil. Subgrid is coupled with second-order stencil, which is kept in its region.
+			VirtualKeyCode::O =>
+			{
+				let spatial_stencil = match self.field_updater.get_spatial_stencil()
+				{
+					electromagnetic_field_updater::SpatialStencil::SecondOrder =>
+					{
+						electromagnetic_field_updater::SpatialStencil::FourthOrder
+					},
+					electromagnetic_field_updater::SpatialStencil::FourthOrder =>
+					{
+						electromagnetic_field_updater::SpatialStencil::SecondOrder
+					},
+				};
+				self.field_updater.set_spatial_stencil(spatial_stencil);
+				println!("Spatial stencil order: {}", spatial_stencil.get_order());
+			},
+			// Toggle view of subgrid field.
+			VirtualKeyCode::G =>
+			{
+				self.show_fine_field = !self.show_fine_field;
+			},
+			// Toggle view of two-dimensional cross-section simulation.
+			VirtualKeyCode::H =>
+			{
+				self.show_cross_section = !self.show_cross_section;
+			},
+			// Export divergence time series.
+			VirtualKeyCode::F8 =>
+			{
+				let file_name = "field_divergence.csv";
+				match self.divergence_tracker.export(std::path::Path::new(file_name))
+				{
+					Ok(()) => println!("Exported field divergence into \"{}\"", file_name),
+					Err(e) => println!("Failed to export field divergence: {}", e),
+				}
+			},
+			// Export voltages and currents of lumped elements.
+			VirtualKeyCode::F9 =>
+			{
+				let file_name = "lumped_elements.csv";
+				match self.lumped_elements_recorder.export(std::path::Path::new(file_name))
+				{
+					Ok(()) => println!("Exported lumped elements into \"{}\"", file_name),
+					Err(e) => println!("Failed to export lumped elements: {}", e),
+				}
+			},
+			// Calculate, show and export radiation pattern of dipole.
+			VirtualKeyCode::F10 =>
+			{
+				self.export_radiation_pattern(display);
+			},
+			// Export probe signal and print resonant modes of it.
+			VirtualKeyCode::F7 =>
+			{
+				let file_name = "field_probe.csv";
+				match self.field_probe.export(std::path::Path::new(file_name))
+				{
+					Ok(()) => println!("Exported field probe into \"{}\"", file_name),
+					Err(e) => println!("Failed to export field probe: {}", e),
+				}
+				self.print_probe_resonant_modes();
+			},
+			_ =>
+			{},
+		}
+	}
+
+	fn export_radiation_pattern(&mut self, display: &glium::Display)
+	{
+		let frequency_index = 0;
+		let pattern = self
+			.huygens_surface
+			.compute_radiation_pattern(frequency_index, 37, 72);
+
+		// Power, accepted by antenna, is power given by feed source. Source uses passive sign convention.
+		let input_power = if self.subgrid.get_fine_updater().get_lumped_elements().is_empty()
+		{
+			None
+		}
+		else
+		{
+			let (voltage, current) = self
+				.lumped_elements_recorder
+				.calculate_spectrum(0, pattern.frequency);
+			Some(-0.5 * (voltage * current.conj()).re)
+		};
+
+		let max_directivity = pattern.get_max_directivity();
+		println!(
+			"Radiation pattern at frequency {}: radiated power {}, max directivity {:.3} dBi",
+			pattern.frequency,
+			pattern.radiated_power,
+			10.0 * max_directivity.log10()
+		);
+		if let Some(input_power) = input_power
+		{
+			println!(
+				"Input power {}, max gain {:.3} dBi",
+				input_power,
+				10.0 * (max_directivity * pattern.radiated_power / input_power).log10()
+			);
+		}
+
+		for (file_name, result) in [
+			(
+				"radiation_pattern.csv",
+				pattern.export(std::path::Path::new("radiation_pattern.csv"), input_power),
+			),
+			(
+				"radiation_pattern_cuts.csv",
+				pattern.export_cuts(std::path::Path::new("radiation_pattern_cuts.csv"), input_power),
+			),
+		]
+		{
+			match result
+			{
+				Ok(()) => println!("Exported radiation pattern into \"{}\"", file_name),
+				Err(e) => println!("Failed to export radiation pattern: {}", e),
+			}
+		}
+
+		self.radiation_pattern_visualizer.set_pattern(display, &pattern);
+	}
+
+	fn print_probe_resonant_modes(&self)
+	{
+		let samples = self.field_probe.get_samples();
+		let times = samples.iter().map(|s| s.time).collect::<Vec<_>>();
+		// Use strongest electric field component as signal.
+		let component = (0 .. 3)
+			.max_by(|&a, &b| {
+				let energy = |c: usize| samples.iter().map(|s| s.electric[c] * s.electric[c]).sum::<f32>();
+				energy(a).partial_cmp(&energy(b)).unwrap_or(std::cmp::Ordering::Equal)
+			})
+			.unwrap_or(0);
+		let values = samples.iter().map(|s| s.electric[component]).collect::<Vec<_>>();
+
+		// Simulation time step depends on frame rate, so resample signal.
+		let (time_step, signal) = resample_uniformly(&times, &values);
+
+		let params = HarmonicInversionParams {
+			frequency_min: 0.01,
+			frequency_max: 0.25,
+			max_modes: 16,
+			min_relative_amplitude: 0.01,
+		};
+		let position = self.field_probe.get_position();
+		println!("Resonant modes at probe {:?}:", position);
+		for mode in extract_resonant_modes(&signal, time_step, &params)
+		{
+			println!(
+				"frequency {}, decay rate {}, Q {}, amplitude {}",
+				mode.frequency, mode.decay_rate, mode.quality_factor, mode.amplitude
+			);
+		}
+	}
+
+	fn create_dft_view(&self, display: &glium::Display, monitor_index: usize, frequency_index: usize) -> DftView
+	{
+		let monitor = &self.dft_field_monitors[monitor_index];
+		let samples = monitor.read_samples();
+		let max_magnitude = monitor.get_max_electric_field_magnitude(&samples, frequency_index);
+
+		let region_size = monitor.get_region_size();
+		DftView {
+			monitor_index,
+			frequency_index,
+			// Normalize field in order to make maximum arrow length equal to one.
+			scale: 1.0 / max_magnitude.max(1.0e-20),
+			electromagnetic_field: ElectromagneticField {
+				electric_field: VectorField::new(display, region_size),
+				magnetic_field: VectorField::new(display, region_size),
+			},
 		}
 	}
 
@@ -44,115 +540,404 @@ impl FieldsSimulator
 		surface.clear_color(0.0, 0.0, 0.0, 0.0);
 		surface.clear_depth(1.0);
 
-		self.vector_field_visualizer.visualize(
-			surface,
-			&self.electromagnetic_field.electric_field,
-			view_matrix,
-			ELECTRIC_FIELD_BASE_COLOR,
-		);
-		self.vector_field_visualizer.visualize(
-			surface,
-			&self.electromagnetic_field.magnetic_field,
-			view_matrix,
-			MAGNETIC_FIELD_BASE_COLOR,
-		);
+		let medium_buffers = self.field_updater.get_medium_buffers();
+		let (field, region_offset) = if let Some(dft_view) = &self.dft_view
+		{
+			(
+				&dft_view.electromagnetic_field,
+				self.dft_field_monitors[dft_view.monitor_index].get_region_offset(),
+			)
+		}
+		else
+		{
+			(&self.electromagnetic_field, [0, 0, 0])
+		};
+
+		if self.show_divergence
+		{
+			// Gauss law violation is shown along X axis, magnetic divergence - along Y axis.
+			self.vector_field_visualizer.visualize(
+				surface,
+				self.divergence_tracker.get_divergence_field(),
+				[0, 0, 0],
+				medium_buffers,
+				view_matrix,
+				DIVERGENCE_BASE_COLOR,
+			);
+		}
+		else if self.show_fine_field && self.dft_view.is_none()
+		{
+			// Fine grid positions start from zero, so it's shifted to its region.
+			let params = self.subgrid.get_params();
+			let fine_view_matrix =
+				view_matrix * Mat4f::from_translation(params.get_fine_origin(medium_buffers.get_grid_spacing()));
+			let fine_field = self.subgrid.get_fine_field();
+			let fine_medium_buffers = self.subgrid.get_fine_updater().get_medium_buffers();
+			for (vector_field, base_color) in [
+				(&fine_field.electric_field, ELECTRIC_FIELD_BASE_COLOR),
+				(&fine_field.magnetic_field, MAGNETIC_FIELD_BASE_COLOR),
+			]
+			{
+				self.vector_field_visualizer.visualize(
+					surface,
+					vector_field,
+					[0, 0, 0],
+					fine_medium_buffers,
+					&fine_view_matrix,
+					base_color,
+				);
+			}
+		}
+		else
+		{
+			self.vector_field_visualizer.visualize(
+				surface,
+				&field.electric_field,
+				region_offset,
+				medium_buffers,
+				view_matrix,
+				ELECTRIC_FIELD_BASE_COLOR,
+			);
+			self.vector_field_visualizer.visualize(
+				surface,
+				&field.magnetic_field,
+				region_offset,
+				medium_buffers,
+				view_matrix,
+				MAGNETIC_FIELD_BASE_COLOR,
+			);
+		}
+
+		self.particles_visualizer
+			.visualize(surface, &self.charged_particles, medium_buffers, view_matrix);
+
+		let extent = medium_buffers.get_grid_spacing().get_extent();
+		self.field_border_visualizer.visualize(surface, view_matrix, extent);
 
-		self.field_border_visualizer.visualize(
+		// Place radiation pattern next to domain box.
+		let pattern_radius = extent.x * 0.5;
+		self.radiation_pattern_visualizer.visualize(
 			surface,
 			view_matrix,
-			&self.electromagnetic_field.electric_field.get_size(),
+			Vec3f::new(extent.x + pattern_radius * 1.5, extent.y * 0.5, extent.z * 0.5),
+			pattern_radius,
 		);
+
+		self.draw_energy_plot(surface);
+		self.draw_lumped_elements_plot(surface);
+		if self.show_divergence
+		{
+			self.draw_divergence_plot(surface);
+		}
+		if self.show_cross_section
+		{
+			// Electric field along Z, height of rectangle keeps aspect ratio of square window.
+			let medium_buffers = self.cross_section_updater.get_medium_buffers();
+			let plane_axes = get_plane_axes(medium_buffers.get_size());
+			let extent = medium_buffers.get_grid_spacing().get_extent();
+			let height = 1.2;
+			let width = height * extent[plane_axes[0]] / extent[plane_axes[1]];
+			self.field_heatmap_visualizer.visualize(
+				surface,
+				&self.cross_section_field.electric_field,
+				medium_buffers,
+				2,
+				1.0,
+				[0.95 - width, 0.95 - height, width, height],
+			);
+		}
+	}
+
+	// Draw magnitudes of voltage and current of first lumped element (usually feed source).
+	fn draw_lumped_elements_plot<S: glium::Surface>(&self, surface: &mut S)
+	{
+		if self.subgrid.get_fine_updater().get_lumped_elements().is_empty()
+		{
+			return;
+		}
+
+		let rect = [0.35, -0.95, 0.6, 0.3];
+		for (values, color) in [
+			(self.lumped_elements_recorder.get_voltages(0), [0.8, 0.2, 0.2]),
+			(self.lumped_elements_recorder.get_currents(0), [0.2, 0.8, 0.2]),
+		]
+		{
+			let magnitudes = values.iter().map(|v| v.abs()).collect::<Vec<_>>();
+			let scale = 1.0 / magnitudes.iter().cloned().fold(1.0e-20, f32::max);
+			self.time_series_visualizer.visualize(surface, &magnitudes, scale, rect, color);
+		}
+	}
+
+	fn draw_divergence_plot<S: glium::Surface>(&self, surface: &mut S)
+	{
+		let samples = self.divergence_tracker.get_samples();
+
+		let max_gauss_law_violation = samples.iter().map(|s| s.max_gauss_law_violation).collect::<Vec<_>>();
+		let max_magnetic_divergence = samples.iter().map(|s| s.max_magnetic_divergence).collect::<Vec<_>>();
+
+		let rect = [-0.95, -0.6, 0.6, 0.3];
+		for (values, color) in [
+			(&max_gauss_law_violation, [0.8, 0.2, 0.8]),
+			(&max_magnetic_divergence, [0.2, 0.8, 0.8]),
+		]
+		{
+			let scale = 1.0 / values.iter().cloned().fold(1.0e-20, f32::max);
+			self.time_series_visualizer.visualize(surface, values, scale, rect, color);
+		}
+	}
+
+	fn draw_energy_plot<S: glium::Surface>(&self, surface: &mut S)
+	{
+		let samples = self.field_energy_tracker.get_samples();
+
+		let total_energy = samples.iter().map(|s| s.get_total_energy()).collect::<Vec<_>>();
+		let electric_energy = samples.iter().map(|s| s.electric_energy).collect::<Vec<_>>();
+		let magnetic_energy = samples.iter().map(|s| s.magnetic_energy).collect::<Vec<_>>();
+		let max_electric_field = samples.iter().map(|s| s.max_electric_field).collect::<Vec<_>>();
+
+		// Use same scale for all energy plots in order to make them comparable.
+		let energy_scale = 1.0 / total_energy.iter().cloned().fold(1.0e-20, f32::max);
+		let field_scale = 1.0 / max_electric_field.iter().cloned().fold(1.0e-20, f32::max);
+
+		let rect = [-0.95, -0.95, 0.6, 0.3];
+		for (values, scale, color) in [
+			(&total_energy, energy_scale, [0.8, 0.8, 0.8]),
+			(&electric_energy, energy_scale, [0.8, 0.2, 0.2]),
+			(&magnetic_energy, energy_scale, [0.2, 0.2, 0.8]),
+			(&max_electric_field, field_scale, [0.8, 0.8, 0.2]),
+		]
+		{
+			self.time_series_visualizer.visualize(surface, values, scale, rect, color);
+		}
 	}
 }
 
 const ELECTRIC_FIELD_BASE_COLOR: [f32; 3] = [0.5, 0.1, 0.1];
 const MAGNETIC_FIELD_BASE_COLOR: [f32; 3] = [0.1, 0.1, 0.5];
+const DIVERGENCE_BASE_COLOR: [f32; 3] = [0.5, 0.1, 0.5];
 
-fn create_test_wave_field(display: &glium::Display) -> ElectromagneticField
+fn create_test_dft_field_monitors_params(field_size: &[u32; 3]) -> Vec<DftFieldMonitorParams>
 {
-	let size = [72, 192 as u32, 72];
-
-	let len = (size[0] * size[1] * size[2]) as usize;
-	let mut electric_data = vec![[0.0; 4]; len];
-	let mut magnetic_data = vec![[0.0; 4]; len];
+	let frequencies = vec![1.0 / 16.0, 1.0 / 12.0, 1.0 / 8.0];
+	vec![
+		// Plane along wave propagation direction.
+		DftFieldMonitorParams {
+			region_offset: [0, 0, field_size[2] / 2],
+			region_size: [field_size[0], field_size[1], 1],
+			frequencies: frequencies.clone(),
+		},
+		// Plane across wave propagation direction.
+		DftFieldMonitorParams {
+			region_offset: [0, field_size[1] * 3 / 4, 0],
+			region_size: [field_size[0], 1, field_size[2]],
+			frequencies,
+		},
+	]
+}
 
-	let center = Vec3f::new(size[0] as f32 * 0.5, size[1] as f32 * 0.25, size[2] as f32 * 0.5);
-	let frequency_mul_2pi = (2.0 * std::f32::consts::PI) / 12.0;
+// Beam of electrons, crossing domain along X axis behind scatterer.
+fn create_test_particle_beam(field_size: [u32; 3]) -> Vec<ChargedParticle>
+{
+	let beam_size = 8;
+	let spacing = 0.5;
+	let start = Vec3f::new(16.0, field_size[1] as f32 * 0.75, field_size[2] as f32 * 0.5) -
+		Vec3f::new(1.0, 1.0, 1.0) * (spacing * beam_size as f32 * 0.5);
 
-	for z in 0 .. size[2]
+	let mut particles = Vec::with_capacity(beam_size * beam_size * beam_size);
+	for z in 0 .. beam_size
 	{
-		let dz = z as f32 - center.z;
-		for y in 0 .. size[1]
+		for y in 0 .. beam_size
 		{
-			let dy = y as f32 - center.y;
-			for x in 0 .. size[0]
+			for x in 0 .. beam_size
 			{
-				let dx = x as f32 - center.x;
-				let vec = Vec3f::new(dx * 0.7, dy, dz * 0.7);
-				let vec_square_len = vec.magnitude2();
-				let scale = 8.0 * ((-1.0 / 64.0) * vec_square_len).exp();
-
-				let e = (y as f32) * frequency_mul_2pi;
-
-				let electric_vector = Vec3f::new(scale * e.sin(), 0.0, 0.0);
-				let magnetic_vector = Vec3f::unit_y().cross(electric_vector);
-
-				let address = (x + y * size[0] + z * (size[0] * size[1])) as usize;
-				electric_data[address] = electric_vector.extend(0.0).into();
-				magnetic_data[address] = magnetic_vector.extend(0.0).into();
+				particles.push(ChargedParticle {
+					position: start + Vec3f::new(x as f32, y as f32, z as f32) * spacing,
+					velocity: Vec3f::new(0.4, 0.0, 0.0),
+					charge: -0.01,
+					mass: 0.01,
+				});
 			}
 		}
 	}
+	particles
+}
 
-	ElectromagneticField {
-		electric_field: VectorField::new_with_data(display, size, &electric_data),
-		magnetic_field: VectorField::new_with_data(display, size, &magnetic_data),
-	}
+fn create_test_scatterer_medium(field_size: [u32; 3]) -> Medium
+{
+	let mut medium = Medium::new(field_size);
+	// Gold core, covered with dispersive dielectric shell.
+	// Cell size is chosen in order to make source wavelength (12 cells) in visible range.
+	let cell_size_m = 40.0e-9;
+	let shell_material_id = medium.add_material(Material {
+		permittivity: 2.0,
+		dispersion_poles: vec![DispersionPole::Lorentz {
+			permittivity_delta: 1.0,
+			resonance_frequency: 1.5,
+			damping: 0.05,
+		}],
+		..Material::VACUUM
+	});
+	let core_material_id = medium.add_material(get_library_material("gold").unwrap().to_material(cell_size_m));
+	let center = Vec3f::new(field_size[0] as f32, field_size[1] as f32, field_size[2] as f32) * 0.5;
+	medium.fill_sphere(center, 10.0, shell_material_id);
+	medium.fill_sphere(center, 6.0, core_material_id);
+	medium
 }
 
-fn create_test_field(display: &glium::Display) -> ElectromagneticField
+fn create_test_plane_wave_source(field_size: [u32; 3]) -> PlaneWaveSource
 {
-	let size = [48 as u32, 32, 24];
-	ElectromagneticField {
-		electric_field: create_test_static_charge_field(display, size),
-		magnetic_field: VectorField::new(display, size),
+	let border = [16, 24, 16];
+	PlaneWaveSource {
+		box_min: border,
+		box_max: [
+			field_size[0] - border[0],
+			field_size[1] - border[1],
+			field_size[2] - border[2],
+		],
+		direction: Vec3f::unit_y(),
+		polarization: Vec3f::unit_x(),
+		amplitude: 1.5,
+		waveform: Waveform::modulated_gaussian_pulse(1.0 / 12.0, 6.0),
 	}
 }
 
-fn create_test_static_charge_field(display: &glium::Display, size: [u32; 3]) -> VectorField
+// Position of dipole feed in cells of coarse grid.
+fn get_test_dipole_feed(field_size: [u32; 3]) -> [u32; 3]
 {
-	let center = Vec3f::new(size[0] as f32, size[1] as f32, size[2] as f32) * 0.5;
+	[field_size[0] / 3, field_size[1] / 4, field_size[2] / 2]
+}
 
-	let inv_scale = 128.0 / center.magnitude2();
+// Subgrid around dipole and its feed elements, it's inside of Huygens surface.
+fn create_test_dipole_subgrid_params(field_size: [u32; 3]) -> SubgridParams
+{
+	let feed = get_test_dipole_feed(field_size);
+	SubgridParams {
+		region_min: [feed[0] - 4, feed[1] - 4, feed[2] - 14],
+		region_max: [feed[0] + 5, feed[1] + 5, feed[2] + 15],
+		refinement: 2,
+	}
+}
 
-	let mut data = vec![[0.0; 4]; (size[0] * size[1] * size[2]) as usize];
+// Position of feed is given in cells of grid, where elements are placed.
+fn create_test_lumped_elements(feed: [u32; 3]) -> Vec<LumpedElement>
+{
+	// Dipole (see create_test_dipole_wires), fed by voltage source with 50 Ohm internal resistance and loaded by
+	// capacitor and inductor.
+	// Resistor and current source are placed nearby.
+	let offset = |dx: i32, dz: i32| [(feed[0] as i32 + dx) as u32, feed[1], (feed[2] as i32 + dz) as u32];
+	let resistance = 50.0 / (VACUUM_IMPEDANCE as f32);
+	let waveform = Waveform::modulated_gaussian_pulse(1.0 / 12.0, 6.0);
+	vec![
+		LumpedElement {
+			position: feed,
+			axis: 2,
+			kind: LumpedElementKind::VoltageSource {
+				voltage: 1.0,
+				waveform: waveform.clone(),
+				resistance,
+			},
+		},
+		LumpedElement {
+			position: offset(0, 1),
+			axis: 2,
+			kind: LumpedElementKind::Capacitor { capacitance: 2.0 },
+		},
+		LumpedElement {
+			position: offset(0, -1),
+			axis: 2,
+			kind: LumpedElementKind::Inductor { inductance: 4.0 },
+		},
+		LumpedElement {
+			position: offset(4, 0),
+			axis: 2,
+			kind: LumpedElementKind::Resistor { resistance },
+		},
+		LumpedElement {
+			position: offset(-4, 0),
+			axis: 2,
+			kind: LumpedElementKind::CurrentSource {
+				current: 0.1,
+				waveform,
+				resistance: f32::INFINITY,
+			},
+		},
+	]
+}
 
-	// Simulate electric field of point charge.
-	for z in 0 .. size[2]
-	{
-		let dz = z as f32 - center.z;
-		for y in 0 .. size[1]
-		{
-			let dy = y as f32 - center.y;
-			for x in 0 .. size[0]
-			{
-				let dx = x as f32 - center.x;
-				let vec = Vec3f::new(dx, dy, dz);
-				let vec_square_len = vec.magnitude2();
+// Arms of dipole, fed by lumped elements. Position of feed is given in cells of grid, where wires are placed, which
+// are "refinement" times smaller than coarse cells, length and radius of arms are scaled accordingly.
+fn create_test_dipole_wires(feed: [u32; 3], refinement: u32) -> Vec<ThinWire>
+{
+	let arm_length = 10 * refinement;
+	let radius = 0.1 * refinement as f32;
+	vec![
+		ThinWire {
+			points: vec![[feed[0], feed[1], feed[2] + 2], [feed[0], feed[1], feed[2] + 2 + arm_length]],
+			radius,
+		},
+		ThinWire {
+			points: vec![[feed[0], feed[1], feed[2] - 2 - arm_length], [feed[0], feed[1], feed[2] - 2]],
+			radius,
+		},
+	]
+}
 
-				let field_vec = if vec_square_len <= 0.0
-				{
-					Vec3f::zero()
-				}
-				else
-				{
-					vec / (inv_scale * vec_square_len * vec_square_len.sqrt())
-				};
+// Huygens surface around dipole (see create_test_dipole_wires) and its feed elements.
+// It is placed inside total field region of plane wave source, so plane wave gives no contribution to far field.
+fn get_test_dipole_huygens_surface_box(field_size: [u32; 3]) -> ([u32; 3], [u32; 3])
+{
+	let feed = get_test_dipole_feed(field_size);
+	let half_size = [7, 7, 16];
+	(
+		[0, 1, 2].map(|i| feed[i] - half_size[i]),
+		[0, 1, 2].map(|i| feed[i] + half_size[i] + 1),
+	)
+}
 
-				data[(x + y * size[0] + z * (size[0] * size[1])) as usize] = field_vec.extend(0.0).into();
-			}
-		}
+#[allow(dead_code)]
+fn create_test_field(display: &glium::Display) -> ElectromagneticField
+{
+	let size = [48, 32, 24];
+	ElectromagneticField {
+		electric_field: create_test_static_charge_field(display, size),
+		magnetic_field: create_test_coil_field(display, size),
 	}
+}
+
+#[allow(dead_code)]
+fn create_test_coil_field(display: &glium::Display, size: [u32; 3]) -> VectorField
+{
+	// Circular coil in the center of grid.
+	let mut setup = MagnetostaticSetup::new(size);
+	setup.wires.push(create_circular_coil(
+		Vec3f::new(size[0] as f32, size[1] as f32, size[2] as f32) * 0.5,
+		Vec3f::unit_x(),
+		(size[2] / 4) as f32,
+		4.0,
+		64,
+	));
+	VectorField::new_with_data(display, size, &solve_magnetostatic_field(&setup))
+}
 
-	VectorField::new_with_data(display, size, &data)
+#[allow(dead_code)]
+fn create_test_static_charge_field(display: &glium::Display, size: [u32; 3]) -> VectorField
+{
+	// Point charge near conductor plate inside grounded box.
+	let mut setup = ElectrostaticSetup::new(size);
+	setup.add_conductor_box(
+		[size[0] / 8, size[1] / 4, size[2] / 4],
+		[size[0] / 8 + 2, size[1] * 3 / 4, size[2] * 3 / 4],
+		1.0,
+	);
+	// Use particle in order to obtain charge density, which is smooth on grid scale.
+	setup.charge_density = calculate_charge_density(
+		size,
+		&[ChargedParticle {
+			position: Vec3f::new(size[0] as f32, size[1] as f32, size[2] as f32) * 0.5,
+			velocity: Vec3f::zero(),
+			charge: (size[0] * size[1] * size[2]) as f32 / 16.0,
+			mass: 1.0,
+		}],
+	);
+	VectorField::new_with_data(display, size, &solve_electrostatic_field(&setup))
 }
@@ -0,0 +1,113 @@
+use super::{dft_field_monitor::*, grid_spacing::*};
+
+// Rectangle of flux monitor, perpendicular to one of axes. Flux through it is calculated in frequency domain, using
+// DFT of fields (see "DftFieldMonitor") in region of two layers of cells along normal axis, rectangle is face between
+// these layers. Electric and magnetic fields are collocated and central differences skip one cell, so flux, which is
+// consistent with conservation of discrete energy, couples electric field of each layer with magnetic field of other
+// one.
+pub struct FluxPlane
+{
+	region_size: [u32; 3],
+	normal_axis: usize,
+	// Area of each cell face of the rectangle.
+	face_areas: Vec<f32>,
+}
+
+impl FluxPlane
+{
+	// Region should have size 2 along normal axis. Flux is positive for energy flowing towards positive normal axis
+	// direction.
+	pub fn new(region_offset: [u32; 3], region_size: [u32; 3], normal_axis: usize, grid_spacing: &GridSpacing) -> Self
+	{
+		assert!(normal_axis < 3);
+		assert_eq!(region_size[normal_axis], 2, "Flux monitor region should have two layers along normal axis!");
+
+		let mut face_areas = Vec::with_capacity((region_size[0] * region_size[1] * region_size[2] / 2) as usize);
+		for coord in get_layer_coords(region_size, normal_axis)
+		{
+			let coord = [0, 1, 2].map(|i| coord[i] + region_offset[i]);
+			face_areas.push(grid_spacing.get_face_area(coord, normal_axis));
+		}
+
+		Self {
+			region_size,
+			normal_axis,
+			face_areas,
+		}
+	}
+
+	// Calculate flux for each frequency of given samples, which contain all cells of region for each frequency.
+	// Samples may be not only result of reading of DFT field monitor, but also difference of samples of same monitor in
+	// different simulations (in order to get scattered field flux).
+	pub fn calculate_flux_spectrum(&self, samples: &[DftFieldSample], num_frequencies: usize) -> Vec<f32>
+	{
+		assert!(samples.len().is_multiple_of(num_frequencies));
+		let cell_count = samples.len() / num_frequencies;
+		assert_eq!(cell_count, self.face_areas.len() * 2);
+
+		let b = (self.normal_axis + 1) % 3;
+		let c = (self.normal_axis + 2) % 3;
+		let size = self.region_size;
+		let get_index = |coord: [u32; 3]| (coord[0] + coord[1] * size[0] + coord[2] * (size[0] * size[1])) as usize;
+		// Re(E x H*) component along normal axis.
+		let get_flux_density = |e: &DftFieldSample, h: &DftFieldSample| {
+			multiply_conjugate_real(&e.electric[b], &h.magnetic[c]) -
+				multiply_conjugate_real(&e.electric[c], &h.magnetic[b])
+		};
+
+		samples
+			.chunks(cell_count)
+			.map(|frequency_samples| {
+				// Time-averaged Poynting vector component - Re(E x H*) / 2, averaged over two couplings of layers.
+				get_layer_coords(size, self.normal_axis)
+					.zip(self.face_areas.iter())
+					.map(|(coord, area)| {
+						let mut upper_coord = coord;
+						upper_coord[self.normal_axis] = 1;
+						let lower = &frequency_samples[get_index(coord)];
+						let upper = &frequency_samples[get_index(upper_coord)];
+						0.25 * area * (get_flux_density(lower, upper) + get_flux_density(upper, lower))
+					})
+					.sum()
+			})
+			.collect()
+	}
+}
+
+// Coordinates (relative to region) of cells of lower layer of region along normal axis.
+fn get_layer_coords(region_size: [u32; 3], normal_axis: usize) -> impl Iterator<Item = [u32; 3]>
+{
+	let mut layer_size = region_size;
+	layer_size[normal_axis] = 1;
+	(0 .. layer_size[2])
+		.flat_map(move |z| (0 .. layer_size[1]).flat_map(move |y| (0 .. layer_size[0]).map(move |x| [x, y, z])))
+}
+
+pub fn subtract_dft_samples(a: &[DftFieldSample], b: &[DftFieldSample]) -> Vec<DftFieldSample>
+{
+	assert_eq!(a.len(), b.len());
+	a.iter()
+		.zip(b.iter())
+		.map(|(a, b)| DftFieldSample {
+			electric: subtract_complex_vectors(&a.electric, &b.electric),
+			magnetic: subtract_complex_vectors(&a.magnetic, &b.magnetic),
+		})
+		.collect()
+}
+
+fn subtract_complex_vectors(a: &ComplexVector, b: &ComplexVector) -> ComplexVector
+{
+	let mut result = *a;
+	for (r, b) in result.iter_mut().zip(b.iter())
+	{
+		r[0] -= b[0];
+		r[1] -= b[1];
+	}
+	result
+}
+
+// Re(a * conj(b)).
+fn multiply_conjugate_real(a: &[f32; 2], b: &[f32; 2]) -> f32
+{
+	a[0] * b[0] + a[1] * b[1]
+}
@@ -0,0 +1,197 @@
+use super::math_types::*;
+
+// Sizes of cells along each axis (graded mesh). First cell of each axis starts at zero.
+// Derivatives along axis are calculated in cell index space and divided by size of cell, so scheme stays stable and
+// energy-conserving (with energy, weighted by cell volumes), but accuracy degrades if sizes of neighbor cells differ
+// much. Sizes should change smoothly - with ratio of neighbor cells close to one.
+// Uniform grid has cells of size 1, all other lengths (wavelengths, positions, time step) are measured in the same
+// units.
+#[derive(Clone, PartialEq, Debug)]
+pub struct GridSpacing
+{
+	spacings: [Vec<f32>; 3],
+	// Positions of cell boundaries for each axis, number of boundaries is number of cells plus one.
+	boundaries: [Vec<f32>; 3],
+}
+
+impl GridSpacing
+{
+	pub fn uniform(size: [u32; 3]) -> Self
+	{
+		Self::new(size.map(|s| vec![1.0; s as usize]))
+	}
+
+	pub fn new(spacings: [Vec<f32>; 3]) -> Self
+	{
+		for axis_spacings in &spacings
+		{
+			assert!(!axis_spacings.is_empty());
+			assert!(axis_spacings.iter().all(|s| *s > 0.0));
+		}
+
+		let boundaries = [0, 1, 2].map(|i| {
+			let mut result = Vec::with_capacity(spacings[i].len() + 1);
+			let mut position = 0.0;
+			result.push(position);
+			for s in &spacings[i]
+			{
+				position += s;
+				result.push(position);
+			}
+			result
+		});
+
+		Self { spacings, boundaries }
+	}
+
+	pub fn get_size(&self) -> [u32; 3]
+	{
+		[0, 1, 2].map(|i| self.spacings[i].len() as u32)
+	}
+
+	pub fn get_spacing(&self, axis: usize, index: u32) -> f32
+	{
+		self.spacings[axis][index as usize]
+	}
+
+	pub fn get_min_spacing(&self) -> f32
+	{
+		self.spacings.iter().flatten().copied().fold(f32::MAX, f32::min)
+	}
+
+	pub fn get_max_spacing(&self) -> f32
+	{
+		self.spacings.iter().flatten().copied().fold(0.0, f32::max)
+	}
+
+	// Get position of lower boundary of cell with given index. Index may be equal to number of cells.
+	pub fn get_boundary(&self, axis: usize, index: u32) -> f32
+	{
+		self.boundaries[axis][index as usize]
+	}
+
+	pub fn get_center(&self, axis: usize, index: u32) -> f32
+	{
+		self.boundaries[axis][index as usize] + 0.5 * self.spacings[axis][index as usize]
+	}
+
+	pub fn get_cell_center(&self, coord: [u32; 3]) -> Vec3f
+	{
+		Vec3f::new(
+			self.get_center(0, coord[0]),
+			self.get_center(1, coord[1]),
+			self.get_center(2, coord[2]),
+		)
+	}
+
+	// Get area of cell face, perpendicular to given axis.
+	pub fn get_face_area(&self, coord: [u32; 3], normal_axis: usize) -> f32
+	{
+		(0 .. 3)
+			.filter(|i| *i != normal_axis)
+			.map(|i| self.get_spacing(i, coord[i]))
+			.product()
+	}
+
+	// Get size of whole grid.
+	pub fn get_extent(&self) -> Vec3f
+	{
+		Vec3f::new(
+			*self.boundaries[0].last().unwrap(),
+			*self.boundaries[1].last().unwrap(),
+			*self.boundaries[2].last().unwrap(),
+		)
+	}
+
+	// Get (cell center, cell size) for all cells along X axis, then along Y and Z axes.
+	pub fn get_shader_data(&self) -> Vec<[f32; 2]>
+	{
+		let mut result = Vec::with_capacity(self.spacings.iter().map(|s| s.len()).sum());
+		for axis in 0 .. 3
+		{
+			for index in 0 .. self.spacings[axis].len() as u32
+			{
+				result.push([self.get_center(axis, index), self.get_spacing(axis, index)]);
+			}
+		}
+		result
+	}
+}
+
+// Make spacings of axis with given number of cells. Cells inside [fine_min; fine_max) have fine spacing, outside
+// this range each next cell is larger than previous one by given ratio, until max spacing is reached.
+pub fn make_graded_spacings(
+	num_cells: u32,
+	fine_min: u32,
+	fine_max: u32,
+	fine_spacing: f32,
+	grading_ratio: f32,
+	max_spacing: f32,
+) -> Vec<f32>
+{
+	assert!(fine_min < fine_max && fine_max <= num_cells);
+	assert!(grading_ratio >= 1.0);
+	(0 .. num_cells)
+		.map(|i| {
+			let distance = if i < fine_min
+			{
+				fine_min - i
+			}
+			else if i >= fine_max
+			{
+				i + 1 - fine_max
+			}
+			else
+			{
+				0
+			};
+			(fine_spacing * grading_ratio.powi(distance as i32)).min(max_spacing.max(fine_spacing))
+		})
+		.collect()
+}
+
+// Grid spacing data for shaders. Requires "field_size" uniform, which should be declared before.
+pub const SHADER_GRID_SPACING: &str = r#"
+	// (cell center, cell size) for all cells along X axis, then along Y and Z axes.
+	layout(std430) buffer grid_spacing_data
+	{
+		vec2 grid_cells[];
+	};
+
+	uint get_grid_cell_index(int axis, int index)
+	{
+		uint offset = axis == 0 ? 0u : (axis == 1 ? field_size.x : field_size.x + field_size.y);
+		return offset + uint(clamp(index, 0, int(field_size[axis]) - 1));
+	}
+
+	// Cells outside field have size of border cells.
+	float get_cell_spacing(int axis, int index)
+	{
+		return grid_cells[get_grid_cell_index(axis, index)].y;
+	}
+
+	float get_cell_center(int axis, int index)
+	{
+		vec2 cell = grid_cells[get_grid_cell_index(axis, index)];
+		int border_index = clamp(index, 0, int(field_size[axis]) - 1);
+		return cell.x + float(index - border_index) * cell.y;
+	}
+
+	vec3 get_cell_position(ivec3 coord)
+	{
+		return vec3(get_cell_center(0, coord.x), get_cell_center(1, coord.y), get_cell_center(2, coord.z));
+	}
+
+	vec3 get_cell_size(ivec3 coord)
+	{
+		return vec3(get_cell_spacing(0, coord.x), get_cell_spacing(1, coord.y), get_cell_spacing(2, coord.z));
+	}
+
+	// Convert position in grid units (where cell with integer coordinates "c" occupies [c; c + 1)) into position in
+	// length units.
+	vec3 get_grid_position(vec3 grid_position)
+	{
+		ivec3 coord = ivec3(floor(grid_position));
+		return get_cell_position(coord) + (grid_position - vec3(coord) - vec3(0.5, 0.5, 0.5)) * get_cell_size(coord);
+	}
+"#;
@@ -0,0 +1,252 @@
+use super::complex_number::*;
+
+// Extraction of resonant modes (frequencies, decay rates, quality factors) from signals, sampled in time.
+// Signal is approximated as sum of damped exponentials using least squares Prony method.
+
+#[derive(Copy, Clone)]
+pub struct HarmonicInversionParams
+{
+	// Range of frequencies of interest.
+	pub frequency_min: f32,
+	pub frequency_max: f32,
+	// Maximum number of modes inside signal (including modes outside range of interest).
+	pub max_modes: usize,
+	// Modes with amplitude less than this value (relative to maximum amplitude) are considered spurious.
+	pub min_relative_amplitude: f32,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct ResonantMode
+{
+	pub frequency: f32,
+	// Decay rate of amplitude - mode is proportional to "exp(-decay_rate * t)".
+	pub decay_rate: f32,
+	pub quality_factor: f32,
+	pub amplitude: f32,
+	pub phase: f32,
+}
+
+// Extract modes from signal with uniform sampling. Returns modes sorted by frequency.
+pub fn extract_resonant_modes(signal: &[f32], time_step: f32, params: &HarmonicInversionParams) -> Vec<ResonantMode>
+{
+	// Decimate oversampled signal - Prony method is poorly conditioned if sampling frequency is much greater than
+	// frequencies of interest. Use box filter for decimation in order to reduce aliasing.
+	let decimation = ((0.25 / (params.frequency_max * time_step)).floor() as usize).max(1);
+	let decimated_signal = signal
+		.chunks_exact(decimation)
+		.take(MAX_SIGNAL_LENGTH)
+		.map(|chunk| chunk.iter().map(|v| *v as f64).sum::<f64>() / (decimation as f64))
+		.collect::<Vec<_>>();
+	let original_time_step = time_step as f64;
+	let time_step = original_time_step * (decimation as f64);
+
+	let order = 2 * params.max_modes;
+	let signal_length = decimated_signal.len();
+	if order == 0 || signal_length < 3 * order
+	{
+		return Vec::new();
+	}
+
+	// Build linear prediction equations "y[n] = sum(b[k] * y[n - k - 1])".
+	let prediction_matrix = (order .. signal_length)
+		.map(|n| {
+			(0 .. order)
+				.map(|k| Complex::from_real(decimated_signal[n - k - 1]))
+				.collect::<Vec<_>>()
+		})
+		.collect::<Vec<_>>();
+	let prediction_rhs = (order .. signal_length)
+		.map(|n| Complex::from_real(decimated_signal[n]))
+		.collect::<Vec<_>>();
+
+	let prediction_coefficients = match solve_least_squares(&prediction_matrix, &prediction_rhs, 1.0e-12)
+	{
+		Some(c) => c,
+		None => return Vec::new(),
+	};
+
+	// Roots of characteristic polynomial "z^p - b[0] * z^(p-1) - ... - b[p-1]" are signal poles.
+	let polynomial = std::iter::once(Complex::ONE)
+		.chain(prediction_coefficients.iter().map(|b| -*b))
+		.collect::<Vec<_>>();
+	// Ignore strongly growing poles, which are possible because of noise - they make amplitude fitting unstable.
+	let mut poles = find_polynomial_roots(&polynomial);
+	poles.retain(|z| z.abs() <= 1.0 + 1.0e-3);
+
+	// Find complex amplitudes by fitting of signal with found poles.
+	let vandermonde_matrix = (0 .. signal_length)
+		.map(|n| poles.iter().map(|z| power(*z, n)).collect::<Vec<_>>())
+		.collect::<Vec<_>>();
+	let signal_complex = decimated_signal
+		.iter()
+		.map(|v| Complex::from_real(*v))
+		.collect::<Vec<_>>();
+	let amplitudes = match solve_least_squares(&vandermonde_matrix, &signal_complex, 1.0e-12)
+	{
+		Some(a) => a,
+		None => return Vec::new(),
+	};
+
+	let mut modes = Vec::new();
+	for (pole, amplitude) in poles.iter().zip(amplitudes.iter())
+	{
+		// Real signal has poles in conjugate pairs, take only one pole of each pair.
+		if pole.im <= 0.0
+		{
+			continue;
+		}
+
+		let s = pole.ln().scale(1.0 / time_step);
+		let angular_frequency = s.im;
+		let decay_rate = -s.re;
+		let frequency = (angular_frequency / (2.0 * std::f64::consts::PI)) as f32;
+		if frequency < params.frequency_min || frequency > params.frequency_max
+		{
+			continue;
+		}
+
+		// Each decimated sample is average of mode over its chunk, so amplitude is divided by gain of box filter.
+		let filter_gain = (0 .. decimation)
+			.map(|j| {
+				let t = original_time_step * (j as f64);
+				Complex::from_polar((s.re * t).exp(), s.im * t)
+			})
+			.fold(Complex::ZERO, |sum, v| sum + v)
+			.scale(1.0 / (decimation as f64));
+		let amplitude = *amplitude / filter_gain;
+
+		modes.push(ResonantMode {
+			frequency,
+			decay_rate: decay_rate as f32,
+			quality_factor: if decay_rate > 0.0
+			{
+				(0.5 * angular_frequency / decay_rate) as f32
+			}
+			else
+			{
+				f32::INFINITY
+			},
+			// Sum of conjugate pair gives doubled amplitude.
+			amplitude: (2.0 * amplitude.abs()) as f32,
+			phase: amplitude.arg() as f32,
+		});
+	}
+
+	let max_amplitude = modes.iter().map(|m| m.amplitude).fold(0.0, f32::max);
+	modes.retain(|m| m.amplitude >= max_amplitude * params.min_relative_amplitude);
+	modes.sort_by(|a, b| a.frequency.partial_cmp(&b.frequency).unwrap_or(std::cmp::Ordering::Equal));
+	modes
+}
+
+// Convert signal with non-uniform sampling into signal with uniform sampling using linear interpolation.
+// Returns time step and resampled values.
+pub fn resample_uniformly(times: &[f32], values: &[f32]) -> (f32, Vec<f32>)
+{
+	assert_eq!(times.len(), values.len());
+	if times.len() < 2
+	{
+		return (1.0, values.to_vec());
+	}
+
+	let start_time = times[0];
+	let end_time = times[times.len() - 1];
+	let time_step = (end_time - start_time) / ((times.len() - 1) as f32);
+
+	let mut result = Vec::with_capacity(times.len());
+	let mut segment = 0;
+	for i in 0 .. times.len()
+	{
+		let t = start_time + (i as f32) * time_step;
+		while segment + 2 < times.len() && times[segment + 1] < t
+		{
+			segment += 1;
+		}
+		let t0 = times[segment];
+		let t1 = times[segment + 1];
+		let k = if t1 > t0 { ((t - t0) / (t1 - t0)).clamp(0.0, 1.0) } else { 0.0 };
+		result.push(values[segment] * (1.0 - k) + values[segment + 1] * k);
+	}
+
+	(time_step, result)
+}
+
+const MAX_SIGNAL_LENGTH: usize = 4096;
+
+fn power(z: Complex, n: usize) -> Complex
+{
+	let mut result = Complex::ONE;
+	let mut base = z;
+	let mut n = n;
+	while n > 0
+	{
+		if (n & 1) != 0
+		{
+			result *= base;
+		}
+		base *= base;
+		n >>= 1;
+	}
+	result
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn two_damped_sinusoids()
+	{
+		// (frequency, quality factor, amplitude, phase) of each mode.
+		let expected_modes = [(0.05, 40.0, 1.0, 0.3), (0.08, 120.0, 0.5, -1.2)];
+		let time_step = 0.5;
+		let signal = (0 .. 2000)
+			.map(|n| {
+				let t = n as f32 * time_step;
+				expected_modes
+					.iter()
+					.map(|(frequency, quality_factor, amplitude, phase)| {
+						let angular_frequency = 2.0 * std::f32::consts::PI * frequency;
+						let decay_rate = 0.5 * angular_frequency / quality_factor;
+						amplitude * (-decay_rate * t).exp() * (angular_frequency * t + phase).cos()
+					})
+					.sum::<f32>()
+			})
+			.collect::<Vec<_>>();
+
+		let modes = extract_resonant_modes(
+			&signal,
+			time_step,
+			&HarmonicInversionParams {
+				frequency_min: 0.02,
+				frequency_max: 0.1,
+				max_modes: 4,
+				min_relative_amplitude: 0.01,
+			},
+		);
+		assert_eq!(modes.len(), expected_modes.len(), "{:?}", modes);
+		for (mode, (frequency, quality_factor, amplitude, phase)) in modes.iter().zip(expected_modes.iter())
+		{
+			assert!((mode.frequency - frequency).abs() < 1.0e-4 * frequency, "{:?}", mode);
+			assert!((mode.quality_factor - quality_factor).abs() < 1.0e-2 * quality_factor, "{:?}", mode);
+			assert!((mode.amplitude - amplitude).abs() < 1.0e-2 * amplitude, "{:?}", mode);
+			assert!((mode.phase - phase).abs() < 1.0e-2, "{:?}", mode);
+		}
+	}
+
+	#[test]
+	fn resampling()
+	{
+		// Linear function is reproduced exactly by linear interpolation.
+		let times = [0.0, 0.3, 1.1, 1.5, 2.6, 3.0];
+		let values = times.iter().map(|t| 2.0 * t - 1.0).collect::<Vec<_>>();
+		let (time_step, resampled) = resample_uniformly(&times, &values);
+		assert!((time_step - 0.6).abs() < 1.0e-6);
+		assert_eq!(resampled.len(), times.len());
+		for (i, value) in resampled.iter().enumerate()
+		{
+			let expected = 2.0 * (i as f32 * time_step) - 1.0;
+			assert!((value - expected).abs() < 1.0e-5, "{} {}", value, expected);
+		}
+	}
+}
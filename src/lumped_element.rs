@@ -0,0 +1,245 @@
+use super::{complex_number::*, electromagnetic_field_updater::*, waveform::*};
+use std::io::Write;
+
+// Lumped circuit element, occupying one cell and oriented along one of axes.
+// Element voltage is electric field component along axis (multiplied by cell length along axis), element current is
+// current density component along axis (multiplied by cell face area). Passive sign convention is used - current flows
+// along axis, from terminal with higher potential, so power "voltage * current" is absorbed by element.
+// Resistance is measured in vacuum impedance units.
+#[derive(Clone)]
+pub struct LumpedElement
+{
+	pub position: [u32; 3],
+	pub axis: usize,
+	pub kind: LumpedElementKind,
+}
+
+#[derive(Clone)]
+pub enum LumpedElementKind
+{
+	Resistor
+	{
+		resistance: f32
+	},
+	Capacitor
+	{
+		capacitance: f32
+	},
+	Inductor
+	{
+		inductance: f32
+	},
+	// Ideal voltage source with series internal resistance. Voltage is open circuit voltage.
+	VoltageSource
+	{
+		voltage: f32,
+		waveform: Waveform,
+		resistance: f32,
+	},
+	// Ideal current source with parallel internal resistance, which may be infinite.
+	// Current is short circuit current, flowing against axis inside source.
+	CurrentSource
+	{
+		current: f32,
+		waveform: Waveform,
+		resistance: f32,
+	},
+}
+
+impl LumpedElement
+{
+	// Each element is represented as parallel connection of conductance, capacitance, inductance and current source.
+	// Get (conductance, capacitance, inverse inductance) of this circuit.
+	pub fn get_parallel_circuit_params(&self) -> [f32; 3]
+	{
+		match &self.kind
+		{
+			LumpedElementKind::Resistor { resistance } => [1.0 / resistance, 0.0, 0.0],
+			LumpedElementKind::Capacitor { capacitance } => [0.0, *capacitance, 0.0],
+			LumpedElementKind::Inductor { inductance } => [0.0, 0.0, 1.0 / inductance],
+			LumpedElementKind::VoltageSource { resistance, .. } |
+			LumpedElementKind::CurrentSource { resistance, .. } => [1.0 / resistance, 0.0, 0.0],
+		}
+	}
+
+	// Get current of equivalent current source at given time.
+	pub fn get_source_current(&self, time: f32) -> f32
+	{
+		match &self.kind
+		{
+			LumpedElementKind::Resistor { .. } |
+			LumpedElementKind::Capacitor { .. } |
+			LumpedElementKind::Inductor { .. } => 0.0,
+			// Norton equivalent of voltage source.
+			LumpedElementKind::VoltageSource {
+				voltage,
+				waveform,
+				resistance,
+			} => voltage * waveform.get_value(time) / resistance,
+			LumpedElementKind::CurrentSource { current, waveform, .. } => current * waveform.get_value(time),
+		}
+	}
+
+	// Get waveform of source element.
+	pub fn get_waveform(&self) -> Option<&Waveform>
+	{
+		match &self.kind
+		{
+			LumpedElementKind::VoltageSource { waveform, .. } | LumpedElementKind::CurrentSource { waveform, .. } =>
+			{
+				Some(waveform)
+			},
+			_ => None,
+		}
+	}
+}
+
+// Recorder of voltage and current time series of all lumped elements of field updater.
+pub struct LumpedElementsRecorder
+{
+	num_elements: usize,
+	// Values, which are not yet read back from GPU - (inductor current density, voltage, current, unused) for each
+	// element.
+	pending_samples_buffer: glium::buffer::Buffer<[[f32; 4]]>,
+	pending_samples_times: Vec<f32>,
+	times: Vec<f32>,
+	// Pairs of voltage and current for each element and each sample.
+	values: Vec<[f32; 2]>,
+}
+
+impl LumpedElementsRecorder
+{
+	pub fn new(display: &glium::Display, num_elements: usize) -> Self
+	{
+		Self {
+			num_elements,
+			pending_samples_buffer: glium::buffer::Buffer::empty_array(
+				display,
+				glium::buffer::BufferType::ShaderStorageBuffer,
+				MAX_PENDING_SAMPLES * num_elements.max(1),
+				glium::buffer::BufferMode::Default,
+			)
+			.unwrap(),
+			pending_samples_times: Vec::with_capacity(MAX_PENDING_SAMPLES),
+			times: Vec::new(),
+			values: Vec::new(),
+		}
+	}
+
+	// Record values of last update step. They are given at half step - this time should be passed.
+	// Result is available after flush.
+	pub fn record(&mut self, field_updater: &ElectromagneticFieldUpdater, time: f32)
+	{
+		assert_eq!(field_updater.get_lumped_elements().len(), self.num_elements);
+		if self.num_elements == 0
+		{
+			return;
+		}
+
+		if self.pending_samples_times.len() == MAX_PENDING_SAMPLES
+		{
+			self.flush();
+		}
+
+		let offset = self.pending_samples_times.len() * self.num_elements;
+		field_updater
+			.get_lumped_element_states_buffer()
+			.copy_to(
+				self.pending_samples_buffer
+					.slice(offset .. offset + self.num_elements)
+					.unwrap(),
+			)
+			.unwrap();
+
+		self.pending_samples_times.push(time);
+	}
+
+	// Read pending results from GPU.
+	pub fn flush(&mut self)
+	{
+		if self.pending_samples_times.is_empty()
+		{
+			return;
+		}
+
+		let data = self
+			.pending_samples_buffer
+			.slice(0 .. self.pending_samples_times.len() * self.num_elements)
+			.unwrap()
+			.read()
+			.unwrap();
+
+		self.times.extend_from_slice(&self.pending_samples_times);
+		self.values.extend(data.iter().map(|v| [v[1], v[2]]));
+		self.pending_samples_times.clear();
+	}
+
+	pub fn get_voltages(&self, element_index: usize) -> Vec<f32>
+	{
+		self.values
+			.iter()
+			.skip(element_index)
+			.step_by(self.num_elements)
+			.map(|v| v[0])
+			.collect()
+	}
+
+	pub fn get_currents(&self, element_index: usize) -> Vec<f32>
+	{
+		self.values
+			.iter()
+			.skip(element_index)
+			.step_by(self.num_elements)
+			.map(|v| v[1])
+			.collect()
+	}
+
+	// Calculate DFT "sum(x(t) * exp(-i * w * t) * dt)" of (voltage, current) of given element.
+	// Time step may vary, it is calculated as difference of times of consecutive samples.
+	pub fn calculate_spectrum(&self, element_index: usize, frequency: f32) -> (Complex, Complex)
+	{
+		let angular_frequency = 2.0 * std::f64::consts::PI * (frequency as f64);
+		let mut voltage = Complex::ZERO;
+		let mut current = Complex::ZERO;
+		for (i, time) in self.times.iter().enumerate()
+		{
+			let time_step = if i > 0
+			{
+				time - self.times[i - 1]
+			}
+			else
+			{
+				self.times.get(1).unwrap_or(time) - time
+			};
+			let values = &self.values[i * self.num_elements + element_index];
+			let kernel = Complex::from_polar(time_step as f64, -angular_frequency * (*time as f64));
+			voltage += kernel.scale(values[0] as f64);
+			current += kernel.scale(values[1] as f64);
+		}
+		(voltage, current)
+	}
+
+	// Export recorded time series into CSV file.
+	pub fn export(&self, file_path: &std::path::Path) -> std::io::Result<()>
+	{
+		let mut file = std::io::BufWriter::new(std::fs::File::create(file_path)?);
+		write!(file, "time")?;
+		for i in 0 .. self.num_elements
+		{
+			write!(file, ",voltage_{},current_{}", i, i)?;
+		}
+		writeln!(file)?;
+		for (time, values) in self.times.iter().zip(self.values.chunks(self.num_elements.max(1)))
+		{
+			write!(file, "{}", time)?;
+			for v in values
+			{
+				write!(file, ",{},{}", v[0], v[1])?;
+			}
+			writeln!(file)?;
+		}
+		file.flush()
+	}
+}
+
+const MAX_PENDING_SAMPLES: usize = 64;
@@ -0,0 +1,164 @@
+use super::{math_types::*, poisson_solver::*, scalar_grid::*};
+
+// Description of magnetostatic problem. Medium is assumed to be vacuum.
+pub struct MagnetostaticSetup
+{
+	// Components of steady current density in cell centers.
+	pub current_density: [ScalarGrid; 3],
+	pub wires: Vec<Wire>,
+}
+
+// Thin wire with steady current, flowing along polyline from first point to last point.
+// Coordinates are measured in cells, cell with integer coordinates i has center at i + 0.5 (like particle positions).
+pub struct Wire
+{
+	pub points: Vec<Vec3f>,
+	pub current: f32,
+	// Field inside this distance from wire axis is linearly decreasing in order to avoid singularity.
+	pub radius: f32,
+}
+
+impl MagnetostaticSetup
+{
+	// Create setup without currents.
+	pub fn new(size: [u32; 3]) -> Self
+	{
+		Self {
+			current_density: [ScalarGrid::new(size), ScalarGrid::new(size), ScalarGrid::new(size)],
+			wires: Vec::new(),
+		}
+	}
+}
+
+// Create closed polyline, approximating circular coil with given center and normal.
+pub fn create_circular_coil(center: Vec3f, normal: Vec3f, radius: f32, current: f32, num_segments: u32) -> Wire
+{
+	let normal = normal.normalize();
+	let helper = if normal.x.abs() < 0.9
+	{
+		Vec3f::unit_x()
+	}
+	else
+	{
+		Vec3f::unit_y()
+	};
+	let tangent0 = normal.cross(helper).normalize();
+	let tangent1 = normal.cross(tangent0);
+
+	let points = (0 ..= num_segments)
+		.map(|i| {
+			let angle = (i % num_segments) as f32 * (std::f32::consts::TAU / (num_segments as f32));
+			center + (tangent0 * angle.cos() + tangent1 * angle.sin()) * radius
+		})
+		.collect();
+
+	Wire {
+		points,
+		current,
+		radius: 0.5,
+	}
+}
+
+// Calculate magnetic field of steady currents (as data for vector field).
+// Field of current density is calculated as central-difference curl of vector potential, so its central-difference
+// divergence is exactly zero. Vector potential outside grid is zero. Current density should be smooth on grid scale.
+// Field of wires is calculated via Biot-Savart law, so it doesn't depend on grid borders.
+pub fn solve_magnetostatic_field(setup: &MagnetostaticSetup) -> Vec<[f32; 4]>
+{
+	let size = setup.current_density[0].get_size();
+	let mut result = vec![[0.0; 4]; (size[0] * size[1] * size[2]) as usize];
+
+	if setup
+		.current_density
+		.iter()
+		.any(|j| j.get_values().iter().any(|v| *v != 0.0))
+	{
+		let no_fixed_values = vec![None; result.len()];
+		let vector_potential = setup
+			.current_density
+			.iter()
+			.map(|j| solve_central_difference_poisson(j, &no_fixed_values))
+			.collect::<Vec<_>>();
+		let [a_x, a_y, a_z] = [&vector_potential[0], &vector_potential[1], &vector_potential[2]];
+
+		for z in 0 .. size[2] as i32
+		{
+			for y in 0 .. size[1] as i32
+			{
+				for x in 0 .. size[0] as i32
+				{
+					result[a_x.get_address(x as u32, y as u32, z as u32)] = [
+						0.5 * (a_z.get(x, y + 1, z) - a_z.get(x, y - 1, z) - a_y.get(x, y, z + 1) +
+							a_y.get(x, y, z - 1)),
+						0.5 * (a_x.get(x, y, z + 1) - a_x.get(x, y, z - 1) - a_z.get(x + 1, y, z) +
+							a_z.get(x - 1, y, z)),
+						0.5 * (a_y.get(x + 1, y, z) - a_y.get(x - 1, y, z) - a_x.get(x, y + 1, z) +
+							a_x.get(x, y - 1, z)),
+						0.0,
+					];
+				}
+			}
+		}
+	}
+
+	for wire in &setup.wires
+	{
+		for segment in wire.points.windows(2)
+		{
+			for z in 0 .. size[2]
+			{
+				for y in 0 .. size[1]
+				{
+					for x in 0 .. size[0]
+					{
+						let position = Vec3f::new(x as f32 + 0.5, y as f32 + 0.5, z as f32 + 0.5);
+						let h = calculate_segment_field(segment[0], segment[1], wire.current, wire.radius, position);
+						let dst = &mut result[(x + y * size[0] + z * (size[0] * size[1])) as usize];
+						dst[0] += h.x;
+						dst[1] += h.y;
+						dst[2] += h.z;
+					}
+				}
+			}
+		}
+	}
+
+	result
+}
+
+// Biot-Savart law for straight segment with current.
+fn calculate_segment_field(start: Vec3f, end: Vec3f, current: f32, radius: f32, position: Vec3f) -> Vec3f
+{
+	let a = start - position;
+	let b = end - position;
+	let a_length = a.magnitude();
+	let b_length = b.magnitude();
+	let denominator = a_length * b_length * (a_length * b_length + a.dot(b));
+	if denominator <= 0.0
+	{
+		// Point lies on segment line.
+		return Vec3f::zero();
+	}
+
+	let cross = a.cross(b);
+	let h = cross * (current / (4.0 * std::f32::consts::PI) * (a_length + b_length) / denominator);
+
+	// Field of wire with finite radius grows linearly inside wire.
+	let segment_length_square = (end - start).magnitude2();
+	let distance_square = if segment_length_square > 0.0
+	{
+		cross.magnitude2() / segment_length_square
+	}
+	else
+	{
+		0.0
+	};
+	if distance_square < radius * radius
+	{
+		h * (distance_square / (radius * radius))
+	}
+	else
+	{
+		h
+	}
+}
@@ -1,19 +1,89 @@
+mod analytic_validation;
+mod body_of_revolution;
 mod camera_controller;
+mod charged_particles;
+mod complex_number;
+mod cpu_electromagnetic_field_updater;
+mod cpu_subgrid;
+mod cross_sections;
+mod dft_field_monitor;
+mod divergence_tracker;
+mod electrostatic_solver;
 mod electromagnetic_field;
 mod electromagnetic_field_updater;
 mod field_border_visualizer;
+mod field_energy_tracker;
+mod field_heatmap_visualizer;
+mod field_probe;
 mod fields_simulator;
+mod flux_monitor;
+mod grid_spacing;
+mod harmonic_inversion;
 mod keyboard_state;
-#[allow(dead_code)]
+mod lumped_element;
+mod magnetostatic_solver;
+mod material_library;
+#[allow(dead_code, unused_imports)]
 mod math_types;
+mod medium;
+mod near_to_far_field;
+mod network_parameters;
+mod numerical_dispersion;
 mod ogl_common;
+mod particles_visualizer;
+mod plane_wave_source;
+mod poisson_solver;
+mod radiation_pattern_visualizer;
+mod resonance_analysis;
+mod scalar_grid;
+mod subgrid;
+mod thin_wire;
+mod time_series_visualizer;
+mod transmission_reflection_spectra;
 mod vector_field;
 mod vector_field_visualizer;
+mod waveform;
 
 use glium::{glutin, Surface};
 
-fn main()
+fn main() -> std::process::ExitCode
 {
+	let args = std::env::args().collect::<Vec<_>>();
+	// Optional last argument "--fourth-order" selects fourth-order spatial stencil for simulations of test devices.
+	let (args, spatial_stencil) = match args.split_last()
+	{
+		Some((last, rest)) if last == "--fourth-order" =>
+		{
+			(rest, electromagnetic_field_updater::SpatialStencil::FourthOrder)
+		},
+		_ => (&args[..], electromagnetic_field_updater::SpatialStencil::SecondOrder),
+	};
+	if args.len() == 3
+	{
+		let file_path = std::path::Path::new(&args[2]);
+		// These modes don't simulate test devices with selected stencil, so stencil argument would be silently ignored.
+		if spatial_stencil == electromagnetic_field_updater::SpatialStencil::FourthOrder &&
+			matches!(args[1].as_str(), "--dispersion" | "--validate" | "--fit-material" | "--bor-resonances")
+		{
+			println!("\"--fourth-order\" can't be used with \"{}\"", args[1]);
+			return std::process::ExitCode::FAILURE;
+		}
+		match args[1].as_str()
+		{
+			"--spectra" => return compute_spectra(file_path, spatial_stencil),
+			"--resonances" => return compute_resonances(file_path, spatial_stencil),
+			"--harmonics" => return compute_harmonics(file_path, spatial_stencil),
+			"--s-parameters" => return compute_s_parameters(file_path, spatial_stencil),
+			"--cross-sections" => return compute_cross_sections(file_path, spatial_stencil),
+			"--dispersion" => return compute_dispersion(file_path),
+			"--validate" => return validate(file_path),
+			"--fit-material" => return fit_material(file_path),
+			"--bor-resonances" => return compute_body_of_revolution_resonances(file_path),
+			_ =>
+			{},
+		}
+	}
+
 	let wb = glutin::window::WindowBuilder::new()
 		.with_inner_size(glutin::dpi::PhysicalSize {
 			width: 640,
@@ -52,16 +122,20 @@ fn main()
 					return;
 				},
 				glutin::event::WindowEvent::KeyboardInput {
-					input: glutin::event::KeyboardInput {
-						state, virtual_keycode, ..
-					},
+					input:
+						glutin::event::KeyboardInput {
+							state,
+							virtual_keycode: Some(code),
+							..
+						},
 					..
 				} =>
 				{
-					if let Some(code) = virtual_keycode
+					if state == glutin::event::ElementState::Pressed && !keyboard_state.is_pressed(&code)
 					{
-						keyboard_state.process_event(state, code);
+						fields_simulator.process_key_press(&display, code);
 					}
+					keyboard_state.process_event(state, code);
 				},
 				_ =>
 				{},
@@ -69,7 +143,7 @@ fn main()
 			glutin::event::Event::MainEventsCleared =>
 			{
 				let cur_time = std::time::Instant::now();
-				let time_delta_s = (cur_time - prev_time).as_secs_f32().max(0.00001).min(0.1);
+				let time_delta_s = (cur_time - prev_time).as_secs_f32().clamp(0.00001, 0.1);
 				prev_time = cur_time;
 
 				camera_controller.update(time_delta_s, &keyboard_state);
@@ -92,3 +166,426 @@ fn main()
 		*control_flow = glutin::event_loop::ControlFlow::Poll;
 	});
 }
+
+// Create invisible window only in order to obtain OpenGL context for offline computations.
+fn create_hidden_display() -> (glium::Display, glutin::event_loop::EventLoop<()>)
+{
+	let wb = glutin::window::WindowBuilder::new().with_visible(false);
+	let cb = glutin::ContextBuilder::new()
+		.with_gl_profile(glutin::GlProfile::Core)
+		.with_gl(glutin::GlRequest::Specific(glutin::Api::OpenGl, (4, 3)));
+	let event_loop = glutin::event_loop::EventLoop::new();
+	let display = glium::Display::new(wb, cb, &event_loop).unwrap();
+	(display, event_loop)
+}
+
+// Run simulations for test device and write its transmission and reflection spectra.
+fn compute_spectra(
+	file_path: &std::path::Path,
+	spatial_stencil: electromagnetic_field_updater::SpatialStencil,
+) -> std::process::ExitCode
+{
+	let (display, _event_loop) = create_hidden_display();
+
+	let setup = transmission_reflection_spectra::create_test_bragg_mirror_setup(spatial_stencil);
+	numerical_dispersion::warn_if_under_resolved(
+		&setup.device_medium,
+		get_max_frequency(&setup.frequencies),
+		setup.time_step,
+		setup.spatial_stencil,
+	);
+	let spectra = transmission_reflection_spectra::compute_transmission_reflection_spectra(&display, &setup);
+	match transmission_reflection_spectra::write_spectra_table(&spectra, file_path)
+	{
+		Ok(()) =>
+		{
+			println!("Spectra written into \"{}\"", file_path.display());
+			std::process::ExitCode::SUCCESS
+		},
+		Err(e) =>
+		{
+			println!("Failed to write spectra: {}", e);
+			std::process::ExitCode::FAILURE
+		},
+	}
+}
+
+// Run simulation of test nonlinear device and write spectrum of transmitted wave.
+fn compute_harmonics(
+	file_path: &std::path::Path,
+	spatial_stencil: electromagnetic_field_updater::SpatialStencil,
+) -> std::process::ExitCode
+{
+	let (display, _event_loop) = create_hidden_display();
+
+	let setup = transmission_reflection_spectra::create_test_harmonic_generation_setup(spatial_stencil);
+	numerical_dispersion::warn_if_under_resolved(
+		&setup.device_medium,
+		get_max_frequency(&setup.frequencies),
+		setup.time_step,
+		setup.spatial_stencil,
+	);
+	let spectra = transmission_reflection_spectra::compute_transmitted_power_spectrum(&display, &setup);
+
+	// Peak of transmitted power near each harmonic of pulse frequency (relative to maximum incident power).
+	let fundamental_frequency = setup.pulse.frequency;
+	let mut passed = true;
+	for harmonic in [2, 3]
+	{
+		let harmonic_frequency = fundamental_frequency * (harmonic as f32);
+		let peak = spectra
+			.frequencies
+			.iter()
+			.zip(spectra.transmission.iter())
+			.filter(|(f, _)| (*f - harmonic_frequency).abs() <= 0.25 * fundamental_frequency)
+			.map(|(_, t)| *t)
+			.fold(0.0, f32::max);
+		println!(
+			"Harmonic {}: peak transmitted power {:.3e} ({})",
+			harmonic,
+			peak,
+			if peak > MIN_HARMONIC_POWER { "passed" } else { "failed" }
+		);
+		passed &= peak > MIN_HARMONIC_POWER;
+	}
+
+	match transmission_reflection_spectra::write_spectra_table(&spectra, file_path)
+	{
+		Ok(()) => println!("Spectrum written into \"{}\"", file_path.display()),
+		Err(e) =>
+		{
+			println!("Failed to write spectrum: {}", e);
+			passed = false;
+		},
+	}
+	get_exit_code(passed)
+}
+
+// Minimum power of generated harmonics (relative to maximum incident power), which is distinguishable from numerical
+// noise.
+const MIN_HARMONIC_POWER: f32 = 1.0e-6;
+
+// Run simulation of test resonator and write its resonant modes.
+fn compute_resonances(
+	file_path: &std::path::Path,
+	spatial_stencil: electromagnetic_field_updater::SpatialStencil,
+) -> std::process::ExitCode
+{
+	let (display, _event_loop) = create_hidden_display();
+
+	let setup = resonance_analysis::create_test_dielectric_resonator_setup(spatial_stencil);
+	numerical_dispersion::warn_if_under_resolved(
+		&setup.medium,
+		setup.harmonic_inversion_params.frequency_max,
+		setup.time_step,
+		setup.spatial_stencil,
+	);
+	let modes = resonance_analysis::compute_resonant_modes(&display, &setup);
+	for mode in &modes
+	{
+		println!(
+			"Mode: frequency {}, decay rate {}, Q {}",
+			mode.frequency, mode.decay_rate, mode.quality_factor
+		);
+	}
+	match resonance_analysis::write_resonant_modes_table(&modes, file_path)
+	{
+		Ok(()) =>
+		{
+			println!("Resonant modes written into \"{}\"", file_path.display());
+			std::process::ExitCode::SUCCESS
+		},
+		Err(e) =>
+		{
+			println!("Failed to write resonant modes: {}", e);
+			std::process::ExitCode::FAILURE
+		},
+	}
+}
+
+// Run body-of-revolution simulations of test axisymmetric resonator for several azimuthal mode numbers and write its
+// resonant modes.
+fn compute_body_of_revolution_resonances(file_path: &std::path::Path) -> std::process::ExitCode
+{
+	let mut modes = Vec::new();
+	for azimuthal_mode_number in 0 .. 3
+	{
+		let setup = body_of_revolution::create_test_dielectric_cylinder_setup(azimuthal_mode_number);
+		for mode in body_of_revolution::compute_body_of_revolution_resonances(&setup).modes
+		{
+			println!(
+				"Mode: azimuthal mode number {}, frequency {}, decay rate {}, Q {}",
+				azimuthal_mode_number, mode.frequency, mode.decay_rate, mode.quality_factor
+			);
+			modes.push((azimuthal_mode_number, mode));
+		}
+	}
+	match body_of_revolution::write_body_of_revolution_modes_table(&modes, file_path)
+	{
+		Ok(()) =>
+		{
+			println!("Resonant modes written into \"{}\"", file_path.display());
+			std::process::ExitCode::SUCCESS
+		},
+		Err(e) =>
+		{
+			println!("Failed to write resonant modes: {}", e);
+			std::process::ExitCode::FAILURE
+		},
+	}
+}
+
+// Run simulations of test microstrip device, write its S-parameters and print input impedance of ports.
+fn compute_s_parameters(
+	file_path: &std::path::Path,
+	spatial_stencil: electromagnetic_field_updater::SpatialStencil,
+) -> std::process::ExitCode
+{
+	let (display, _event_loop) = create_hidden_display();
+
+	let (setup, cell_size_m) = network_parameters::create_test_microstrip_setup(spatial_stencil);
+	numerical_dispersion::warn_if_under_resolved(
+		&setup.medium,
+		get_max_frequency(&setup.frequencies),
+		setup.time_step,
+		setup.spatial_stencil,
+	);
+	let parameters = network_parameters::compute_network_parameters(&display, &setup);
+
+	println!("frequency_hz,port,impedance_re_ohm,impedance_im_ohm,reflection_magnitude");
+	let frequency_scale = material_library::SPEED_OF_LIGHT / cell_size_m;
+	for (frequency_index, frequency) in parameters.frequencies.iter().enumerate()
+	{
+		for port_index in 0 .. parameters.get_num_ports()
+		{
+			let impedance = parameters.input_impedances[frequency_index][port_index]
+				.scale(material_library::VACUUM_IMPEDANCE);
+			println!(
+				"{},{},{},{},{}",
+				*frequency as f64 * frequency_scale,
+				port_index,
+				impedance.re,
+				impedance.im,
+				parameters.s_parameters[frequency_index][port_index][port_index].abs()
+			);
+		}
+	}
+
+	match network_parameters::write_touchstone_file(&parameters, cell_size_m, file_path)
+	{
+		Ok(()) =>
+		{
+			println!("S-parameters written into \"{}\"", file_path.display());
+			std::process::ExitCode::SUCCESS
+		},
+		Err(e) =>
+		{
+			println!("Failed to write S-parameters: {}", e);
+			std::process::ExitCode::FAILURE
+		},
+	}
+}
+
+// Run simulations of test sphere, write its cross sections along with Mie solution and print deviation from it.
+fn compute_cross_sections(
+	file_path: &std::path::Path,
+	spatial_stencil: electromagnetic_field_updater::SpatialStencil,
+) -> std::process::ExitCode
+{
+	let (display, _event_loop) = create_hidden_display();
+
+	let (setup, sphere) = cross_sections::create_test_mie_sphere_setup(spatial_stencil);
+	numerical_dispersion::warn_if_under_resolved(
+		&setup.medium,
+		get_max_frequency(&setup.frequencies),
+		setup.time_step,
+		setup.spatial_stencil,
+	);
+	let cross_sections = cross_sections::compute_cross_sections(&display, &setup);
+	let reference = sphere.calculate_cross_sections(&setup.frequencies);
+
+	// Deviation relative to maximum of reference values, in order to avoid division by near-zero values.
+	let mut passed = true;
+	for (name, values, reference_values) in [
+		("scattering", &cross_sections.scattering, &reference.scattering),
+		("absorption", &cross_sections.absorption, &reference.absorption),
+		("extinction", &cross_sections.extinction, &reference.extinction),
+	]
+	{
+		let max_reference_value = reference_values.iter().copied().fold(0.0, f64::max);
+		let max_deviation = values
+			.iter()
+			.zip(reference_values.iter())
+			.map(|(v, r)| (v - r).abs())
+			.fold(0.0, f64::max);
+		let relative_deviation = max_deviation / max_reference_value.max(1.0e-30);
+		println!(
+			"{} cross section: max relative deviation from Mie solution {:.4} ({})",
+			name,
+			relative_deviation,
+			if relative_deviation <= MIE_TOLERANCE { "passed" } else { "failed" }
+		);
+		passed &= relative_deviation <= MIE_TOLERANCE;
+	}
+
+	match cross_sections::write_cross_sections_table(&cross_sections, Some(&reference), file_path)
+	{
+		Ok(()) => println!("Cross sections written into \"{}\"", file_path.display()),
+		Err(e) =>
+		{
+			println!("Failed to write cross sections: {}", e);
+			passed = false;
+		},
+	}
+	get_exit_code(passed)
+}
+
+// Maximum allowed relative deviation of test sphere cross sections from Mie solution (staircase approximation of
+// sphere and numerical dispersion are main sources of error).
+const MIE_TOLERANCE: f64 = 0.1;
+
+// Compute analytic and measured numerical dispersion for each spatial stencil and write it, print maximum phase
+// velocity errors.
+fn compute_dispersion(file_path: &std::path::Path) -> std::process::ExitCode
+{
+	let time_step = 0.5;
+	let cells_per_wavelength = [4.0, 6.0, 8.0, 12.0, 16.0, 24.0, 32.0];
+	let mut samples = Vec::new();
+	for spatial_stencil in [
+		electromagnetic_field_updater::SpatialStencil::SecondOrder,
+		electromagnetic_field_updater::SpatialStencil::FourthOrder,
+	]
+	{
+		let stencil_samples =
+			numerical_dispersion::compute_dispersion_table(time_step, &cells_per_wavelength, 19, 7, spatial_stencil);
+		for cells in &cells_per_wavelength
+		{
+			let max_error = stencil_samples
+				.iter()
+				.filter(|s| s.measured_phase_velocity.is_none() && s.cells_per_wavelength == *cells)
+				.map(|s| (s.phase_velocity - 1.0).abs())
+				.fold(0.0, f64::max);
+			println!(
+				"Order {}, {} cells per wavelength: max phase velocity error {:.3}%",
+				spatial_stencil.get_order(),
+				cells,
+				max_error * 100.0
+			);
+		}
+		samples.extend(stencil_samples);
+	}
+	let max_measurement_deviation = samples
+		.iter()
+		.filter_map(|s| s.measured_phase_velocity.map(|v| (v - s.phase_velocity).abs()))
+		.fold(0.0, f64::max);
+	println!(
+		"Max deviation of measured phase velocity from analytic: {:.2e}",
+		max_measurement_deviation
+	);
+	let mut passed = max_measurement_deviation <= DISPERSION_MEASUREMENT_TOLERANCE;
+
+	match numerical_dispersion::write_dispersion_table(&samples, file_path)
+	{
+		Ok(()) => println!("Dispersion table written into \"{}\"", file_path.display()),
+		Err(e) =>
+		{
+			println!("Failed to write dispersion table: {}", e);
+			passed = false;
+		},
+	}
+	get_exit_code(passed)
+}
+
+// Maximum allowed deviation of phase velocity measured in simulation from analytic dispersion relation.
+const DISPERSION_MEASUREMENT_TOLERANCE: f64 = 1.0e-3;
+
+fn get_max_frequency(frequencies: &[f32]) -> f32
+{
+	frequencies.iter().copied().fold(0.0, f32::max)
+}
+
+fn get_exit_code(passed: bool) -> std::process::ExitCode
+{
+	if passed
+	{
+		std::process::ExitCode::SUCCESS
+	}
+	else
+	{
+		std::process::ExitCode::FAILURE
+	}
+}
+
+// Compare results of CPU simulations with analytic results and write report.
+fn validate(file_path: &std::path::Path) -> std::process::ExitCode
+{
+	let results = analytic_validation::run_all_validations();
+	for result in &results
+	{
+		println!(
+			"{}: measured {}, expected {}, relative error {:.5} ({})",
+			result.name,
+			result.measured,
+			result.expected,
+			result.get_relative_error(),
+			if result.is_passed() { "passed" } else { "failed" }
+		);
+	}
+	let mut passed = results.iter().all(|result| result.is_passed());
+
+	match analytic_validation::write_validation_report(&results, file_path)
+	{
+		Ok(()) => println!("Validation report written into \"{}\"", file_path.display()),
+		Err(e) =>
+		{
+			println!("Failed to write validation report: {}", e);
+			passed = false;
+		},
+	}
+	get_exit_code(passed)
+}
+
+// Load refractive index table and print fitted permittivity model.
+fn fit_material(file_path: &std::path::Path) -> std::process::ExitCode
+{
+	let samples = match material_library::load_refractive_index_table(file_path)
+	{
+		Ok(samples) => samples,
+		Err(e) =>
+		{
+			println!("Failed to load refractive index table: {}", e);
+			return std::process::ExitCode::FAILURE;
+		},
+	};
+
+	let num_lorentz_poles = 2;
+	match material_library::fit_dispersion_model(&samples, num_lorentz_poles)
+	{
+		Some(material) =>
+		{
+			println!("Fitted material: {:?}", material);
+			println!("wavelength_um,n,k,fitted_n,fitted_k");
+			for sample in &samples
+			{
+				// Refractive index is principal square root of permittivity.
+				let permittivity = material.get_permittivity(sample.get_angular_frequency());
+				let magnitude = permittivity.abs().sqrt();
+				let angle = 0.5 * permittivity.arg();
+				println!(
+					"{},{},{},{},{}",
+					sample.wavelength * 1.0e6,
+					sample.n,
+					sample.k,
+					magnitude * angle.cos(),
+					magnitude * angle.sin()
+				);
+			}
+			std::process::ExitCode::SUCCESS
+		},
+		None =>
+		{
+			println!("Failed to fit permittivity model");
+			std::process::ExitCode::FAILURE
+		},
+	}
+}
@@ -0,0 +1,563 @@
+use super::{complex_number::*, medium::*};
+use std::io::BufRead;
+
+pub const SPEED_OF_LIGHT: f64 = 299792458.0;
+pub const VACUUM_IMPEDANCE: f64 = 376.730313668;
+
+// Material description in physical (SI) units.
+#[derive(Clone, PartialEq, Debug)]
+pub struct PhysicalMaterial
+{
+	// Relative permittivity at infinite frequency.
+	pub permittivity: f32,
+	pub permeability: f32,
+	// Conductivity in S/m.
+	pub conductivity: f32,
+	// Pole frequencies are in rad/s, relaxation times are in seconds.
+	pub dispersion_poles: Vec<DispersionPole>,
+}
+
+impl PhysicalMaterial
+{
+	pub const VACUUM: PhysicalMaterial = PhysicalMaterial {
+		permittivity: 1.0,
+		permeability: 1.0,
+		conductivity: 0.0,
+		dispersion_poles: Vec::new(),
+	};
+
+	// Convert into simulation units, where cell size and speed of light are equal to one.
+	pub fn to_material(&self, cell_size_m: f64) -> Material
+	{
+		// Physical duration of one simulation time unit.
+		let time_scale = cell_size_m / SPEED_OF_LIGHT;
+		let scale_frequency = |f: f32| ((f as f64) * time_scale) as f32;
+		let scale_time = |t: f32| ((t as f64) / time_scale) as f32;
+
+		Material {
+			permittivity: self.permittivity,
+			permeability: self.permeability,
+			conductivity: ((self.conductivity as f64) * cell_size_m * VACUUM_IMPEDANCE) as f32,
+			dispersion_poles: self
+				.dispersion_poles
+				.iter()
+				.map(|pole| match *pole
+				{
+					DispersionPole::Drude {
+						plasma_frequency,
+						collision_frequency,
+					} => DispersionPole::Drude {
+						plasma_frequency: scale_frequency(plasma_frequency),
+						collision_frequency: scale_frequency(collision_frequency),
+					},
+					DispersionPole::Lorentz {
+						permittivity_delta,
+						resonance_frequency,
+						damping,
+					} => DispersionPole::Lorentz {
+						permittivity_delta,
+						resonance_frequency: scale_frequency(resonance_frequency),
+						damping: scale_frequency(damping),
+					},
+					DispersionPole::Debye {
+						permittivity_delta,
+						relaxation_time,
+					} => DispersionPole::Debye {
+						permittivity_delta,
+						relaxation_time: scale_time(relaxation_time),
+					},
+				})
+				.collect(),
+			..Material::VACUUM
+		}
+	}
+
+	// Calculate complex relative permittivity for given angular frequency (in rad/s).
+	pub fn get_permittivity(&self, angular_frequency: f64) -> Complex
+	{
+		let mut result = Complex::new(
+			self.permittivity as f64,
+			(self.conductivity as f64) * VACUUM_IMPEDANCE * SPEED_OF_LIGHT / angular_frequency,
+		);
+		for pole in &self.dispersion_poles
+		{
+			result += get_pole_susceptibility(pole, angular_frequency);
+		}
+		result
+	}
+}
+
+// Get material from library by name. Returns None for unknown names.
+// Models are approximate and valid only in specified frequency ranges.
+pub fn get_library_material(name: &str) -> Option<PhysicalMaterial>
+{
+	let terahertz = 2.0 * std::f32::consts::PI * 1.0e12;
+	let electron_volt = 1.519267e15;
+	// Lossless Lorentz pole from Sellmeier coefficients (wavelength in micrometers).
+	let sellmeier_pole = |coefficient: f32, wavelength: f32| DispersionPole::Lorentz {
+		permittivity_delta: coefficient,
+		resonance_frequency: (2.0 * std::f64::consts::PI * SPEED_OF_LIGHT / ((wavelength as f64) * 1.0e-6)) as f32,
+		damping: 0.0,
+	};
+
+	match name
+	{
+		// Visible and near infrared, Drude-Lorentz fit by Vial et al.
+		"gold" => Some(PhysicalMaterial {
+			permittivity: 5.9673,
+			dispersion_poles: vec![
+				DispersionPole::Drude {
+					plasma_frequency: 2113.6 * terahertz,
+					collision_frequency: 15.92 * terahertz,
+				},
+				DispersionPole::Lorentz {
+					permittivity_delta: 1.09,
+					resonance_frequency: 650.07 * terahertz,
+					damping: 104.86 * terahertz,
+				},
+			],
+			..PhysicalMaterial::VACUUM
+		}),
+		// Visible and near infrared, Drude model.
+		"silver" => Some(PhysicalMaterial {
+			permittivity: 3.7,
+			dispersion_poles: vec![DispersionPole::Drude {
+				plasma_frequency: 9.1 * electron_volt,
+				collision_frequency: 0.018 * electron_volt,
+			}],
+			..PhysicalMaterial::VACUUM
+		}),
+		// Ultraviolet and visible, Drude model.
+		"aluminium" => Some(PhysicalMaterial {
+			dispersion_poles: vec![DispersionPole::Drude {
+				plasma_frequency: 14.98 * electron_volt,
+				collision_frequency: 0.047 * electron_volt,
+			}],
+			..PhysicalMaterial::VACUUM
+		}),
+		// Below band gap (near and middle infrared), single oscillator model.
+		"silicon" => Some(PhysicalMaterial {
+			dispersion_poles: vec![DispersionPole::Lorentz {
+				permittivity_delta: 10.7,
+				resonance_frequency: 3.6 * electron_volt,
+				damping: 0.0,
+			}],
+			..PhysicalMaterial::VACUUM
+		}),
+		// Fused silica, Sellmeier formula by Malitson.
+		"glass" => Some(PhysicalMaterial {
+			dispersion_poles: vec![
+				sellmeier_pole(0.6961663, 0.0684043),
+				sellmeier_pole(0.4079426, 0.1162414),
+				sellmeier_pole(0.8974794, 9.896161),
+			],
+			..PhysicalMaterial::VACUUM
+		}),
+		// Microwaves, Debye model at room temperature.
+		"water" => Some(PhysicalMaterial {
+			permittivity: 5.2,
+			dispersion_poles: vec![DispersionPole::Debye {
+				permittivity_delta: 73.2,
+				relaxation_time: 8.27e-12,
+			}],
+			..PhysicalMaterial::VACUUM
+		}),
+		// Radio frequencies, where copper is a good conductor.
+		"copper" => Some(PhysicalMaterial {
+			conductivity: 5.8e7,
+			..PhysicalMaterial::VACUUM
+		}),
+		_ => None,
+	}
+}
+
+// Susceptibility of single pole for "exp(-i * w * t)" time dependence.
+pub fn get_pole_susceptibility(pole: &DispersionPole, angular_frequency: f64) -> Complex
+{
+	let w = angular_frequency;
+	match *pole
+	{
+		DispersionPole::Drude {
+			plasma_frequency,
+			collision_frequency,
+		} =>
+		{
+			let wp = plasma_frequency as f64;
+			Complex::from_real(wp * wp) / Complex::new(-w * w, -(collision_frequency as f64) * w)
+		},
+		DispersionPole::Lorentz {
+			permittivity_delta,
+			resonance_frequency,
+			damping,
+		} =>
+		{
+			let w0 = resonance_frequency as f64;
+			Complex::from_real((permittivity_delta as f64) * w0 * w0) /
+				Complex::new(w0 * w0 - w * w, -(damping as f64) * w)
+		},
+		DispersionPole::Debye {
+			permittivity_delta,
+			relaxation_time,
+		} => Complex::from_real(permittivity_delta as f64) / Complex::new(1.0, -w * (relaxation_time as f64)),
+	}
+}
+
+// Measured complex refractive index "n + i * k" for specific wavelength.
+#[derive(Copy, Clone, Debug)]
+pub struct RefractiveIndexSample
+{
+	// Vacuum wavelength in meters.
+	pub wavelength: f64,
+	pub n: f64,
+	pub k: f64,
+}
+
+impl RefractiveIndexSample
+{
+	pub fn get_angular_frequency(&self) -> f64
+	{
+		2.0 * std::f64::consts::PI * SPEED_OF_LIGHT / self.wavelength
+	}
+
+	pub fn get_permittivity(&self) -> Complex
+	{
+		let index = Complex::new(self.n, self.k);
+		index * index
+	}
+}
+
+// Load table with columns "wavelength (in micrometers), n, k".
+// Lines which are empty or start with non-numeric values (like header) are skipped.
+pub fn load_refractive_index_table(file_path: &std::path::Path) -> std::io::Result<Vec<RefractiveIndexSample>>
+{
+	let file = std::io::BufReader::new(std::fs::File::open(file_path)?);
+	let mut result = Vec::new();
+	for line in file.lines()
+	{
+		let line = line?;
+		let values = line.split(',').map(|v| v.trim().parse::<f64>()).collect::<Vec<_>>();
+		if values.first().map(|v| v.is_err()).unwrap_or(true)
+		{
+			continue;
+		}
+		let values = values.into_iter().collect::<Result<Vec<_>, _>>().map_err(|e| {
+			std::io::Error::new(
+				std::io::ErrorKind::InvalidData,
+				format!("Invalid line \"{}\": {}", line, e),
+			)
+		})?;
+		if values.len() < 3 || values[0] <= 0.0
+		{
+			return Err(std::io::Error::new(
+				std::io::ErrorKind::InvalidData,
+				format!("Invalid line \"{}\"", line),
+			));
+		}
+		result.push(RefractiveIndexSample {
+			wavelength: values[0] * 1.0e-6,
+			n: values[1],
+			k: values[2],
+		});
+	}
+	Ok(result)
+}
+
+// Fit permittivity model to measured data using Levenberg-Marquardt method.
+// Drude pole is added automatically if real part of permittivity at lowest frequency is negative (for metals).
+pub fn fit_dispersion_model(samples: &[RefractiveIndexSample], num_lorentz_poles: usize) -> Option<PhysicalMaterial>
+{
+	if samples.is_empty()
+	{
+		return None;
+	}
+
+	// Use frequencies, normalized by maximum frequency, for better conditioning.
+	let reference_frequency = samples.iter().map(|s| s.get_angular_frequency()).fold(0.0, f64::max);
+	let points = samples
+		.iter()
+		.map(|s| (s.get_angular_frequency() / reference_frequency, s.get_permittivity()))
+		.collect::<Vec<_>>();
+
+	let (min_frequency, min_frequency_permittivity) = points
+		.iter()
+		.copied()
+		.min_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal))?;
+	let use_drude_pole = min_frequency_permittivity.re < 0.0;
+	if num_lorentz_poles + (use_drude_pole as usize) > MAX_DISPERSION_POLES
+	{
+		return None;
+	}
+
+	// All parameters are logarithms in order to keep model passive - with non-negative losses.
+	// Parameters are infinite-frequency permittivity excess over one, Drude frequencies and for each Lorentz pole -
+	// permittivity delta and frequencies.
+	let make_initial_parameters = |min_resonance_frequency: f64| {
+		let mut parameters = vec![0.0];
+		if use_drude_pole
+		{
+			let plasma_frequency = min_frequency * (1.0 - min_frequency_permittivity.re).sqrt();
+			let collision_frequency = (min_frequency_permittivity.im * min_frequency.powi(3) /
+				(plasma_frequency * plasma_frequency))
+				.max(0.01 * min_frequency);
+			parameters.push(plasma_frequency.ln());
+			parameters.push(collision_frequency.ln());
+		}
+		for i in 0 .. num_lorentz_poles
+		{
+			// Spread initial resonances from given frequency to frequency above data range.
+			let resonance_frequency = min_resonance_frequency *
+				(4.0 / min_resonance_frequency).powf((i as f64 + 0.5) / (num_lorentz_poles as f64));
+			parameters.push(0.5f64.ln());
+			parameters.push(resonance_frequency.ln());
+			parameters.push((0.1 * resonance_frequency).ln());
+		}
+		parameters
+	};
+
+	let make_poles = |parameters: &[f64], frequency_scale: f64| {
+		let mut poles = Vec::new();
+		let mut p = &parameters[1 ..];
+		if use_drude_pole
+		{
+			poles.push(DispersionPole::Drude {
+				plasma_frequency: (p[0].exp() * frequency_scale) as f32,
+				collision_frequency: (p[1].exp() * frequency_scale) as f32,
+			});
+			p = &p[2 ..];
+		}
+		for pole_parameters in p.chunks(3)
+		{
+			poles.push(DispersionPole::Lorentz {
+				permittivity_delta: pole_parameters[0].exp() as f32,
+				resonance_frequency: (pole_parameters[1].exp() * frequency_scale) as f32,
+				damping: (pole_parameters[2].exp() * frequency_scale) as f32,
+			});
+		}
+		poles
+	};
+
+	let calculate_residuals = |parameters: &[f64]| {
+		let poles = make_poles(parameters, 1.0);
+		let mut residuals = Vec::with_capacity(points.len() * 2);
+		for (frequency, permittivity) in &points
+		{
+			let mut model = Complex::from_real(1.0 + parameters[0].exp());
+			for pole in &poles
+			{
+				model += get_pole_susceptibility(pole, *frequency);
+			}
+			let error = (model - *permittivity).scale(1.0 / permittivity.abs().max(1.0));
+			residuals.push(error.re);
+			residuals.push(error.im);
+		}
+		residuals
+	};
+	// Fitting may converge to local minimum, so try several initial approximations and select the best result.
+	let (parameters, _cost) = [1.0, 0.5, 0.25]
+		.iter()
+		.filter_map(|scale| minimize_least_squares(make_initial_parameters(scale * min_frequency), calculate_residuals))
+		.min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))?;
+
+	Some(PhysicalMaterial {
+		permittivity: (1.0 + parameters[0].exp()) as f32,
+		dispersion_poles: make_poles(&parameters, reference_frequency),
+		..PhysicalMaterial::VACUUM
+	})
+}
+
+// Minimize sum of squares of residuals using Levenberg-Marquardt method.
+// Parameters are limited in order to avoid degenerate poles.
+// Returns optimal parameters and sum of squares of residuals.
+fn minimize_least_squares<F: Fn(&[f64]) -> Vec<f64>>(
+	mut parameters: Vec<f64>,
+	calculate_residuals: F,
+) -> Option<(Vec<f64>, f64)>
+{
+	let calculate_cost = |residuals: &[f64]| residuals.iter().map(|r| r * r).sum::<f64>();
+	let mut residuals = calculate_residuals(&parameters);
+	let mut cost = calculate_cost(&residuals);
+	let mut damping = 1.0e-3;
+	const MAX_ITERATIONS: usize = 500;
+	// Limits for parameters (logarithms).
+	const MIN_LOG_PARAMETER: f64 = -20.0;
+	const MAX_LOG_PARAMETER: f64 = 8.0;
+	for _iteration in 0 .. MAX_ITERATIONS
+	{
+		// Calculate Jacobian using finite differences.
+		let jacobian = (0 .. parameters.len())
+			.map(|j| {
+				let step = 1.0e-6 * parameters[j].abs().max(1.0);
+				let mut shifted_parameters = parameters.clone();
+				shifted_parameters[j] += step;
+				calculate_residuals(&shifted_parameters)
+					.iter()
+					.zip(residuals.iter())
+					.map(|(shifted, r)| (shifted - r) / step)
+					.collect::<Vec<_>>()
+			})
+			.collect::<Vec<_>>();
+
+		let n = parameters.len();
+		let mut matrix = vec![vec![Complex::ZERO; n]; n];
+		let mut rhs = vec![Complex::ZERO; n];
+		for i in 0 .. n
+		{
+			for j in 0 .. n
+			{
+				matrix[i][j] = Complex::from_real(jacobian[i].iter().zip(jacobian[j].iter()).map(|(a, b)| a * b).sum());
+			}
+			matrix[i][i].re *= 1.0 + damping;
+			matrix[i][i].re += 1.0e-12;
+			rhs[i] = Complex::from_real(
+				-jacobian[i]
+					.iter()
+					.zip(residuals.iter())
+					.map(|(a, b)| a * b)
+					.sum::<f64>(),
+			);
+		}
+
+		let delta = solve_linear_system(matrix, rhs)?;
+		let new_parameters = parameters
+			.iter()
+			.zip(delta.iter())
+			.map(|(p, d)| (p + d.re).clamp(MIN_LOG_PARAMETER, MAX_LOG_PARAMETER))
+			.collect::<Vec<_>>();
+		let new_residuals = calculate_residuals(&new_parameters);
+		let new_cost = calculate_cost(&new_residuals);
+		if new_cost.is_finite() && new_cost < cost
+		{
+			let relative_change = (cost - new_cost) / cost.max(1.0e-30);
+			parameters = new_parameters;
+			residuals = new_residuals;
+			cost = new_cost;
+			damping = (damping / 3.0).max(1.0e-12);
+			if relative_change < 1.0e-12
+			{
+				break;
+			}
+		}
+		else
+		{
+			damping *= 4.0;
+			if damping > 1.0e12
+			{
+				break;
+			}
+		}
+	}
+
+	Some((parameters, cost))
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn debye_pole_convention()
+	{
+		// For "exp(-i * w * t)" time dependence lossy media have positive imaginary part of permittivity.
+		let pole = DispersionPole::Debye {
+			permittivity_delta: 2.0,
+			relaxation_time: 0.5,
+		};
+		let susceptibility = get_pole_susceptibility(&pole, 2.0);
+		// "2 / (1 - i)" is "1 + i".
+		assert!((susceptibility.re - 1.0).abs() < 1.0e-6 && (susceptibility.im - 1.0).abs() < 1.0e-6);
+
+		// Permittivity of water at 10 GHz is about "63 + 30 * i".
+		let water = get_library_material("water").unwrap();
+		let permittivity = water.get_permittivity(2.0 * std::f64::consts::PI * 1.0e10);
+		assert!((permittivity.re - 63.0).abs() < 1.0, "{:?}", permittivity);
+		assert!((permittivity.im - 30.0).abs() < 1.0, "{:?}", permittivity);
+	}
+
+	// Sample refractive index of given material at wavelengths in given range (in micrometers).
+	fn make_samples(material: &PhysicalMaterial, min_wavelength: f64, max_wavelength: f64) -> Vec<RefractiveIndexSample>
+	{
+		let num_samples = 40;
+		(0 .. num_samples)
+			.map(|i| {
+				let fraction = (i as f64) / ((num_samples - 1) as f64);
+				let wavelength = (min_wavelength + (max_wavelength - min_wavelength) * fraction) * 1.0e-6;
+				let permittivity = material.get_permittivity(2.0 * std::f64::consts::PI * SPEED_OF_LIGHT / wavelength);
+				let index = Complex::from_polar(permittivity.abs().sqrt(), 0.5 * permittivity.arg());
+				RefractiveIndexSample {
+					wavelength,
+					n: index.re,
+					k: index.im,
+				}
+			})
+			.collect()
+	}
+
+	fn check_parameter(name: &str, fitted: f32, expected: f32)
+	{
+		assert!(
+			(fitted - expected).abs() <= 1.0e-2 * expected.abs(),
+			"{}: fitted {}, expected {}",
+			name,
+			fitted,
+			expected
+		);
+	}
+
+	#[test]
+	fn drude_model_fitting()
+	{
+		let material = PhysicalMaterial {
+			permittivity: 3.0,
+			dispersion_poles: vec![DispersionPole::Drude {
+				plasma_frequency: 1.2e16,
+				collision_frequency: 1.0e14,
+			}],
+			..PhysicalMaterial::VACUUM
+		};
+		let fitted = fit_dispersion_model(&make_samples(&material, 0.4, 1.6), 0).unwrap();
+		check_parameter("permittivity", fitted.permittivity, material.permittivity);
+		match fitted.dispersion_poles[..]
+		{
+			[DispersionPole::Drude {
+				plasma_frequency,
+				collision_frequency,
+			}] =>
+			{
+				check_parameter("plasma frequency", plasma_frequency, 1.2e16);
+				check_parameter("collision frequency", collision_frequency, 1.0e14);
+			},
+			_ => panic!("unexpected poles {:?}", fitted.dispersion_poles),
+		}
+	}
+
+	#[test]
+	fn lorentz_model_fitting()
+	{
+		let material = PhysicalMaterial {
+			permittivity: 2.0,
+			dispersion_poles: vec![DispersionPole::Lorentz {
+				permittivity_delta: 1.5,
+				resonance_frequency: 6.0e15,
+				damping: 2.0e14,
+			}],
+			..PhysicalMaterial::VACUUM
+		};
+		let fitted = fit_dispersion_model(&make_samples(&material, 0.2, 2.0), 1).unwrap();
+		check_parameter("permittivity", fitted.permittivity, material.permittivity);
+		match fitted.dispersion_poles[..]
+		{
+			[DispersionPole::Lorentz {
+				permittivity_delta,
+				resonance_frequency,
+				damping,
+			}] =>
+			{
+				check_parameter("permittivity delta", permittivity_delta, 1.5);
+				check_parameter("resonance frequency", resonance_frequency, 6.0e15);
+				check_parameter("damping", damping, 2.0e14);
+			},
+			_ => panic!("unexpected poles {:?}", fitted.dispersion_poles),
+		}
+	}
+}
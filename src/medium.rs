@@ -0,0 +1,443 @@
+use super::{grid_spacing::*, math_types::*};
+
+// Description of material properties for simulation.
+// All values are relative - in units where vacuum permittivity and permeability are equal to one.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Material
+{
+	// For dispersive materials this is permittivity at infinite frequency.
+	pub permittivity: f32,
+	pub permeability: f32,
+	pub conductivity: f32,
+	pub magnetic_conductivity: f32,
+	// Frequency-dependent part of permittivity.
+	pub dispersion_poles: Vec<DispersionPole>,
+	// Field-dependent part of permittivity.
+	pub nonlinearity: Nonlinearity,
+	// Tensors for anisotropic materials, which override scalar permittivity and permeability values.
+	// Scalar values are still used for absorbing layer and energy calculation, so it's recommended to set them
+	// equal to average of diagonal tensor components.
+	// Averaging of off-diagonal components isn't needed, since all field components are located in cell centers.
+	pub permittivity_tensor: Option<Mat3f>,
+	pub permeability_tensor: Option<Mat3f>,
+}
+
+// Single term of permittivity model (for "exp(-i * w * t)" time dependence). All frequencies are angular.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum DispersionPole
+{
+	// Free electrons - "wp^2 / (-w^2 - i * g * w)".
+	Drude
+	{
+		plasma_frequency: f32, collision_frequency: f32
+	},
+	// Bound electrons - "de * w0^2 / (w0^2 - w^2 - i * g * w)".
+	Lorentz
+	{
+		permittivity_delta: f32,
+		resonance_frequency: f32,
+		damping: f32,
+	},
+	// Relaxation of dipoles - "de / (1 - i * w * t)".
+	Debye
+	{
+		permittivity_delta: f32, relaxation_time: f32
+	},
+}
+
+// Nonlinear polarization, which is (for each component) "chi2 * E^2 + chi3 * |E|^2 * E / (1 + |E|^2 / Es^2)".
+// Second-order term is simplified - it affects each component independently.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Nonlinearity
+{
+	pub second_order_susceptibility: f32,
+	pub third_order_susceptibility: f32,
+	// Field magnitude "Es" of Kerr effect saturation. Zero means no saturation.
+	pub saturation_field: f32,
+}
+
+impl Nonlinearity
+{
+	pub const NONE: Nonlinearity = Nonlinearity {
+		second_order_susceptibility: 0.0,
+		third_order_susceptibility: 0.0,
+		saturation_field: 0.0,
+	};
+
+	pub fn is_linear(&self) -> bool
+	{
+		self.second_order_susceptibility == 0.0 && self.third_order_susceptibility == 0.0
+	}
+}
+
+// Maximum number of dispersion poles of single material.
+pub const MAX_DISPERSION_POLES: usize = 3;
+
+impl Material
+{
+	pub const VACUUM: Material = Material {
+		permittivity: 1.0,
+		permeability: 1.0,
+		conductivity: 0.0,
+		magnetic_conductivity: 0.0,
+		dispersion_poles: Vec::new(),
+		nonlinearity: Nonlinearity::NONE,
+		permittivity_tensor: None,
+		permeability_tensor: None,
+	};
+
+	pub fn dielectric(permittivity: f32) -> Self
+	{
+		Material {
+			permittivity,
+			..Self::VACUUM
+		}
+	}
+}
+
+pub type MaterialId = u32;
+
+// Per-cell medium description. Each cell references one of materials.
+#[derive(Clone)]
+pub struct Medium
+{
+	size: [u32; 3],
+	grid_spacing: GridSpacing,
+	materials: Vec<Material>,
+	material_ids: Vec<MaterialId>,
+}
+
+impl Medium
+{
+	pub const VACUUM_MATERIAL_ID: MaterialId = 0;
+
+	// Create medium filled with vacuum, on uniform grid.
+	pub fn new(size: [u32; 3]) -> Self
+	{
+		Self {
+			size,
+			grid_spacing: GridSpacing::uniform(size),
+			materials: vec![Material::VACUUM],
+			material_ids: vec![Self::VACUUM_MATERIAL_ID; (size[0] * size[1] * size[2]) as usize],
+		}
+	}
+
+	// Create medium with same size and grid spacing, filled with vacuum. Useful for reference simulations.
+	pub fn make_vacuum_copy(&self) -> Self
+	{
+		let mut result = Self::new(self.size);
+		result.grid_spacing = self.grid_spacing.clone();
+		result
+	}
+
+	// Set sizes of cells. Materials of cells are kept, so spacing should be set before filling of medium with
+	// shapes, given in length units.
+	pub fn set_grid_spacing(&mut self, grid_spacing: GridSpacing)
+	{
+		assert_eq!(grid_spacing.get_size(), self.size);
+		self.grid_spacing = grid_spacing;
+	}
+
+	pub fn add_material(&mut self, material: Material) -> MaterialId
+	{
+		assert!(material.dispersion_poles.len() <= MAX_DISPERSION_POLES);
+		self.materials.push(material);
+		(self.materials.len() - 1) as MaterialId
+	}
+
+	// Fill box [min; max) with given material.
+	pub fn fill_box(&mut self, min: [u32; 3], max: [u32; 3], material_id: MaterialId)
+	{
+		assert!((material_id as usize) < self.materials.len());
+		for z in min[2] .. max[2].min(self.size[2])
+		{
+			for y in min[1] .. max[1].min(self.size[1])
+			{
+				for x in min[0] .. max[0].min(self.size[0])
+				{
+					let address = self.get_address(x, y, z);
+					self.material_ids[address] = material_id;
+				}
+			}
+		}
+	}
+
+	// Fill all cells with centers inside given sphere. Center and radius are given in length units.
+	pub fn fill_sphere(&mut self, center: Vec3f, radius: f32, material_id: MaterialId)
+	{
+		assert!((material_id as usize) < self.materials.len());
+		let radius2 = radius * radius;
+		for z in 0 .. self.size[2]
+		{
+			for y in 0 .. self.size[1]
+			{
+				for x in 0 .. self.size[0]
+				{
+					let position = self.grid_spacing.get_cell_center([x, y, z]);
+					if (position - center).magnitude2() <= radius2
+					{
+						let address = self.get_address(x, y, z);
+						self.material_ids[address] = material_id;
+					}
+				}
+			}
+		}
+	}
+
+	pub fn get_size(&self) -> [u32; 3]
+	{
+		self.size
+	}
+
+	pub fn get_grid_spacing(&self) -> &GridSpacing
+	{
+		&self.grid_spacing
+	}
+
+	pub fn get_materials(&self) -> &[Material]
+	{
+		&self.materials
+	}
+
+	pub fn get_material_ids(&self) -> &[MaterialId]
+	{
+		&self.material_ids
+	}
+
+	pub fn has_dispersive_materials(&self) -> bool
+	{
+		self.materials.iter().any(|m| !m.dispersion_poles.is_empty())
+	}
+
+	pub fn has_nonlinear_materials(&self) -> bool
+	{
+		self.materials.iter().any(|m| !m.nonlinearity.is_linear())
+	}
+
+	pub fn has_anisotropic_materials(&self) -> bool
+	{
+		self.materials
+			.iter()
+			.any(|m| m.permittivity_tensor.is_some() || m.permeability_tensor.is_some())
+	}
+
+	fn get_address(&self, x: u32, y: u32, z: u32) -> usize
+	{
+		(x + y * self.size[0] + z * (self.size[0] * self.size[1])) as usize
+	}
+}
+
+// Medium data, uploaded to GPU.
+pub struct MediumBuffers
+{
+	size: [u32; 3],
+	grid_spacing: GridSpacing,
+	// (cell center, cell size) for all cells along X axis, then along Y and Z axes.
+	grid_spacing_buffer: glium::buffer::Buffer<[[f32; 2]]>,
+	material_ids_buffer: glium::buffer::Buffer<[u32]>,
+	// Each material is (permittivity, permeability, conductivity, magnetic conductivity).
+	materials_buffer: glium::buffer::Buffer<[[f32; 4]]>,
+	// MAX_DISPERSION_POLES values for each material - (pole type, pole parameters).
+	// Pole type is 0 for absent pole, 1 for Drude, 2 for Lorentz, 3 for Debye.
+	material_poles_buffer: glium::buffer::Buffer<[[f32; 4]]>,
+	has_dispersive_materials: bool,
+	// Each material is (chi2, chi3, inverse square of saturation field, unused).
+	material_nonlinearities_buffer: glium::buffer::Buffer<[[f32; 4]]>,
+	has_nonlinear_materials: bool,
+	// Six values for each material - rows of permittivity and permeability tensors.
+	// Last component of first row of each tensor is 1 if tensor is present and 0 otherwise.
+	material_tensors_buffer: glium::buffer::Buffer<[[f32; 4]]>,
+	has_anisotropic_materials: bool,
+}
+
+impl MediumBuffers
+{
+	pub fn new(display: &glium::Display, medium: &Medium) -> Self
+	{
+		let materials_data = medium
+			.get_materials()
+			.iter()
+			.map(|m| {
+				[
+					m.permittivity,
+					m.permeability,
+					m.conductivity,
+					m.magnetic_conductivity,
+				]
+			})
+			.collect::<Vec<_>>();
+
+		let mut material_poles_data = Vec::with_capacity(medium.get_materials().len() * MAX_DISPERSION_POLES);
+		for material in medium.get_materials()
+		{
+			for i in 0 .. MAX_DISPERSION_POLES
+			{
+				material_poles_data.push(match material.dispersion_poles.get(i)
+				{
+					None => [0.0; 4],
+					Some(DispersionPole::Drude {
+						plasma_frequency,
+						collision_frequency,
+					}) => [1.0, *plasma_frequency, *collision_frequency, 0.0],
+					Some(DispersionPole::Lorentz {
+						permittivity_delta,
+						resonance_frequency,
+						damping,
+					}) => [2.0, *permittivity_delta, *resonance_frequency, *damping],
+					Some(DispersionPole::Debye {
+						permittivity_delta,
+						relaxation_time,
+					}) => [3.0, *permittivity_delta, *relaxation_time, 0.0],
+				});
+			}
+		}
+
+		let material_nonlinearities_data = medium
+			.get_materials()
+			.iter()
+			.map(|m| {
+				let n = &m.nonlinearity;
+				[
+					n.second_order_susceptibility,
+					n.third_order_susceptibility,
+					if n.saturation_field > 0.0
+					{
+						1.0 / (n.saturation_field * n.saturation_field)
+					}
+					else
+					{
+						0.0
+					},
+					0.0,
+				]
+			})
+			.collect::<Vec<_>>();
+
+		let mut material_tensors_data = Vec::with_capacity(medium.get_materials().len() * 6);
+		for material in medium.get_materials()
+		{
+			for tensor in [material.permittivity_tensor, material.permeability_tensor]
+			{
+				match tensor
+				{
+					Some(t) =>
+					{
+						// Matrix is indexed by columns, store rows.
+						material_tensors_data.push([t[0][0], t[1][0], t[2][0], 1.0]);
+						material_tensors_data.push([t[0][1], t[1][1], t[2][1], 0.0]);
+						material_tensors_data.push([t[0][2], t[1][2], t[2][2], 0.0]);
+					},
+					None =>
+					{
+						material_tensors_data.extend_from_slice(&[[0.0; 4]; 3]);
+					},
+				}
+			}
+		}
+
+		Self {
+			size: medium.get_size(),
+			grid_spacing: medium.get_grid_spacing().clone(),
+			grid_spacing_buffer: glium::buffer::Buffer::new(
+				display,
+				&medium.get_grid_spacing().get_shader_data()[..],
+				glium::buffer::BufferType::ShaderStorageBuffer,
+				glium::buffer::BufferMode::Default,
+			)
+			.unwrap(),
+			material_ids_buffer: glium::buffer::Buffer::new(
+				display,
+				medium.get_material_ids(),
+				glium::buffer::BufferType::ShaderStorageBuffer,
+				glium::buffer::BufferMode::Default,
+			)
+			.unwrap(),
+			materials_buffer: glium::buffer::Buffer::new(
+				display,
+				&materials_data[..],
+				glium::buffer::BufferType::ShaderStorageBuffer,
+				glium::buffer::BufferMode::Default,
+			)
+			.unwrap(),
+			material_poles_buffer: glium::buffer::Buffer::new(
+				display,
+				&material_poles_data[..],
+				glium::buffer::BufferType::ShaderStorageBuffer,
+				glium::buffer::BufferMode::Default,
+			)
+			.unwrap(),
+			has_dispersive_materials: medium.has_dispersive_materials(),
+			material_nonlinearities_buffer: glium::buffer::Buffer::new(
+				display,
+				&material_nonlinearities_data[..],
+				glium::buffer::BufferType::ShaderStorageBuffer,
+				glium::buffer::BufferMode::Default,
+			)
+			.unwrap(),
+			has_nonlinear_materials: medium.has_nonlinear_materials(),
+			material_tensors_buffer: glium::buffer::Buffer::new(
+				display,
+				&material_tensors_data[..],
+				glium::buffer::BufferType::ShaderStorageBuffer,
+				glium::buffer::BufferMode::Default,
+			)
+			.unwrap(),
+			has_anisotropic_materials: medium.has_anisotropic_materials(),
+		}
+	}
+
+	pub fn get_size(&self) -> [u32; 3]
+	{
+		self.size
+	}
+
+	pub fn get_grid_spacing(&self) -> &GridSpacing
+	{
+		&self.grid_spacing
+	}
+
+	pub fn get_grid_spacing_buffer(&self) -> &glium::buffer::Buffer<[[f32; 2]]>
+	{
+		&self.grid_spacing_buffer
+	}
+
+	pub fn get_material_ids_buffer(&self) -> &glium::buffer::Buffer<[u32]>
+	{
+		&self.material_ids_buffer
+	}
+
+	pub fn get_materials_buffer(&self) -> &glium::buffer::Buffer<[[f32; 4]]>
+	{
+		&self.materials_buffer
+	}
+
+	pub fn get_material_poles_buffer(&self) -> &glium::buffer::Buffer<[[f32; 4]]>
+	{
+		&self.material_poles_buffer
+	}
+
+	pub fn has_dispersive_materials(&self) -> bool
+	{
+		self.has_dispersive_materials
+	}
+
+	pub fn get_material_nonlinearities_buffer(&self) -> &glium::buffer::Buffer<[[f32; 4]]>
+	{
+		&self.material_nonlinearities_buffer
+	}
+
+	pub fn has_nonlinear_materials(&self) -> bool
+	{
+		self.has_nonlinear_materials
+	}
+
+	pub fn get_material_tensors_buffer(&self) -> &glium::buffer::Buffer<[[f32; 4]]>
+	{
+		&self.material_tensors_buffer
+	}
+
+	pub fn has_anisotropic_materials(&self) -> bool
+	{
+		self.has_anisotropic_materials
+	}
+}
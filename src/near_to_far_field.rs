@@ -0,0 +1,456 @@
+use super::{complex_number::*, dft_field_monitor::*, electromagnetic_field::*, grid_spacing::*};
+use std::io::Write;
+
+// Closed Huygens surface, collecting DFT of fields on faces of a box, which is used for near-to-far-field
+// transformation. Box should be placed in vacuum and should contain all radiating sources and scatterers.
+// Surface goes through centers of border cells of the box.
+pub struct HuygensSurface
+{
+	box_min: [u32; 3],
+	box_max: [u32; 3],
+	frequencies: Vec<f32>,
+	faces: Vec<HuygensSurfaceFace>,
+	grid_spacing: GridSpacing,
+}
+
+struct HuygensSurfaceFace
+{
+	dft_field_monitor: DftFieldMonitor,
+	normal_axis: usize,
+	// 1 for face with outward normal along axis, -1 for opposite face.
+	normal_sign: f64,
+}
+
+// Far field in some direction, normalized as "E(r) * r * exp(i * k * r)".
+#[derive(Copy, Clone)]
+pub struct FarFieldSample
+{
+	pub e_theta: Complex,
+	pub e_phi: Complex,
+}
+
+pub struct RadiationPattern
+{
+	pub frequency: f32,
+	// Polar angles (measured from Z axis) from 0 to PI inclusive.
+	pub thetas: Vec<f64>,
+	// Azimuthal angles (measured from X axis) from 0 to 2 * PI exclusive.
+	pub phis: Vec<f64>,
+	// Samples for each theta, for each phi inside each theta.
+	pub samples: Vec<FarFieldSample>,
+	// Total power flowing out through Huygens surface.
+	pub radiated_power: f64,
+}
+
+impl HuygensSurface
+{
+	pub fn new(
+		display: &glium::Display,
+		box_min: [u32; 3],
+		box_max: [u32; 3],
+		frequencies: &[f32],
+		grid_spacing: &GridSpacing,
+	) -> Self
+	{
+		for i in 0 .. 3
+		{
+			assert!(box_max[i] > box_min[i] + 1);
+		}
+
+		let mut faces = Vec::with_capacity(6);
+		for normal_axis in 0 .. 3
+		{
+			for (normal_sign, position) in [(-1.0, box_min[normal_axis]), (1.0, box_max[normal_axis] - 1)]
+			{
+				let mut region_offset = box_min;
+				region_offset[normal_axis] = position;
+				let mut region_size = [0; 3];
+				for i in 0 .. 3
+				{
+					region_size[i] = if i == normal_axis { 1 } else { box_max[i] - box_min[i] };
+				}
+
+				faces.push(HuygensSurfaceFace {
+					dft_field_monitor: DftFieldMonitor::new(
+						display,
+						&DftFieldMonitorParams {
+							region_offset,
+							region_size,
+							frequencies: frequencies.to_vec(),
+						},
+					),
+					normal_axis,
+					normal_sign,
+				});
+			}
+		}
+
+		Self {
+			box_min,
+			box_max,
+			frequencies: frequencies.to_vec(),
+			faces,
+			grid_spacing: grid_spacing.clone(),
+		}
+	}
+
+	// Should be called after each field update step, see DftFieldMonitor::accumulate.
+	pub fn accumulate(&mut self, field: &ElectromagneticField, time: f32, time_delta_s: f32)
+	{
+		for face in &mut self.faces
+		{
+			face.dft_field_monitor.accumulate(field, time, time_delta_s);
+		}
+	}
+
+	// Calculate far field for given frequency on uniform grid of directions.
+	pub fn compute_radiation_pattern(
+		&self,
+		frequency_index: usize,
+		num_theta: usize,
+		num_phi: usize,
+	) -> RadiationPattern
+	{
+		transform_to_far_field(
+			self.frequencies[frequency_index],
+			&self.collect_surface_elements(frequency_index),
+			self.calculate_flux_spectrum()[frequency_index],
+			num_theta,
+			num_phi,
+		)
+	}
+
+	// Calculate net time-averaged power flowing out through surface for each frequency.
+	// This allows to use Huygens surface as closed flux monitor.
+	pub fn calculate_flux_spectrum(&self) -> Vec<f64>
+	{
+		let mut result = vec![0.0; self.frequencies.len()];
+		self.visit_surface_cells(|frequency_index, cell| {
+			// Time-averaged Poynting vector - Re(E x H*) / 2.
+			let poynting_vector = cross(&cell.electric, &cell.magnetic.map(|c| c.conj()));
+			result[frequency_index] += cell.weight * 0.5 * project(&poynting_vector, &cell.normal).re;
+		});
+		result
+	}
+
+	// Calculate equivalent surface currents (multiplied by area of surface elements) for given frequency.
+	fn collect_surface_elements(&self, frequency_index: usize) -> Vec<SurfaceElement>
+	{
+		let mut elements = Vec::new();
+		self.visit_surface_cells(|cell_frequency_index, cell| {
+			if cell_frequency_index != frequency_index
+			{
+				return;
+			}
+
+			// J = n x H, M = -n x E.
+			let normal = cell.normal.map(Complex::from_real);
+			let electric_current = cross(&normal, &cell.magnetic);
+			let magnetic_current = cross(&cell.electric, &normal);
+			elements.push(SurfaceElement {
+				position: cell.position,
+				electric_current: electric_current.map(|c| c.scale(cell.weight)),
+				magnetic_current: magnetic_current.map(|c| c.scale(cell.weight)),
+			});
+		});
+		elements
+	}
+
+	// Call given function for each frequency index and each cell of surface.
+	fn visit_surface_cells<F: FnMut(usize, &SurfaceCell)>(&self, mut f: F)
+	{
+		let grid_spacing = &self.grid_spacing;
+		let center = [0, 1, 2].map(|i| {
+			0.5 * (grid_spacing.get_boundary(i, self.box_min[i]) + grid_spacing.get_boundary(i, self.box_max[i])) as f64
+		});
+
+		for face in &self.faces
+		{
+			let region_offset = face.dft_field_monitor.get_region_offset();
+			let region_size = face.dft_field_monitor.get_region_size();
+			let cell_count = (region_size[0] * region_size[1] * region_size[2]) as usize;
+			let samples = face.dft_field_monitor.read_samples();
+
+			let mut normal = [0.0; 3];
+			normal[face.normal_axis] = face.normal_sign;
+
+			for (sample_index, sample) in samples.iter().enumerate()
+			{
+				let cell_index = (sample_index % cell_count) as u32;
+				let coord = [
+					cell_index % region_size[0],
+					cell_index / region_size[0] % region_size[1],
+					cell_index / (region_size[0] * region_size[1]),
+				];
+
+				let position = [0, 1, 2].map(|i| coord[i] + region_offset[i]);
+
+				// Use trapezoidal rule over cell centers - cells on edges of faces are shared with neighbor faces.
+				let mut weight = 1.0;
+				for i in 0 .. 3
+				{
+					if i == face.normal_axis
+					{
+						continue;
+					}
+					let prev_index = if coord[i] == 0 { position[i] } else { position[i] - 1 };
+					let next_index = if coord[i] == region_size[i] - 1 { position[i] } else { position[i] + 1 };
+					let distance = grid_spacing.get_center(i, next_index) - grid_spacing.get_center(i, prev_index);
+					weight *= 0.5 * distance as f64;
+				}
+
+				f(
+					sample_index / cell_count,
+					&SurfaceCell {
+						position: [0, 1, 2].map(|i| grid_spacing.get_center(i, position[i]) as f64 - center[i]),
+						normal,
+						weight,
+						electric: sample.electric.map(|c| Complex::new(c[0] as f64, c[1] as f64)),
+						magnetic: sample.magnetic.map(|c| Complex::new(c[0] as f64, c[1] as f64)),
+					},
+				);
+			}
+		}
+	}
+}
+
+impl RadiationPattern
+{
+	pub fn get_num_theta(&self) -> usize
+	{
+		self.thetas.len()
+	}
+
+	pub fn get_num_phi(&self) -> usize
+	{
+		self.phis.len()
+	}
+
+	pub fn get_sample_index(&self, theta_index: usize, phi_index: usize) -> usize
+	{
+		theta_index * self.phis.len() + phi_index
+	}
+
+	// Radiation intensity (power per unit solid angle).
+	pub fn get_intensity(&self, sample_index: usize) -> f64
+	{
+		let sample = &self.samples[sample_index];
+		0.5 * (sample.e_theta.norm_sqr() + sample.e_phi.norm_sqr())
+	}
+
+	pub fn get_directivity(&self, sample_index: usize) -> f64
+	{
+		4.0 * std::f64::consts::PI * self.get_intensity(sample_index) / self.radiated_power
+	}
+
+	// Gain relative to power, accepted by antenna. Input power should be calculated using same DFT normalization as
+	// fields on Huygens surface.
+	pub fn get_gain(&self, sample_index: usize, input_power: f64) -> f64
+	{
+		4.0 * std::f64::consts::PI * self.get_intensity(sample_index) / input_power
+	}
+
+	pub fn get_max_directivity(&self) -> f64
+	{
+		(0 .. self.samples.len())
+			.map(|i| self.get_directivity(i))
+			.fold(0.0, f64::max)
+	}
+
+	// Export full 3D pattern into CSV file. Gain is exported only if input power is known.
+	pub fn export(&self, file_path: &std::path::Path, input_power: Option<f64>) -> std::io::Result<()>
+	{
+		let mut file = std::io::BufWriter::new(std::fs::File::create(file_path)?);
+		write!(
+			file,
+			"theta_deg,phi_deg,e_theta_re,e_theta_im,e_phi_re,e_phi_im,directivity_dbi"
+		)?;
+		if input_power.is_some()
+		{
+			write!(file, ",gain_dbi")?;
+		}
+		writeln!(file)?;
+
+		for (theta_index, theta) in self.thetas.iter().enumerate()
+		{
+			for (phi_index, phi) in self.phis.iter().enumerate()
+			{
+				let sample_index = self.get_sample_index(theta_index, phi_index);
+				let sample = &self.samples[sample_index];
+				write!(
+					file,
+					"{},{},{},{},{},{},{}",
+					theta.to_degrees(),
+					phi.to_degrees(),
+					sample.e_theta.re,
+					sample.e_theta.im,
+					sample.e_phi.re,
+					sample.e_phi.im,
+					to_decibels(self.get_directivity(sample_index))
+				)?;
+				if let Some(input_power) = input_power
+				{
+					write!(file, ",{}", to_decibels(self.get_gain(sample_index, input_power)))?;
+				}
+				writeln!(file)?;
+			}
+		}
+		file.flush()
+	}
+
+	// Export 2D cuts of pattern in XZ (phi = 0) and YZ (phi = 90) planes into CSV file.
+	// Angle in cut plane is measured from Z axis, negative angles correspond to opposite half-plane.
+	// Number of phi samples should be multiple of 4.
+	pub fn export_cuts(&self, file_path: &std::path::Path, input_power: Option<f64>) -> std::io::Result<()>
+	{
+		let num_phi = self.get_num_phi();
+		assert!(num_phi.is_multiple_of(4));
+
+		let mut file = std::io::BufWriter::new(std::fs::File::create(file_path)?);
+		write!(file, "angle_deg,directivity_xz_dbi,directivity_yz_dbi")?;
+		if input_power.is_some()
+		{
+			write!(file, ",gain_xz_dbi,gain_yz_dbi")?;
+		}
+		writeln!(file)?;
+
+		let num_theta = self.get_num_theta();
+		let rows = (1 .. num_theta)
+			.rev()
+			.map(|theta_index| (theta_index, num_phi / 2, -1.0))
+			.chain((0 .. num_theta).map(|theta_index| (theta_index, 0, 1.0)));
+		for (theta_index, phi_offset, sign) in rows
+		{
+			let sample_indices = [phi_offset, phi_offset + num_phi / 4]
+				.map(|phi_index| self.get_sample_index(theta_index, phi_index % num_phi));
+
+			write!(file, "{}", sign * self.thetas[theta_index].to_degrees())?;
+			for sample_index in sample_indices
+			{
+				write!(file, ",{}", to_decibels(self.get_directivity(sample_index)))?;
+			}
+			if let Some(input_power) = input_power
+			{
+				for sample_index in sample_indices
+				{
+					write!(file, ",{}", to_decibels(self.get_gain(sample_index, input_power)))?;
+				}
+			}
+			writeln!(file)?;
+		}
+		file.flush()
+	}
+}
+
+// Calculate far field for given frequency on uniform grid of directions, using equivalent surface currents.
+fn transform_to_far_field(
+	frequency: f32,
+	elements: &[SurfaceElement],
+	radiated_power: f64,
+	num_theta: usize,
+	num_phi: usize,
+) -> RadiationPattern
+{
+	assert!(num_theta >= 2 && num_phi >= 1);
+
+	let thetas = (0 .. num_theta)
+		.map(|i| std::f64::consts::PI * (i as f64) / ((num_theta - 1) as f64))
+		.collect::<Vec<_>>();
+	let phis = (0 .. num_phi)
+		.map(|i| 2.0 * std::f64::consts::PI * (i as f64) / (num_phi as f64))
+		.collect::<Vec<_>>();
+
+	let wave_number = 2.0 * std::f64::consts::PI * (frequency as f64);
+	// Far field of radiation vectors is "i * k / (4 * PI)" multiplied by their combinations.
+	let factor = Complex::new(0.0, wave_number / (4.0 * std::f64::consts::PI));
+
+	let mut samples = Vec::with_capacity(num_theta * num_phi);
+	for theta in &thetas
+	{
+		for phi in &phis
+		{
+			let (sin_theta, cos_theta) = theta.sin_cos();
+			let (sin_phi, cos_phi) = phi.sin_cos();
+			let direction = [sin_theta * cos_phi, sin_theta * sin_phi, cos_theta];
+			let theta_direction = [cos_theta * cos_phi, cos_theta * sin_phi, -sin_theta];
+			let phi_direction = [-sin_phi, cos_phi, 0.0];
+
+			// Radiation vectors of electric and magnetic currents.
+			let mut n = [Complex::ZERO; 3];
+			let mut l = [Complex::ZERO; 3];
+			for element in elements
+			{
+				let phase = Complex::from_polar(1.0, wave_number * dot(&direction, &element.position));
+				for i in 0 .. 3
+				{
+					n[i] += element.electric_current[i] * phase;
+					l[i] += element.magnetic_current[i] * phase;
+				}
+			}
+
+			let n_theta = project(&n, &theta_direction);
+			let n_phi = project(&n, &phi_direction);
+			let l_theta = project(&l, &theta_direction);
+			let l_phi = project(&l, &phi_direction);
+			samples.push(FarFieldSample {
+				e_theta: -(factor * (l_phi + n_theta)),
+				e_phi: factor * (l_theta - n_phi),
+			});
+		}
+	}
+
+	RadiationPattern {
+		frequency,
+		thetas,
+		phis,
+		samples,
+		radiated_power,
+	}
+}
+
+struct SurfaceCell
+{
+	// Position relative to center of Huygens surface.
+	position: [f64; 3],
+	// Outward normal.
+	normal: [f64; 3],
+	// Area of surface element.
+	weight: f64,
+	electric: [Complex; 3],
+	magnetic: [Complex; 3],
+}
+
+struct SurfaceElement
+{
+	// Position relative to center of Huygens surface.
+	position: [f64; 3],
+	electric_current: [Complex; 3],
+	magnetic_current: [Complex; 3],
+}
+
+fn cross(a: &[Complex; 3], b: &[Complex; 3]) -> [Complex; 3]
+{
+	[
+		a[1] * b[2] - a[2] * b[1],
+		a[2] * b[0] - a[0] * b[2],
+		a[0] * b[1] - a[1] * b[0],
+	]
+}
+
+fn project(v: &[Complex; 3], direction: &[f64; 3]) -> Complex
+{
+	v.iter()
+		.zip(direction.iter())
+		.fold(Complex::ZERO, |sum, (c, d)| sum + c.scale(*d))
+}
+
+fn dot(a: &[f64; 3], b: &[f64; 3]) -> f64
+{
+	a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn to_decibels(value: f64) -> f64
+{
+	10.0 * value.max(1.0e-30).log10()
+}
+
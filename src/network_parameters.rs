@@ -0,0 +1,296 @@
+use super::{
+	complex_number::*, electromagnetic_field::*, electromagnetic_field_updater::*, lumped_element::*,
+	material_library::*, medium::*, thin_wire::*, vector_field::*, waveform::*,
+};
+use std::io::Write;
+
+// Setup for calculation of network parameters of device with several ports.
+// Each port is lumped voltage source, which internal resistance is used as reference impedance.
+// Separate simulation is performed for each port, where only this port is excited and other ports act as matched loads.
+pub struct NetworkSetup
+{
+	pub field_size: [u32; 3],
+	pub medium: Medium,
+	pub boundary_conditions: [BoundaryCondition; 3],
+	pub spatial_stencil: SpatialStencil,
+	pub ports: Vec<Port>,
+	// Additional lumped elements (loads, matching circuits, etc.).
+	pub lumped_elements: Vec<LumpedElement>,
+	// Wires of antennas, connected to ports.
+	pub thin_wires: Vec<ThinWire>,
+	pub waveform: Waveform,
+	pub time_step: f32,
+	pub num_steps: u32,
+	pub frequencies: Vec<f32>,
+}
+
+#[derive(Copy, Clone)]
+pub struct Port
+{
+	pub position: [u32; 3],
+	pub axis: usize,
+	// Reference impedance (in vacuum impedance units).
+	pub resistance: f32,
+}
+
+pub struct NetworkParameters
+{
+	pub frequencies: Vec<f32>,
+	pub reference_impedances: Vec<f32>,
+	// Scattering matrix for each frequency, indexed as [frequency][output port][input port].
+	pub s_parameters: Vec<Vec<Vec<Complex>>>,
+	// Input impedance of each port (with other ports matched) for each frequency, indexed as [frequency][port].
+	pub input_impedances: Vec<Vec<Complex>>,
+}
+
+impl NetworkParameters
+{
+	pub fn get_num_ports(&self) -> usize
+	{
+		self.reference_impedances.len()
+	}
+}
+
+pub fn compute_network_parameters(display: &glium::Display, setup: &NetworkSetup) -> NetworkParameters
+{
+	let num_ports = setup.ports.len();
+	let num_frequencies = setup.frequencies.len();
+
+	let mut s_parameters = vec![vec![vec![Complex::ZERO; num_ports]; num_ports]; num_frequencies];
+	let mut input_impedances = vec![vec![Complex::ZERO; num_ports]; num_frequencies];
+	for excited_port_index in 0 .. num_ports
+	{
+		// Power waves for each port and each frequency.
+		let waves = run_simulation(display, setup, excited_port_index);
+		for frequency_index in 0 .. num_frequencies
+		{
+			let (incident_wave, _) = waves[excited_port_index][frequency_index];
+			for (port_index, port_waves) in waves.iter().enumerate()
+			{
+				let (_, reflected_wave) = port_waves[frequency_index];
+				s_parameters[frequency_index][port_index][excited_port_index] = reflected_wave / incident_wave;
+			}
+
+			// Matched ports have no incident wave, so input impedance is obtained from reflection coefficient.
+			let reflection = s_parameters[frequency_index][excited_port_index][excited_port_index];
+			input_impedances[frequency_index][excited_port_index] = (Complex::ONE + reflection) /
+				(Complex::ONE - reflection) *
+				Complex::from_real(setup.ports[excited_port_index].resistance as f64);
+		}
+	}
+
+	NetworkParameters {
+		frequencies: setup.frequencies.clone(),
+		reference_impedances: setup.ports.iter().map(|p| p.resistance).collect(),
+		s_parameters,
+		input_impedances,
+	}
+}
+
+// Write S-parameters in Touchstone (version 1) format, using real and imaginary parts.
+// Cell size is used for conversion of frequency and impedance into physical units.
+// All ports should have the same reference impedance.
+pub fn write_touchstone_file(
+	parameters: &NetworkParameters,
+	cell_size_m: f64,
+	file_path: &std::path::Path,
+) -> std::io::Result<()>
+{
+	let reference_impedance = parameters.reference_impedances.first().copied().unwrap_or(1.0);
+	if parameters
+		.reference_impedances
+		.iter()
+		.any(|r| *r != reference_impedance)
+	{
+		return Err(std::io::Error::new(
+			std::io::ErrorKind::InvalidInput,
+			"Touchstone file requires the same reference impedance for all ports",
+		));
+	}
+
+	let frequency_scale = SPEED_OF_LIGHT / cell_size_m;
+	let num_ports = parameters.get_num_ports();
+
+	let mut file = std::io::BufWriter::new(std::fs::File::create(file_path)?);
+	writeln!(file, "! Fernweh S-parameters, {} ports", num_ports)?;
+	writeln!(file, "# HZ S RI R {}", reference_impedance as f64 * VACUUM_IMPEDANCE)?;
+	for (frequency, s) in parameters.frequencies.iter().zip(parameters.s_parameters.iter())
+	{
+		write!(file, "{:e}", *frequency as f64 * frequency_scale)?;
+		if num_ports <= 2
+		{
+			// One-port and two-port files contain all values in one line, two-port files use column-major order.
+			for j in 0 .. num_ports
+			{
+				for row in s
+				{
+					write!(file, " {:e} {:e}", row[j].re, row[j].im)?;
+				}
+			}
+			writeln!(file)?;
+			continue;
+		}
+
+		// Each row of matrix starts from new line, at most four values are written per line.
+		for row in s
+		{
+			for (j, value) in row.iter().enumerate()
+			{
+				if j > 0 && j % 4 == 0
+				{
+					writeln!(file)?;
+				}
+				write!(file, " {:e} {:e}", value.re, value.im)?;
+			}
+			writeln!(file)?;
+		}
+	}
+	file.flush()
+}
+
+// Two microstrip lines, connected by series gap, over copper ground plane.
+// Ports are placed between ground plane and ends of strip.
+pub fn create_test_microstrip_setup(spatial_stencil: SpatialStencil) -> (NetworkSetup, f64)
+{
+	let cell_size_m = 0.25e-3;
+	let field_size = [40, 112, 32];
+	let absorbing_layer_width = 12;
+
+	let mut medium = Medium::new(field_size);
+	let substrate_material_id = medium.add_material(Material::dielectric(4.4));
+	let copper_material_id = medium.add_material(get_library_material("copper").unwrap().to_material(cell_size_m));
+
+	let ground_z = 12;
+	let strip_z = ground_z + 2;
+	let strip_width = 3;
+	let strip_x = (field_size[0] - strip_width) / 2;
+	let strip_start_y = 24;
+	let strip_end_y = field_size[1] - 24;
+	let gap_y = field_size[1] / 2;
+	medium.fill_box(
+		[absorbing_layer_width, absorbing_layer_width, ground_z],
+		[
+			field_size[0] - absorbing_layer_width,
+			field_size[1] - absorbing_layer_width,
+			strip_z,
+		],
+		substrate_material_id,
+	);
+	medium.fill_box(
+		[absorbing_layer_width, absorbing_layer_width, ground_z],
+		[
+			field_size[0] - absorbing_layer_width,
+			field_size[1] - absorbing_layer_width,
+			ground_z + 1,
+		],
+		copper_material_id,
+	);
+	medium.fill_box(
+		[strip_x, strip_start_y, strip_z],
+		[strip_x + strip_width, gap_y, strip_z + 1],
+		copper_material_id,
+	);
+	medium.fill_box(
+		[strip_x, gap_y + 1, strip_z],
+		[strip_x + strip_width, strip_end_y, strip_z + 1],
+		copper_material_id,
+	);
+
+	let resistance = (50.0 / VACUUM_IMPEDANCE) as f32;
+	let port_x = strip_x + strip_width / 2;
+	let ports = vec![
+		Port {
+			position: [port_x, strip_start_y, ground_z + 1],
+			axis: 2,
+			resistance,
+		},
+		Port {
+			position: [port_x, strip_end_y - 1, ground_z + 1],
+			axis: 2,
+			resistance,
+		},
+	];
+
+	let setup = NetworkSetup {
+		field_size,
+		medium,
+		boundary_conditions: [BoundaryCondition::Absorbing {
+			width: absorbing_layer_width,
+		}; 3],
+		spatial_stencil,
+		ports,
+		lumped_elements: Vec::new(),
+		thin_wires: Vec::new(),
+		waveform: Waveform::modulated_gaussian_pulse(0.04, 8.0),
+		time_step: 0.5,
+		num_steps: 6000,
+		frequencies: (0 .. 61).map(|i| 0.01 + 0.001 * (i as f32)).collect(),
+	};
+	(setup, cell_size_m)
+}
+
+// Run simulation with given excited port and calculate power waves (incident, reflected) for each port and each
+// frequency.
+fn run_simulation(
+	display: &glium::Display,
+	setup: &NetworkSetup,
+	excited_port_index: usize,
+) -> Vec<Vec<(Complex, Complex)>>
+{
+	let mut field = ElectromagneticField {
+		electric_field: VectorField::new(display, setup.field_size),
+		magnetic_field: VectorField::new(display, setup.field_size),
+	};
+
+	let mut updater = ElectromagneticFieldUpdater::new(display, &setup.medium, setup.boundary_conditions);
+	updater.set_spatial_stencil(setup.spatial_stencil);
+	let mut lumped_elements = setup
+		.ports
+		.iter()
+		.enumerate()
+		.map(|(index, port)| LumpedElement {
+			position: port.position,
+			axis: port.axis,
+			kind: LumpedElementKind::VoltageSource {
+				voltage: if index == excited_port_index { 1.0 } else { 0.0 },
+				waveform: setup.waveform.clone(),
+				resistance: port.resistance,
+			},
+		})
+		.collect::<Vec<_>>();
+	lumped_elements.extend_from_slice(&setup.lumped_elements);
+	updater.set_lumped_elements(display, lumped_elements);
+	updater.set_thin_wires(display, &setup.thin_wires);
+
+	let mut recorder = LumpedElementsRecorder::new(display, updater.get_lumped_elements().len());
+
+	let mut time = 0.0;
+	for _i in 0 .. setup.num_steps
+	{
+		updater.update(&mut field, time, setup.time_step);
+		time += setup.time_step;
+		recorder.record(&updater, time - 0.5 * setup.time_step);
+	}
+	recorder.flush();
+
+	(0 .. setup.ports.len())
+		.map(|port_index| {
+			let resistance = setup.ports[port_index].resistance as f64;
+			let wave_scale = 0.5 / resistance.sqrt();
+			setup
+				.frequencies
+				.iter()
+				.map(|frequency| {
+					let (voltage, current) = recorder.calculate_spectrum(port_index, *frequency);
+					// Element uses passive sign convention, so voltage of device terminals has opposite sign.
+					let voltage = -voltage;
+					let scaled_current = current.scale(resistance);
+					(
+						(voltage + scaled_current).scale(wave_scale),
+						(voltage - scaled_current).scale(wave_scale),
+					)
+				})
+				.collect()
+		})
+		.collect()
+}
@@ -0,0 +1,497 @@
+use super::{
+	complex_number::*, cpu_electromagnetic_field_updater::*, electromagnetic_field_updater::*,
+	material_library::get_pole_susceptibility, medium::*,
+};
+use std::io::Write;
+
+// Analysis of numerical dispersion of field update scheme. Curl is calculated with central differences and leapfrog
+// time integration is used, so plane wave "exp(i * (k * r - w * t))" in medium with refractive index "n" satisfies
+// "n^2 * (2 / dt * sin(w * dt / 2))^2 = s(kx)^2 + s(ky)^2 + s(kz)^2", where "s(k)" is "sin(k)" for second-order
+// stencil and "(8 * sin(k) - sin(2 * k)) / 6" for fourth-order stencil.
+// Number of cells per wavelength is calculated using wave vector, i.e. it's numerical wavelength.
+
+pub struct DispersionSample
+{
+	pub spatial_stencil: SpatialStencil,
+	pub cells_per_wavelength: f64,
+	// Polar and azimuthal angles of propagation direction.
+	pub theta: f64,
+	pub phi: f64,
+	// Phase velocities are relative to speed of light in medium.
+	pub phase_velocity: f64,
+	pub measured_phase_velocity: Option<f64>,
+}
+
+// Maximum phase velocity error, which is considered acceptable for scene.
+pub const MAX_PHASE_VELOCITY_ERROR: f64 = 0.02;
+
+pub struct ResolutionReport
+{
+	// Shortest wavelength in scene (in largest cells of grid).
+	pub shortest_wavelength: f64,
+	// Maximum phase velocity error (over propagation directions) for shortest wavelength.
+	// None if such wave can't propagate on grid.
+	pub max_phase_velocity_error: Option<f64>,
+}
+
+impl ResolutionReport
+{
+	pub fn is_under_resolved(&self) -> bool
+	{
+		self.max_phase_velocity_error
+			.is_none_or(|error| error > MAX_PHASE_VELOCITY_ERROR)
+	}
+}
+
+// Solve numerical dispersion relation for angular frequency. Returns None if there is no real solution (time step is
+// too large for such wave vector).
+pub fn calculate_angular_frequency(
+	wave_vector: [f64; 3],
+	time_step: f64,
+	refractive_index: f64,
+	spatial_stencil: SpatialStencil,
+) -> Option<f64>
+{
+	let spatial_term = wave_vector
+		.iter()
+		.map(|k| get_stencil_wave_number(*k, spatial_stencil).powi(2))
+		.sum::<f64>()
+		.sqrt();
+	let sine = 0.5 * time_step * spatial_term / refractive_index;
+	if sine > 1.0
+	{
+		return None;
+	}
+	Some(2.0 / time_step * sine.asin())
+}
+
+// Calculate phase velocity (relative to speed of light in medium) of wave with given numerical wavelength.
+// Returns None if wave can't propagate - if time step is too large or if wavelength is too short for stencil (shorter
+// than four cells for second-order stencil), since central differences can't represent shorter waves.
+pub fn calculate_phase_velocity(
+	cells_per_wavelength: f64,
+	direction: [f64; 3],
+	time_step: f64,
+	refractive_index: f64,
+	spatial_stencil: SpatialStencil,
+) -> Option<f64>
+{
+	let wave_number = 2.0 * std::f64::consts::PI / cells_per_wavelength;
+	let max_wave_number = get_max_stencil_wave_number(spatial_stencil);
+	if direction.iter().any(|d| (d * wave_number).abs() > max_wave_number)
+	{
+		return None;
+	}
+	let wave_vector = direction.map(|d| d * wave_number);
+	calculate_angular_frequency(wave_vector, time_step, refractive_index, spatial_stencil)
+		.map(|angular_frequency| angular_frequency * refractive_index / wave_number)
+}
+
+// Get unit vector of propagation direction.
+pub fn get_direction(theta: f64, phi: f64) -> [f64; 3]
+{
+	[theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos()]
+}
+
+// Compute phase velocity in vacuum for given numbers of cells per wavelength.
+// Analytic samples cover polar angles from 0 to 90 degrees in planes with azimuthal angles 0 and 45 degrees (by
+// symmetry this covers all directions). Measured samples are obtained by simulation of plane waves in periodic domain
+// in plane with zero azimuthal angle. Wave vector should be periodic in domain, so directions and numbers of cells per
+// wavelength of measured samples are slightly different from requested ones.
+pub fn compute_dispersion_table(
+	time_step: f32,
+	cells_per_wavelength: &[f64],
+	num_angles: usize,
+	num_measured_angles: usize,
+	spatial_stencil: SpatialStencil,
+) -> Vec<DispersionSample>
+{
+	let get_angle =
+		|index: usize, count: usize| 0.5 * std::f64::consts::PI * (index as f64) / ((count - 1).max(1) as f64);
+
+	let mut result = Vec::new();
+	for phi in [0.0, 0.25 * std::f64::consts::PI]
+	{
+		for cells in cells_per_wavelength
+		{
+			for angle_index in 0 .. num_angles
+			{
+				let theta = get_angle(angle_index, num_angles);
+				if let Some(phase_velocity) = calculate_phase_velocity(
+					*cells,
+					get_direction(theta, phi),
+					time_step as f64,
+					1.0,
+					spatial_stencil,
+				)
+				{
+					result.push(DispersionSample {
+						spatial_stencil,
+						cells_per_wavelength: *cells,
+						theta,
+						phi,
+						phase_velocity,
+						measured_phase_velocity: None,
+					});
+				}
+			}
+		}
+	}
+
+	let domain_size = 128;
+	let field_size = [domain_size, 1, domain_size];
+	let num_steps = 200;
+	for cells in cells_per_wavelength
+	{
+		for angle_index in 0 .. num_measured_angles
+		{
+			let theta = get_angle(angle_index, num_measured_angles);
+			let mode_numbers = [theta.sin(), theta.cos()].map(|d| (d * (domain_size as f64) / cells).round());
+			if mode_numbers[0] == 0.0 && mode_numbers[1] == 0.0
+			{
+				continue;
+			}
+			let wave_vector = [
+				2.0 * std::f64::consts::PI * mode_numbers[0] / (domain_size as f64),
+				0.0,
+				2.0 * std::f64::consts::PI * mode_numbers[1] / (domain_size as f64),
+			];
+			let wave_number = (wave_vector[0].powi(2) + wave_vector[2].powi(2)).sqrt();
+			let angular_frequency =
+				match calculate_angular_frequency(wave_vector, time_step as f64, 1.0, spatial_stencil)
+				{
+					Some(angular_frequency) => angular_frequency,
+					None => continue,
+				};
+			result.push(DispersionSample {
+				spatial_stencil,
+				cells_per_wavelength: 2.0 * std::f64::consts::PI / wave_number,
+				theta: wave_vector[0].atan2(wave_vector[2]),
+				phi: 0.0,
+				phase_velocity: angular_frequency / wave_number,
+				measured_phase_velocity: Some(measure_phase_velocity(
+					field_size,
+					wave_vector,
+					time_step,
+					num_steps,
+					spatial_stencil,
+				)),
+			});
+		}
+	}
+	result
+}
+
+// Run simulation of sinusoidal plane wave with given wave vector in periodic vacuum domain and measure its phase
+// velocity using phase of spatial Fourier component of electric field.
+pub fn measure_phase_velocity(
+	field_size: [u32; 3],
+	wave_vector: [f64; 3],
+	time_step: f32,
+	num_steps: u32,
+	spatial_stencil: SpatialStencil,
+) -> f64
+{
+	let mut updater = CpuElectromagneticFieldUpdater::new(&Medium::new(field_size), [BoundaryCondition::Periodic; 3]);
+	updater.set_spatial_stencil(spatial_stencil);
+
+	// Electric field is directed along any axis, which is orthogonal to wave vector, magnetic field is directed
+	// along "k x E".
+	let wave_number = wave_vector.iter().map(|k| k * k).sum::<f64>().sqrt();
+	let direction = wave_vector.map(|k| k / wave_number);
+	let electric_axis = (0 .. 3)
+		.min_by(|a, b| direction[*a].abs().partial_cmp(&direction[*b].abs()).unwrap())
+		.unwrap();
+	let mut electric_direction = [0.0; 3];
+	electric_direction[electric_axis] = 1.0;
+	let electric_direction = normalize(sub(
+		electric_direction,
+		scale(direction, dot(direction, electric_direction)),
+	));
+	let magnetic_direction = cross(direction, electric_direction);
+
+	// Use exact numerical frequency for initial magnetic field (which is half step ahead), so that only wave,
+	// propagating along wave vector, is excited.
+	let initial_angular_frequency =
+		calculate_angular_frequency(wave_vector, time_step as f64, 1.0, spatial_stencil).unwrap_or(0.0);
+
+	let mut field = CpuElectromagneticField::new(field_size);
+	let mut phases = Vec::with_capacity(field.electric_field.len());
+	for z in 0 .. field_size[2]
+	{
+		for y in 0 .. field_size[1]
+		{
+			for x in 0 .. field_size[0]
+			{
+				let position = [x, y, z].map(|c| c as f64 + 0.5);
+				phases.push(dot(wave_vector, position));
+			}
+		}
+	}
+	for (address, phase) in phases.iter().enumerate()
+	{
+		let electric_value = phase.cos();
+		let magnetic_value = (phase - initial_angular_frequency * 0.5 * time_step as f64).cos();
+		let electric_vec = scale(electric_direction, electric_value).map(|v| v as f32);
+		let magnetic_vec = scale(magnetic_direction, magnetic_value).map(|v| v as f32);
+		field.electric_field[address] = [electric_vec[0], electric_vec[1], electric_vec[2], 0.0];
+		field.magnetic_field[address] = [magnetic_vec[0], magnetic_vec[1], magnetic_vec[2], 0.0];
+	}
+
+	// Component "exp(i * (k * r - w * t))" of field has phase "-w * t".
+	let get_phase = |field: &CpuElectromagneticField| {
+		field
+			.electric_field
+			.iter()
+			.zip(phases.iter())
+			.fold(Complex::ZERO, |sum, (e, phase)| {
+				let value = (0 .. 3).map(|i| e[i] as f64 * electric_direction[i]).sum::<f64>();
+				sum + Complex::from_polar(value, -phase)
+			})
+			.arg()
+	};
+
+	let mut total_phase_delta = 0.0;
+	let mut prev_phase = get_phase(&field);
+	let mut time = 0.0;
+	for _step in 0 .. num_steps
+	{
+		updater.update(&mut field, time_step);
+		time += time_step as f64;
+
+		let phase = get_phase(&field);
+		let mut delta = phase - prev_phase;
+		if delta > std::f64::consts::PI
+		{
+			delta -= 2.0 * std::f64::consts::PI;
+		}
+		if delta < -std::f64::consts::PI
+		{
+			delta += 2.0 * std::f64::consts::PI;
+		}
+		total_phase_delta += delta;
+		prev_phase = phase;
+	}
+
+	-total_phase_delta / time / wave_number
+}
+
+pub fn write_dispersion_table(samples: &[DispersionSample], file_path: &std::path::Path) -> std::io::Result<()>
+{
+	let mut file = std::io::BufWriter::new(std::fs::File::create(file_path)?);
+	writeln!(
+		file,
+		"stencil_order,cells_per_wavelength,theta_deg,phi_deg,phase_velocity,phase_velocity_error,\
+		 measured_phase_velocity"
+	)?;
+	for sample in samples
+	{
+		write!(
+			file,
+			"{},{},{},{},{},{},",
+			sample.spatial_stencil.get_order(),
+			sample.cells_per_wavelength,
+			sample.theta.to_degrees(),
+			sample.phi.to_degrees(),
+			sample.phase_velocity,
+			sample.phase_velocity - 1.0
+		)?;
+		if let Some(measured_phase_velocity) = sample.measured_phase_velocity
+		{
+			write!(file, "{}", measured_phase_velocity)?;
+		}
+		writeln!(file)?;
+	}
+	file.flush()
+}
+
+// Estimate shortest wavelength in scene with given maximum frequency of sources and calculate maximum phase velocity
+// error for it.
+// Wavelength inside materials is calculated using real part of permittivity, since fields in conductors decay
+// instead of propagating. Scalar permittivity and permeability are used for anisotropic materials.
+// For graded mesh both largest cells (with worst resolution) and smallest cells (with largest time step relative to
+// cell size) are checked.
+pub fn analyze_resolution(
+	medium: &Medium,
+	max_frequency: f32,
+	time_step: f32,
+	spatial_stencil: SpatialStencil,
+) -> ResolutionReport
+{
+	let angular_frequency = 2.0 * std::f64::consts::PI * (max_frequency as f64);
+	let mut is_material_used = vec![false; medium.get_materials().len()];
+	for id in medium.get_material_ids()
+	{
+		is_material_used[*id as usize] = true;
+	}
+	let refractive_index = medium
+		.get_materials()
+		.iter()
+		.zip(is_material_used.iter())
+		.filter(|(_material, is_used)| **is_used)
+		.map(|(material, _is_used)| {
+			let mut permittivity = Complex::from_real(material.permittivity as f64);
+			for pole in &material.dispersion_poles
+			{
+				permittivity += get_pole_susceptibility(pole, angular_frequency);
+			}
+			(permittivity.re.max(0.0) * (material.permeability as f64)).sqrt()
+		})
+		.fold(0.0, f64::max)
+		.max(1.0e-3);
+
+	let shortest_wavelength = 1.0 / ((max_frequency as f64) * refractive_index);
+	let grid_spacing = medium.get_grid_spacing();
+	let max_spacing = grid_spacing.get_max_spacing() as f64;
+
+	// Sample directions within symmetry sector.
+	let num_angles = 10;
+	let mut max_phase_velocity_error = Some(0.0f64);
+	for spacing in [max_spacing, grid_spacing.get_min_spacing() as f64]
+	{
+		for theta_index in 0 .. num_angles
+		{
+			for phi_index in 0 .. num_angles
+			{
+				let theta = 0.5 * std::f64::consts::PI * (theta_index as f64) / ((num_angles - 1) as f64);
+				let phi = 0.25 * std::f64::consts::PI * (phi_index as f64) / ((num_angles - 1) as f64);
+				let phase_velocity = calculate_phase_velocity(
+					shortest_wavelength / spacing,
+					get_direction(theta, phi),
+					time_step as f64 / spacing,
+					refractive_index,
+					spatial_stencil,
+				);
+				max_phase_velocity_error = match (max_phase_velocity_error, phase_velocity)
+				{
+					(Some(error), Some(phase_velocity)) => Some(error.max((phase_velocity - 1.0).abs())),
+					_ => None,
+				};
+			}
+		}
+	}
+
+	ResolutionReport {
+		shortest_wavelength: shortest_wavelength / max_spacing,
+		max_phase_velocity_error,
+	}
+}
+
+// Print warning if shortest wavelength of scene is under-resolved.
+pub fn warn_if_under_resolved(medium: &Medium, max_frequency: f32, time_step: f32, spatial_stencil: SpatialStencil)
+{
+	let report = analyze_resolution(medium, max_frequency, time_step, spatial_stencil);
+	if !report.is_under_resolved()
+	{
+		return;
+	}
+	match report.max_phase_velocity_error
+	{
+		Some(error) => println!(
+			"Warning: shortest wavelength ({:.2} cells) is under-resolved, phase velocity error is up to {:.2}%",
+			report.shortest_wavelength,
+			error * 100.0
+		),
+		None => println!(
+			"Warning: shortest wavelength ({:.2} cells) is too short for grid and time step",
+			report.shortest_wavelength
+		),
+	}
+}
+
+// Get effective wave number "s(k)" of central difference stencil for given wave vector component.
+fn get_stencil_wave_number(wave_number: f64, spatial_stencil: SpatialStencil) -> f64
+{
+	match spatial_stencil
+	{
+		SpatialStencil::SecondOrder => wave_number.sin(),
+		SpatialStencil::FourthOrder => (8.0 * wave_number.sin() - (2.0 * wave_number).sin()) / 6.0,
+	}
+}
+
+// Get wave vector component, at which effective wave number of stencil reaches maximum. Shorter waves propagate
+// backwards, so they can't be represented on grid.
+fn get_max_stencil_wave_number(spatial_stencil: SpatialStencil) -> f64
+{
+	match spatial_stencil
+	{
+		SpatialStencil::SecondOrder => 0.5 * std::f64::consts::PI,
+		// Root of "8 * cos(k) - 2 * cos(2 * k) = 0".
+		SpatialStencil::FourthOrder => (1.0 - 0.5 * 6.0f64.sqrt()).acos(),
+	}
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64
+{
+	a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3]
+{
+	[
+		a[1] * b[2] - a[2] * b[1],
+		a[2] * b[0] - a[0] * b[2],
+		a[0] * b[1] - a[1] * b[0],
+	]
+}
+
+fn sub(a: [f64; 3], b: [f64; 3]) -> [f64; 3]
+{
+	[a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn scale(a: [f64; 3], s: f64) -> [f64; 3]
+{
+	a.map(|v| v * s)
+}
+
+fn normalize(a: [f64; 3]) -> [f64; 3]
+{
+	scale(a, 1.0 / dot(a, a).sqrt())
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn fourth_order_dispersion_error()
+	{
+		// Fourth-order stencil should have much smaller phase velocity error than second-order one, both in dispersion
+		// relation and in simulation.
+		let time_step = 0.5;
+		let domain_size = 64;
+		for cells_per_wavelength in [8, 16]
+		{
+			let wave_number = 2.0 * std::f64::consts::PI / (cells_per_wavelength as f64);
+			let [second_order_error, fourth_order_error] =
+				[SpatialStencil::SecondOrder, SpatialStencil::FourthOrder].map(|spatial_stencil| {
+					let measured_velocity = measure_phase_velocity(
+						[1, 1, domain_size],
+						[0.0, 0.0, wave_number],
+						time_step,
+						200,
+						spatial_stencil,
+					);
+					let analytic_velocity = calculate_phase_velocity(
+						cells_per_wavelength as f64,
+						[0.0, 0.0, 1.0],
+						time_step as f64,
+						1.0,
+						spatial_stencil,
+					)
+					.unwrap();
+					assert!((measured_velocity - analytic_velocity).abs() < 1.0e-3);
+					(measured_velocity - 1.0).abs()
+				});
+			assert!(
+				fourth_order_error < 0.1 * second_order_error,
+				"{} cells per wavelength: second-order error {}, fourth-order error {}",
+				cells_per_wavelength,
+				second_order_error,
+				fourth_order_error
+			);
+		}
+	}
+}
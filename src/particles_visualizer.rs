@@ -0,0 +1,106 @@
+use super::{charged_particles::*, grid_spacing::*, math_types::*, medium::*, ogl_common::*};
+
+// Draws charged particles as points, colored by charge sign.
+// Particle positions are given in grid units, so they are converted into length units using grid spacing.
+pub struct ParticlesVisualizer
+{
+	program: glium::Program,
+}
+
+impl ParticlesVisualizer
+{
+	pub fn new(display: &glium::Display) -> Self
+	{
+		let program = glium::Program::from_source(
+			display,
+			&[VERTEX_SHADER_HEADER, SHADER_GRID_SPACING, VERTEX_SHADER].concat(),
+			FRAGMENT_SHADER,
+			None,
+		)
+		.unwrap();
+		Self { program }
+	}
+
+	pub fn visualize<S: glium::Surface>(
+		&self,
+		surface: &mut S,
+		particles: &ChargedParticles,
+		medium_buffers: &MediumBuffers,
+		view_matrix: &Mat4f,
+	)
+	{
+		if particles.get_num_particles() == 0
+		{
+			return;
+		}
+
+		let uniforms = glium::uniform! {
+			view_matrix: make_uniform_matrix(view_matrix),
+			field_size: medium_buffers.get_size(),
+			grid_spacing_data: medium_buffers.get_grid_spacing_buffer(),
+			particles_data: particles.get_particles_buffer(),
+		};
+
+		surface
+			.draw(
+				glium::vertex::EmptyVertexAttributes {
+					len: particles.get_num_particles() as usize,
+				},
+				glium::index::IndicesSource::NoIndices {
+					primitives: glium::index::PrimitiveType::Points,
+				},
+				&self.program,
+				&uniforms,
+				&glium::DrawParameters {
+					point_size: Some(POINT_SIZE),
+					..get_default_drawing_params()
+				},
+			)
+			.unwrap();
+	}
+}
+
+const POINT_SIZE: f32 = 3.0;
+
+const VERTEX_SHADER_HEADER: &str = r#"
+	#version 430
+
+	uniform uvec3 field_size;
+"#;
+
+const VERTEX_SHADER: &str = r#"
+	uniform mat4 view_matrix;
+
+	layout(std430) buffer particles_data
+	{
+		vec4 particles[];
+	};
+
+	out vec3 f_color;
+
+	void main()
+	{
+		vec4 position_and_charge = particles[gl_VertexID * 2];
+		float mass = particles[gl_VertexID * 2 + 1].w;
+		if(mass <= 0.0)
+		{
+			// Move deactivated particles outside view volume.
+			gl_Position = vec4(2.0, 2.0, 2.0, 1.0);
+		}
+		else
+		{
+			gl_Position = vec4(get_grid_position(position_and_charge.xyz), 1.0) * view_matrix;
+		}
+		f_color = position_and_charge.w >= 0.0 ? vec3(0.9, 0.5, 0.1) : vec3(0.1, 0.9, 0.9);
+	}
+"#;
+
+const FRAGMENT_SHADER: &str = r#"
+	#version 430
+	in vec3 f_color;
+	out vec4 color;
+	void main()
+	{
+		color = vec4(f_color, 1.0);
+	}
+"#;
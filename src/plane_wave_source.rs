@@ -0,0 +1,39 @@
+use super::{grid_spacing::*, math_types::*, waveform::*};
+
+// Plane wave, injected via total-field/scattered-field boundary.
+// Inside given box field is total (incident + scattered), outside it only scattered field exists.
+// Box should be placed inside vacuum.
+#[derive(Clone)]
+pub struct PlaneWaveSource
+{
+	// Total field region is [box_min; box_max).
+	pub box_min: [u32; 3],
+	pub box_max: [u32; 3],
+	// Propagation direction, should be normalized.
+	pub direction: Vec3f,
+	// Electric field direction, should be normalized and perpendicular to propagation direction.
+	pub polarization: Vec3f,
+	pub amplitude: f32,
+	pub waveform: Waveform,
+}
+
+impl PlaneWaveSource
+{
+	// Get point of the box (in length units) where wave starts entering the box at zero time.
+	pub fn get_reference_point(&self, grid_spacing: &GridSpacing) -> Vec3f
+	{
+		let mut result = Vec3f::zero();
+		for i in 0 .. 3
+		{
+			result[i] = if self.direction[i] >= 0.0
+			{
+				grid_spacing.get_boundary(i, self.box_min[i])
+			}
+			else
+			{
+				grid_spacing.get_boundary(i, self.box_max[i])
+			};
+		}
+		result
+	}
+}
@@ -0,0 +1,308 @@
+use super::scalar_grid::*;
+
+// Solve Poisson equation "laplacian(u) = -f" with zero value outside grid using multigrid method.
+// Cells with fixed values (like conductors) are kept unchanged.
+pub fn solve_poisson(f: &ScalarGrid, fixed_values: &[Option<f32>], grid_step: f32) -> ScalarGrid
+{
+	let size = f.get_size();
+	assert_eq!(fixed_values.len(), f.get_values().len());
+
+	let levels = build_levels(size, fixed_values.iter().map(|v| v.is_some()).collect());
+
+	// Equation is scaled by square of grid step - "6 * u - sum(neighbors) = grid_step^2 * f".
+	let scale = grid_step * grid_step;
+	let mut rhs = f.get_values().iter().map(|v| v * scale).collect::<Vec<_>>();
+	let mut result = ScalarGrid::new(size);
+	for ((dst, rhs_value), fixed_value) in result
+		.get_values_mut()
+		.iter_mut()
+		.zip(rhs.iter_mut())
+		.zip(fixed_values.iter())
+	{
+		if let Some(v) = fixed_value
+		{
+			*dst = *v;
+			*rhs_value = 0.0;
+		}
+	}
+
+	let initial_residual_norm = calculate_residual_norm(&levels[0], result.get_values(), &rhs);
+	if initial_residual_norm == 0.0
+	{
+		return result;
+	}
+
+	for _cycle in 0 .. MAX_V_CYCLES
+	{
+		run_v_cycle(&levels, 0, result.get_values_mut(), &rhs);
+		if calculate_residual_norm(&levels[0], result.get_values(), &rhs) <= initial_residual_norm * TOLERANCE
+		{
+			break;
+		}
+	}
+
+	result
+}
+
+// Solve Poisson equation with Laplacian, composed of central differences (with stride of two cells).
+// Such Laplacian couples only cells with the same coordinates parity, so separate Poisson problems are solved for
+// each of eight subgrids with doubled grid step.
+pub fn solve_central_difference_poisson(f: &ScalarGrid, fixed_values: &[Option<f32>]) -> ScalarGrid
+{
+	let size = f.get_size();
+	assert_eq!(fixed_values.len(), f.get_values().len());
+
+	let mut result = ScalarGrid::new(size);
+	for parity in 0 .. 8
+	{
+		let offset = [parity & 1, (parity >> 1) & 1, (parity >> 2) & 1];
+		if (0 .. 3).any(|i| offset[i] >= size[i])
+		{
+			continue;
+		}
+		let subgrid_size = [
+			(size[0] - offset[0]).div_ceil(2),
+			(size[1] - offset[1]).div_ceil(2),
+			(size[2] - offset[2]).div_ceil(2),
+		];
+		let get_address = |x, y, z| f.get_address(x * 2 + offset[0], y * 2 + offset[1], z * 2 + offset[2]);
+
+		let mut subgrid_f = ScalarGrid::new(subgrid_size);
+		let mut subgrid_fixed_values = vec![None; subgrid_f.get_values().len()];
+		for z in 0 .. subgrid_size[2]
+		{
+			for y in 0 .. subgrid_size[1]
+			{
+				for x in 0 .. subgrid_size[0]
+				{
+					let address = get_address(x, y, z);
+					let subgrid_address = subgrid_f.get_address(x, y, z);
+					subgrid_f.get_values_mut()[subgrid_address] = f.get_values()[address];
+					subgrid_fixed_values[subgrid_address] = fixed_values[address];
+				}
+			}
+		}
+
+		let subgrid_result = solve_poisson(&subgrid_f, &subgrid_fixed_values, 2.0);
+
+		for z in 0 .. subgrid_size[2]
+		{
+			for y in 0 .. subgrid_size[1]
+			{
+				for x in 0 .. subgrid_size[0]
+				{
+					result.get_values_mut()[get_address(x, y, z)] =
+						subgrid_result.get_values()[subgrid_result.get_address(x, y, z)];
+				}
+			}
+		}
+	}
+
+	result
+}
+
+const MAX_V_CYCLES: usize = 100;
+const TOLERANCE: f32 = 1.0e-6;
+const NUM_SMOOTHING_ITERATIONS: usize = 3;
+const NUM_COARSEST_LEVEL_ITERATIONS: usize = 64;
+
+struct Level
+{
+	size: [u32; 3],
+	fixed: Vec<bool>,
+}
+
+fn build_levels(size: [u32; 3], fixed: Vec<bool>) -> Vec<Level>
+{
+	let mut levels = vec![Level { size, fixed }];
+	loop
+	{
+		let fine = levels.last().unwrap();
+		if fine.size.iter().any(|s| *s <= 2)
+		{
+			break;
+		}
+
+		let coarse_size = [
+			fine.size[0].div_ceil(2),
+			fine.size[1].div_ceil(2),
+			fine.size[2].div_ceil(2),
+		];
+		// Coarse cell is fixed if any of its children is fixed.
+		let mut coarse_fixed = vec![false; (coarse_size[0] * coarse_size[1] * coarse_size[2]) as usize];
+		for z in 0 .. fine.size[2]
+		{
+			for y in 0 .. fine.size[1]
+			{
+				for x in 0 .. fine.size[0]
+				{
+					if fine.fixed[get_address(fine.size, x, y, z)]
+					{
+						coarse_fixed[get_address(coarse_size, x / 2, y / 2, z / 2)] = true;
+					}
+				}
+			}
+		}
+		levels.push(Level {
+			size: coarse_size,
+			fixed: coarse_fixed,
+		});
+	}
+	levels
+}
+
+fn run_v_cycle(levels: &[Level], level_index: usize, values: &mut [f32], rhs: &[f32])
+{
+	let level = &levels[level_index];
+	if level_index + 1 == levels.len()
+	{
+		for _i in 0 .. NUM_COARSEST_LEVEL_ITERATIONS
+		{
+			smooth(level, values, rhs);
+		}
+		return;
+	}
+
+	for _i in 0 .. NUM_SMOOTHING_ITERATIONS
+	{
+		smooth(level, values, rhs);
+	}
+
+	// Restrict residual by averaging.
+	// Coarse grid step is two times greater, which gives scale 4 for right-hand side, but for piecewise-constant
+	// prolongation Galerkin coarse operator is half of rediscretized operator, so use scale 2.
+	let coarse_level = &levels[level_index + 1];
+	let residual = calculate_residual(level, values, rhs);
+	let mut coarse_rhs = vec![0.0; coarse_level.fixed.len()];
+	for z in 0 .. level.size[2]
+	{
+		for y in 0 .. level.size[1]
+		{
+			for x in 0 .. level.size[0]
+			{
+				let coarse_address = get_address(coarse_level.size, x / 2, y / 2, z / 2);
+				coarse_rhs[coarse_address] += residual[get_address(level.size, x, y, z)] * (2.0 / 8.0);
+			}
+		}
+	}
+	for (v, fixed) in coarse_rhs.iter_mut().zip(coarse_level.fixed.iter())
+	{
+		if *fixed
+		{
+			*v = 0.0;
+		}
+	}
+
+	let mut correction = vec![0.0; coarse_level.fixed.len()];
+	run_v_cycle(levels, level_index + 1, &mut correction, &coarse_rhs);
+
+	// Prolongate correction using piecewise-constant interpolation.
+	for z in 0 .. level.size[2]
+	{
+		for y in 0 .. level.size[1]
+		{
+			for x in 0 .. level.size[0]
+			{
+				let address = get_address(level.size, x, y, z);
+				if !level.fixed[address]
+				{
+					values[address] += correction[get_address(coarse_level.size, x / 2, y / 2, z / 2)];
+				}
+			}
+		}
+	}
+
+	for _i in 0 .. NUM_SMOOTHING_ITERATIONS
+	{
+		smooth(level, values, rhs);
+	}
+}
+
+// Perform red-black Gauss-Seidel iteration.
+fn smooth(level: &Level, values: &mut [f32], rhs: &[f32])
+{
+	for color in 0 .. 2
+	{
+		for z in 0 .. level.size[2]
+		{
+			for y in 0 .. level.size[1]
+			{
+				let x_start = (color + y + z) % 2;
+				for x in (x_start .. level.size[0]).step_by(2)
+				{
+					let address = get_address(level.size, x, y, z);
+					if !level.fixed[address]
+					{
+						values[address] = (rhs[address] + get_neighbors_sum(level.size, values, x, y, z)) / 6.0;
+					}
+				}
+			}
+		}
+	}
+}
+
+fn calculate_residual(level: &Level, values: &[f32], rhs: &[f32]) -> Vec<f32>
+{
+	let mut residual = vec![0.0; values.len()];
+	for z in 0 .. level.size[2]
+	{
+		for y in 0 .. level.size[1]
+		{
+			for x in 0 .. level.size[0]
+			{
+				let address = get_address(level.size, x, y, z);
+				if !level.fixed[address]
+				{
+					residual[address] =
+						rhs[address] - (6.0 * values[address] - get_neighbors_sum(level.size, values, x, y, z));
+				}
+			}
+		}
+	}
+	residual
+}
+
+fn calculate_residual_norm(level: &Level, values: &[f32], rhs: &[f32]) -> f32
+{
+	calculate_residual(level, values, rhs)
+		.iter()
+		.map(|r| r * r)
+		.sum::<f32>()
+		.sqrt()
+}
+
+// Sum of values in six neighbor cells. Values outside grid are zero.
+fn get_neighbors_sum(size: [u32; 3], values: &[f32], x: u32, y: u32, z: u32) -> f32
+{
+	let mut sum = 0.0;
+	if x > 0
+	{
+		sum += values[get_address(size, x - 1, y, z)];
+	}
+	if x + 1 < size[0]
+	{
+		sum += values[get_address(size, x + 1, y, z)];
+	}
+	if y > 0
+	{
+		sum += values[get_address(size, x, y - 1, z)];
+	}
+	if y + 1 < size[1]
+	{
+		sum += values[get_address(size, x, y + 1, z)];
+	}
+	if z > 0
+	{
+		sum += values[get_address(size, x, y, z - 1)];
+	}
+	if z + 1 < size[2]
+	{
+		sum += values[get_address(size, x, y, z + 1)];
+	}
+	sum
+}
+
+fn get_address(size: [u32; 3], x: u32, y: u32, z: u32) -> usize
+{
+	(x + y * size[0] + z * (size[0] * size[1])) as usize
+}
@@ -0,0 +1,133 @@
+use super::{math_types::*, near_to_far_field::*, ogl_common::*};
+
+// Draws 3D radiation pattern as a lobe mesh, where distance from center is proportional to directivity.
+pub struct RadiationPatternVisualizer
+{
+	program: glium::Program,
+	mesh: Option<(glium::VertexBuffer<Vertex>, glium::IndexBuffer<u32>)>,
+}
+
+impl RadiationPatternVisualizer
+{
+	pub fn new(display: &glium::Display) -> Self
+	{
+		let program = glium::Program::from_source(display, VERTEX_SHADER, FRAGMENT_SHADER, None).unwrap();
+		Self { program, mesh: None }
+	}
+
+	// Build mesh of given pattern. Mesh has unit radius in direction of maximum directivity.
+	pub fn set_pattern(&mut self, display: &glium::Display, pattern: &RadiationPattern)
+	{
+		let max_directivity = pattern.get_max_directivity().max(1.0e-30);
+
+		let mut vertices = Vec::with_capacity(pattern.samples.len());
+		for (theta_index, theta) in pattern.thetas.iter().enumerate()
+		{
+			for (phi_index, phi) in pattern.phis.iter().enumerate()
+			{
+				let value = pattern.get_directivity(pattern.get_sample_index(theta_index, phi_index)) / max_directivity;
+				let (sin_theta, cos_theta) = theta.sin_cos();
+				let (sin_phi, cos_phi) = phi.sin_cos();
+				vertices.push(Vertex {
+					position: [
+						(value * sin_theta * cos_phi) as f32,
+						(value * sin_theta * sin_phi) as f32,
+						(value * cos_theta) as f32,
+					],
+					value: value as f32,
+				});
+			}
+		}
+
+		let num_phi = pattern.get_num_phi() as u32;
+		let mut indices = Vec::with_capacity(pattern.samples.len() * 6);
+		for theta_index in 0 .. pattern.get_num_theta() as u32 - 1
+		{
+			for phi_index in 0 .. num_phi
+			{
+				let next_phi_index = (phi_index + 1) % num_phi;
+				let v0 = theta_index * num_phi + phi_index;
+				let v1 = theta_index * num_phi + next_phi_index;
+				let v2 = v0 + num_phi;
+				let v3 = v1 + num_phi;
+				indices.extend_from_slice(&[v0, v2, v3, v0, v3, v1]);
+			}
+		}
+
+		self.mesh = Some((
+			glium::VertexBuffer::new(display, &vertices).unwrap(),
+			glium::IndexBuffer::new(display, glium::index::PrimitiveType::TrianglesList, &indices).unwrap(),
+		));
+	}
+
+	// Draw pattern with given center and radius (in world space).
+	pub fn visualize<S: glium::Surface>(&self, surface: &mut S, view_matrix: &Mat4f, center: Vec3f, radius: f32)
+	{
+		let (vertex_buffer, index_buffer) = if let Some(mesh) = &self.mesh
+		{
+			mesh
+		}
+		else
+		{
+			return;
+		};
+
+		let matrix = view_matrix * Mat4f::from_translation(center) * Mat4f::from_scale(radius);
+
+		let uniforms = glium::uniform! {
+			matrix: make_uniform_matrix(&matrix)
+		};
+
+		surface
+			.draw(
+				vertex_buffer,
+				index_buffer,
+				&self.program,
+				&uniforms,
+				&glium::DrawParameters {
+					polygon_mode: glium::draw_parameters::PolygonMode::Line,
+					..get_default_drawing_params()
+				},
+			)
+			.unwrap();
+	}
+}
+
+#[derive(Copy, Clone)]
+struct Vertex
+{
+	position: [f32; 3],
+	// Normalized directivity.
+	value: f32,
+}
+
+glium::implement_vertex!(Vertex, position, value);
+
+const VERTEX_SHADER: &str = r#"
+	#version 430
+
+	uniform mat4 matrix;
+
+	in vec3 position;
+	in float value;
+
+	out vec3 f_color;
+
+	void main()
+	{
+		gl_Position = vec4(position, 1.0) * matrix;
+		f_color = mix(vec3(0.1, 0.2, 0.8), vec3(0.9, 0.8, 0.1), value);
+	}
+"#;
+
+const FRAGMENT_SHADER: &str = r#"
+	#version 430
+
+	in vec3 f_color;
+	out vec4 color;
+
+	void main()
+	{
+		color = vec4(f_color, 1.0);
+	}
+"#;
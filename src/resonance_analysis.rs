@@ -0,0 +1,147 @@
+use super::{
+	electromagnetic_field::*, electromagnetic_field_updater::*, field_probe::*, harmonic_inversion::*, math_types::*,
+	medium::*, vector_field::*,
+};
+use std::io::Write;
+
+// Setup for search of resonant modes of some structure.
+// Field is excited by initial Gaussian pulse of electric field, signal is recorded with point probe.
+pub struct ResonatorSetup
+{
+	pub field_size: [u32; 3],
+	pub medium: Medium,
+	pub boundary_conditions: [BoundaryCondition; 3],
+	pub spatial_stencil: SpatialStencil,
+	pub excitation_position: Vec3f,
+	pub excitation_width: f32,
+	pub excitation_direction: Vec3f,
+	pub probe_position: [u32; 3],
+	// Component of electric field used as signal.
+	pub probe_component: usize,
+	pub time_step: f32,
+	pub num_steps: u32,
+	// Number of initial steps, ignored for analysis (while excitation pulse is still present).
+	pub num_skipped_steps: u32,
+	pub harmonic_inversion_params: HarmonicInversionParams,
+}
+
+pub fn compute_resonant_modes(display: &glium::Display, setup: &ResonatorSetup) -> Vec<ResonantMode>
+{
+	let mut field = create_excitation_field(display, setup);
+	let mut updater = ElectromagneticFieldUpdater::new(display, &setup.medium, setup.boundary_conditions);
+	updater.set_spatial_stencil(setup.spatial_stencil);
+	let mut probe = FieldProbe::new(display, setup.probe_position);
+
+	let mut time = 0.0;
+	for step in 0 .. setup.num_steps
+	{
+		updater.update(&mut field, time, setup.time_step);
+		time += setup.time_step;
+		if step >= setup.num_skipped_steps
+		{
+			probe.record(&field, time);
+		}
+	}
+	probe.flush();
+
+	let signal = probe
+		.get_samples()
+		.iter()
+		.map(|s| s.electric[setup.probe_component])
+		.collect::<Vec<_>>();
+
+	extract_resonant_modes(&signal, setup.time_step, &setup.harmonic_inversion_params)
+}
+
+pub fn write_resonant_modes_table(modes: &[ResonantMode], file_path: &std::path::Path) -> std::io::Result<()>
+{
+	let mut file = std::io::BufWriter::new(std::fs::File::create(file_path)?);
+	writeln!(file, "frequency,decay_rate,quality_factor,amplitude,phase")?;
+	for mode in modes
+	{
+		writeln!(
+			file,
+			"{},{},{},{},{}",
+			mode.frequency, mode.decay_rate, mode.quality_factor, mode.amplitude, mode.phase
+		)?;
+	}
+	file.flush()
+}
+
+// Dielectric cube with high permittivity inside open (absorbing) domain.
+pub fn create_test_dielectric_resonator_setup(spatial_stencil: SpatialStencil) -> ResonatorSetup
+{
+	let field_size = [64, 64, 64];
+	let resonator_size = 16;
+
+	let mut medium = Medium::new(field_size);
+	let dielectric_material_id = medium.add_material(Material::dielectric(9.0));
+	let resonator_min = [
+		(field_size[0] - resonator_size) / 2,
+		(field_size[1] - resonator_size) / 2,
+		(field_size[2] - resonator_size) / 2,
+	];
+	medium.fill_box(
+		resonator_min,
+		[
+			resonator_min[0] + resonator_size,
+			resonator_min[1] + resonator_size,
+			resonator_min[2] + resonator_size,
+		],
+		dielectric_material_id,
+	);
+
+	let center = Vec3f::new(field_size[0] as f32, field_size[1] as f32, field_size[2] as f32) * 0.5;
+
+	ResonatorSetup {
+		field_size,
+		medium,
+		boundary_conditions: [BoundaryCondition::Absorbing { width: 12 }; 3],
+		spatial_stencil,
+		// Use asymmetric excitation in order to excite more modes.
+		excitation_position: center + Vec3f::new(3.0, 2.0, 1.0),
+		excitation_width: 2.0,
+		excitation_direction: Vec3f::new(0.0, 0.0, 1.0),
+		probe_position: [
+			(center.x - 4.0) as u32,
+			(center.y + 3.0) as u32,
+			(center.z - 2.0) as u32,
+		],
+		probe_component: 2,
+		time_step: 0.5,
+		num_steps: 8000,
+		num_skipped_steps: 200,
+		harmonic_inversion_params: HarmonicInversionParams {
+			frequency_min: 0.01,
+			frequency_max: 0.1,
+			max_modes: 16,
+			min_relative_amplitude: 0.01,
+		},
+	}
+}
+
+fn create_excitation_field(display: &glium::Display, setup: &ResonatorSetup) -> ElectromagneticField
+{
+	let size = setup.field_size;
+	let mut electric_data = vec![[0.0; 4]; (size[0] * size[1] * size[2]) as usize];
+
+	let inv_width2 = 1.0 / (setup.excitation_width * setup.excitation_width);
+	for z in 0 .. size[2]
+	{
+		for y in 0 .. size[1]
+		{
+			for x in 0 .. size[0]
+			{
+				let position = Vec3f::new(x as f32 + 0.5, y as f32 + 0.5, z as f32 + 0.5);
+				let scale = (-0.5 * (position - setup.excitation_position).magnitude2() * inv_width2).exp();
+				let address = (x + y * size[0] + z * (size[0] * size[1])) as usize;
+				electric_data[address] = (setup.excitation_direction * scale).extend(0.0).into();
+			}
+		}
+	}
+
+	ElectromagneticField {
+		electric_field: VectorField::new_with_data(display, size, &electric_data),
+		magnetic_field: VectorField::new(display, size),
+	}
+}
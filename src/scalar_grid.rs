@@ -0,0 +1,65 @@
+// Scalar values in cell centers, stored on CPU side.
+#[derive(Clone)]
+pub struct ScalarGrid
+{
+	size: [u32; 3],
+	values: Vec<f32>,
+}
+
+impl ScalarGrid
+{
+	// Create grid filled with zeros.
+	pub fn new(size: [u32; 3]) -> Self
+	{
+		Self {
+			size,
+			values: vec![0.0; (size[0] * size[1] * size[2]) as usize],
+		}
+	}
+
+	pub fn get_size(&self) -> [u32; 3]
+	{
+		self.size
+	}
+
+	pub fn get_values(&self) -> &[f32]
+	{
+		&self.values
+	}
+
+	pub fn get_values_mut(&mut self) -> &mut [f32]
+	{
+		&mut self.values
+	}
+
+	// Get value with given coordinates. Coordinates outside grid are allowed - zero is returned for them.
+	pub fn get(&self, x: i32, y: i32, z: i32) -> f32
+	{
+		if !self.is_inside(x, y, z)
+		{
+			return 0.0;
+		}
+		self.values[self.get_address(x as u32, y as u32, z as u32)]
+	}
+
+	// Add value to cell with given coordinates. Coordinates outside grid are ignored.
+	pub fn add(&mut self, x: i32, y: i32, z: i32, value: f32)
+	{
+		if !self.is_inside(x, y, z)
+		{
+			return;
+		}
+		let address = self.get_address(x as u32, y as u32, z as u32);
+		self.values[address] += value;
+	}
+
+	pub fn is_inside(&self, x: i32, y: i32, z: i32) -> bool
+	{
+		x >= 0 && y >= 0 && z >= 0 && x < self.size[0] as i32 && y < self.size[1] as i32 && z < self.size[2] as i32
+	}
+
+	pub fn get_address(&self, x: u32, y: u32, z: u32) -> usize
+	{
+		(x + y * self.size[0] + z * (self.size[0] * self.size[1])) as usize
+	}
+}
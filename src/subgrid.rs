@@ -0,0 +1,865 @@
+use super::{
+	electromagnetic_field::*, electromagnetic_field_updater::*, grid_spacing::*, lumped_element::*, math_types::*,
+	medium::*, thin_wire::*, vector_field::*,
+};
+
+// Parameters of locally refined subgrid. Region [region_min; region_max) of coarse grid is covered by fine grid, where
+// each coarse cell is split into "refinement" cells along each axis.
+//
+// Coupling of grids: outer layer of coarse cells of region (interface layer) is updated on coarse grid, coarse cells
+// inside it (covered cells) - on fine grid. Coarse update of interface cells uses average of adjacent fine cells
+// instead of covered neighbor, and fine cells of interface layer are set to field of coarse cell, which contains them.
+// These links are adjoint to each other, so energy is conserved at interface and there are no late-time instabilities
+// of interpolation-based coupling.
+// Fine grid is subcycled: both electric and magnetic fields of it are updated "refinement" times per coarse step with
+// proportionally smaller time step, so stability limit of time step is the same as for coarse grid. Step is symmetric
+// in time (see "update_subgrid"), which keeps subcycling stable.
+// After step field of covered cells is replaced by average of fine cells.
+// Along axes, where region covers whole field, there is no interface - fine grid uses boundary condition of coarse
+// grid instead.
+// Coupling is derived for central differences over neighbor cells, so if fourth-order stencil is enabled, coarse
+// updater should use second-order one in region (see "ElectromagneticFieldUpdater::set_second_order_box").
+//
+// Fine features (materials, lumped elements, thin wires) should be placed inside of interface layer, plane wave source
+// box and absorbing layers shouldn't cross region.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct SubgridParams
+{
+	pub region_min: [u32; 3],
+	pub region_max: [u32; 3],
+	pub refinement: u32,
+}
+
+impl SubgridParams
+{
+	pub fn get_region_size(&self) -> [u32; 3]
+	{
+		[0, 1, 2].map(|i| self.region_max[i] - self.region_min[i])
+	}
+
+	pub fn get_fine_size(&self) -> [u32; 3]
+	{
+		self.get_region_size().map(|s| s * self.refinement)
+	}
+
+	// Axes with interface between grids.
+	pub fn get_coupled_axes(&self, coarse_size: [u32; 3]) -> [bool; 3]
+	{
+		[0, 1, 2].map(|i| self.region_min[i] > 0 || self.region_max[i] < coarse_size[i])
+	}
+
+	pub fn validate(&self, coarse_size: [u32; 3])
+	{
+		assert!(self.refinement >= 2, "subgrid refinement should be at least 2");
+		let coupled_axes = self.get_coupled_axes(coarse_size);
+		for i in 0 .. 3
+		{
+			assert!(self.region_min[i] < self.region_max[i] && self.region_max[i] <= coarse_size[i]);
+			// Interface layers on both sides and at least one restricted cell between them.
+			assert!(
+				!coupled_axes[i] || self.region_max[i] - self.region_min[i] >= 3,
+				"subgrid region is too small"
+			);
+		}
+	}
+
+	// Boundary conditions of fine grid. Border cells of coupled axes are overwritten, so their condition doesn't
+	// matter.
+	pub fn get_fine_boundary_conditions(
+		&self,
+		coarse_size: [u32; 3],
+		coarse_boundary_conditions: [BoundaryCondition; 3],
+	) -> [BoundaryCondition; 3]
+	{
+		let coupled_axes = self.get_coupled_axes(coarse_size);
+		[0, 1, 2].map(|i| {
+			if coupled_axes[i]
+			{
+				if let BoundaryCondition::Absorbing { width } = coarse_boundary_conditions[i]
+				{
+					assert!(
+						self.region_min[i] >= width && self.region_max[i] + width <= coarse_size[i],
+						"subgrid region shouldn't overlap absorbing layer"
+					);
+				}
+				return BoundaryCondition::Reflecting;
+			}
+			match coarse_boundary_conditions[i]
+			{
+				BoundaryCondition::Absorbing { width } => BoundaryCondition::Absorbing {
+					width: width * self.refinement,
+				},
+				boundary_condition => boundary_condition,
+			}
+		})
+	}
+
+	// Covered cells are coarse cells of region inside interface layer, their field is restricted from fine grid.
+	pub fn is_covered(&self, coarse_size: [u32; 3], coord: [u32; 3]) -> bool
+	{
+		let coupled_axes = self.get_coupled_axes(coarse_size);
+		(0 .. 3).all(|i| {
+			let (min, max) = if coupled_axes[i]
+			{
+				(self.region_min[i] + 1, self.region_max[i] - 1)
+			}
+			else
+			{
+				(self.region_min[i], self.region_max[i])
+			};
+			coord[i] >= min && coord[i] < max
+		})
+	}
+
+	// Check, that spatial stencil of coarse updater is compatible with coupling at interface: fourth-order stencil is
+	// allowed only if region is inside of second-order box of updater.
+	pub fn check_coarse_spatial_stencil(
+		&self,
+		spatial_stencil: SpatialStencil,
+		second_order_box: Option<([u32; 3], [u32; 3])>,
+	)
+	{
+		assert!(
+			spatial_stencil == SpatialStencil::SecondOrder ||
+				second_order_box.is_some_and(|(box_min, box_max)| {
+					(0 .. 3).all(|i| box_min[i] <= self.region_min[i] && box_max[i] >= self.region_max[i])
+				}),
+			"coarse updater should use second-order stencil in subgrid region"
+		);
+	}
+
+	// Fine cell, which contains center of given coarse cell of region. For even refinement center is on border of fine
+	// cells, upper one is chosen.
+	pub fn get_fine_cell(&self, coarse_coord: [u32; 3]) -> [u32; 3]
+	{
+		[0, 1, 2].map(|i| (coarse_coord[i] - self.region_min[i]) * self.refinement + self.refinement / 2)
+	}
+
+	// Position of lower corner of fine grid in length units of coarse grid. Fine grid positions start from zero, so
+	// this offset should be subtracted from positions of fine features and added to fine grid for visualization.
+	pub fn get_fine_origin(&self, coarse_grid_spacing: &GridSpacing) -> Vec3f
+	{
+		Vec3f::new(
+			coarse_grid_spacing.get_boundary(0, self.region_min[0]),
+			coarse_grid_spacing.get_boundary(1, self.region_min[1]),
+			coarse_grid_spacing.get_boundary(2, self.region_min[2]),
+		)
+	}
+
+	// Create medium of fine grid. Each coarse cell of region is split into cells of equal size with the same material.
+	// Fine features should be added to result.
+	pub fn make_fine_medium(&self, coarse_medium: &Medium) -> Medium
+	{
+		self.validate(coarse_medium.get_size());
+
+		let fine_size = self.get_fine_size();
+		let coarse_grid_spacing = coarse_medium.get_grid_spacing();
+		let r = self.refinement;
+
+		let mut result = Medium::new(fine_size);
+		result.set_grid_spacing(GridSpacing::new([0, 1, 2].map(|axis| {
+			(0 .. fine_size[axis])
+				.map(|i| coarse_grid_spacing.get_spacing(axis, self.region_min[axis] + i / r) / (r as f32))
+				.collect()
+		})));
+		for material in &coarse_medium.get_materials()[1 ..]
+		{
+			result.add_material(material.clone());
+		}
+
+		let coarse_size = coarse_medium.get_size();
+		let region_size = self.get_region_size();
+		for z in 0 .. region_size[2]
+		{
+			for y in 0 .. region_size[1]
+			{
+				for x in 0 .. region_size[0]
+				{
+					let coord = [x + self.region_min[0], y + self.region_min[1], z + self.region_min[2]];
+					let address =
+						(coord[0] + coord[1] * coarse_size[0] + coord[2] * coarse_size[0] * coarse_size[1]) as usize;
+					let material_id = coarse_medium.get_material_ids()[address];
+					if material_id != Medium::VACUUM_MATERIAL_ID
+					{
+						let fine_min = [x * r, y * r, z * r];
+						result.fill_box(fine_min, fine_min.map(|c| c + r), material_id);
+					}
+				}
+			}
+		}
+
+		result
+	}
+
+	// Two values for each cell of region: (permittivity, permeability, conductivity, magnetic conductivity) of coarse
+	// material and (cell size along each axis, unused). Interface cells are partially updated by subgrid, so only
+	// materials without dispersion, nonlinearity and anisotropy are supported there.
+	pub fn make_interface_data(&self, coarse_medium: &Medium) -> Vec<[f32; 4]>
+	{
+		let coarse_size = coarse_medium.get_size();
+		let grid_spacing = coarse_medium.get_grid_spacing();
+		let region_size = self.get_region_size();
+		let mut result = Vec::with_capacity((region_size[0] * region_size[1] * region_size[2] * 2) as usize);
+		for z in 0 .. region_size[2]
+		{
+			for y in 0 .. region_size[1]
+			{
+				for x in 0 .. region_size[0]
+				{
+					let coord = [x + self.region_min[0], y + self.region_min[1], z + self.region_min[2]];
+					let address =
+						(coord[0] + coord[1] * coarse_size[0] + coord[2] * coarse_size[0] * coarse_size[1]) as usize;
+					let material = &coarse_medium.get_materials()[coarse_medium.get_material_ids()[address] as usize];
+					result.push([
+						material.permittivity,
+						material.permeability,
+						material.conductivity,
+						material.magnetic_conductivity,
+					]);
+					result.push([
+						grid_spacing.get_spacing(0, coord[0]),
+						grid_spacing.get_spacing(1, coord[1]),
+						grid_spacing.get_spacing(2, coord[2]),
+						0.0,
+					]);
+				}
+			}
+		}
+		result
+	}
+}
+
+// Fine field, used by correction of interface cells.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SubgridFineField
+{
+	// Magnetic field, which corrects electric field of interface cells.
+	Magnetic,
+	// Current and stored (see "SubgridOperations::store_previous_fields") electric field, which corrects magnetic
+	// field of interface cells.
+	Electric,
+	PreviousElectric,
+}
+
+// Operations of subgrid update step, implemented by GPU and CPU subgrids. Coupling scheme is defined by
+// "update_subgrid" in terms of them, so both implementations perform the same sequence of updates.
+pub trait SubgridOperations
+{
+	// Update electric field of coarse grid. "time" is time of electric field before update.
+	fn update_coarse_electric_field(&mut self, time: f32, time_delta_s: f32);
+	// Update magnetic field of coarse grid. "electric_time" is time of electric field, which is used by update.
+	fn update_coarse_magnetic_field(&mut self, electric_time: f32, time_delta_s: f32);
+	fn update_fine_electric_field(&mut self, time: f32, time_delta_s: f32);
+	fn update_fine_magnetic_field(&mut self, electric_time: f32, time_delta_s: f32);
+	// Add correction of interface cells for given fine field, multiplied by weight, to coarse field.
+	fn correct_interface_field(&mut self, source: SubgridFineField, time_delta_s: f32, weight: f32);
+	// Store coarse magnetic field of region and fine electric field.
+	fn store_previous_fields(&mut self);
+	// Set fine cells of interface layer to coarse electric field and to average of stored and current coarse magnetic
+	// field.
+	fn set_border_fields(&mut self);
+	// Set field of covered cells to average of fine cells.
+	fn restrict_covered_cells(&mut self);
+}
+
+// Perform coarse update step and corresponding fine substeps. "time" is time of coarse electric field before update.
+//
+// Coarse grid starts with electric field at time "n" and magnetic field at "n + 1/2", fine grid - with both fields at
+// "n + 1/2". Step is composed of symmetric parts:
+// - coarse electric field is updated to "n + 1" with fine magnetic field at "n + 1/2";
+// - coarse magnetic field is updated by half step to "n + 1" with fine electric field at "n + 1/2";
+// - fine grid is advanced to "n + 3/2" by "refinement" leapfrog substeps, which start and end with half update of
+//   magnetic field, coarse field of interface layer at "n + 1" is used as fixed border;
+// - coarse magnetic field is updated by second half step to "n + 3/2" with fine electric field at "n + 3/2".
+// Both half steps of coarse magnetic field are done by single coarse update, only correction of interface cells is
+// split.
+pub fn update_subgrid<O: SubgridOperations>(operations: &mut O, refinement: u32, time: f32, time_delta_s: f32)
+{
+	let substep_time_delta_s = time_delta_s / (refinement as f32);
+
+	operations.update_coarse_electric_field(time, time_delta_s);
+	operations.correct_interface_field(SubgridFineField::Magnetic, time_delta_s, 1.0);
+
+	operations.store_previous_fields();
+	operations.update_coarse_magnetic_field(time + time_delta_s, time_delta_s);
+	operations.correct_interface_field(SubgridFineField::Electric, time_delta_s, 1.0);
+
+	let mut electric_time = time + 0.5 * time_delta_s;
+	// Fine update overwrites border, so it's set before each update.
+	operations.set_border_fields();
+	operations.update_fine_magnetic_field(electric_time, 0.5 * substep_time_delta_s);
+	for step in 0 .. refinement
+	{
+		operations.set_border_fields();
+		operations.update_fine_electric_field(electric_time, substep_time_delta_s);
+		electric_time += substep_time_delta_s;
+
+		let magnetic_time_delta_s = if step + 1 < refinement
+		{
+			substep_time_delta_s
+		}
+		else
+		{
+			0.5 * substep_time_delta_s
+		};
+		operations.set_border_fields();
+		operations.update_fine_magnetic_field(electric_time, magnetic_time_delta_s);
+	}
+	operations.set_border_fields();
+
+	// Replace half of correction by fine electric field at "n + 1/2" with correction by field at "n + 3/2".
+	operations.correct_interface_field(SubgridFineField::Electric, time_delta_s, 0.5);
+	operations.correct_interface_field(SubgridFineField::PreviousElectric, time_delta_s, -0.5);
+
+	operations.restrict_covered_cells();
+}
+
+pub struct Subgrid
+{
+	params: SubgridParams,
+	coarse_size: [u32; 3],
+	// Two values for each cell of region, see "SubgridParams::make_interface_data".
+	interface_data_buffer: glium::buffer::Buffer<[[f32; 4]]>,
+	fine_field: ElectromagneticField,
+	fine_updater: ElectromagneticFieldUpdater,
+	// Coarse magnetic field of region and fine electric field before coarse magnetic field update.
+	previous_magnetic_field: VectorField,
+	previous_fine_electric_field: VectorField,
+	shader_correct_interface_field: glium::program::ComputeShader,
+	shader_store_previous_magnetic_field: glium::program::ComputeShader,
+	shader_set_border: glium::program::ComputeShader,
+	shader_restrict: glium::program::ComputeShader,
+}
+
+impl Subgrid
+{
+	// Fine medium should be created with "SubgridParams::make_fine_medium".
+	pub fn new(
+		display: &glium::Display,
+		params: SubgridParams,
+		coarse_medium: &Medium,
+		coarse_boundary_conditions: [BoundaryCondition; 3],
+		fine_medium: &Medium,
+	) -> Self
+	{
+		let coarse_size = coarse_medium.get_size();
+		params.validate(coarse_size);
+		let fine_size = params.get_fine_size();
+		assert_eq!(fine_medium.get_size(), fine_size);
+
+		Self {
+			params,
+			coarse_size,
+			interface_data_buffer: glium::buffer::Buffer::new(
+				display,
+				&params.make_interface_data(coarse_medium)[..],
+				glium::buffer::BufferType::ShaderStorageBuffer,
+				glium::buffer::BufferMode::Default,
+			)
+			.unwrap(),
+			fine_field: ElectromagneticField {
+				electric_field: VectorField::new(display, fine_size),
+				magnetic_field: VectorField::new(display, fine_size),
+			},
+			fine_updater: ElectromagneticFieldUpdater::new(
+				display,
+				fine_medium,
+				params.get_fine_boundary_conditions(coarse_size, coarse_boundary_conditions),
+			),
+			previous_magnetic_field: VectorField::new(display, params.get_region_size()),
+			previous_fine_electric_field: VectorField::new(display, fine_size),
+			shader_correct_interface_field: glium::program::ComputeShader::from_source(
+				display,
+				&[SHADER_COMMON, SHADER_FACE_AVERAGE, SHADER_CORRECT_INTERFACE_FIELD].concat(),
+			)
+			.unwrap(),
+			shader_store_previous_magnetic_field: glium::program::ComputeShader::from_source(
+				display,
+				&[SHADER_COMMON, SHADER_STORE_PREVIOUS_MAGNETIC_FIELD].concat(),
+			)
+			.unwrap(),
+			shader_set_border: glium::program::ComputeShader::from_source(
+				display,
+				&[SHADER_COMMON, SHADER_SET_BORDER].concat(),
+			)
+			.unwrap(),
+			shader_restrict: glium::program::ComputeShader::from_source(
+				display,
+				&[SHADER_COMMON, SHADER_RESTRICT].concat(),
+			)
+			.unwrap(),
+		}
+	}
+
+	pub fn get_params(&self) -> &SubgridParams
+	{
+		&self.params
+	}
+
+	pub fn get_fine_field(&self) -> &ElectromagneticField
+	{
+		&self.fine_field
+	}
+
+	pub fn get_fine_updater(&self) -> &ElectromagneticFieldUpdater
+	{
+		&self.fine_updater
+	}
+
+	// Set lumped elements of fine grid, their positions are given in fine cells (see "SubgridParams::get_fine_cell").
+	pub fn set_fine_lumped_elements(&mut self, display: &glium::Display, lumped_elements: Vec<LumpedElement>)
+	{
+		self.fine_updater.set_lumped_elements(display, lumped_elements);
+	}
+
+	// Set thin wires of fine grid, their positions are given in fine cells.
+	pub fn set_fine_thin_wires(&mut self, display: &glium::Display, thin_wires: &[ThinWire])
+	{
+		self.fine_updater.set_thin_wires(display, thin_wires);
+	}
+
+	// Perform coarse update step with given updater and external current density of coarse grid, which should be given
+	// at half step, and corresponding fine substeps. "time" is time of electric field before update.
+	pub fn update_with_current_density(
+		&mut self,
+		field: &mut ElectromagneticField,
+		updater: &ElectromagneticFieldUpdater,
+		current_density: Option<&VectorField>,
+		time: f32,
+		time_delta_s: f32,
+	)
+	{
+		assert_eq!(field.electric_field.get_size(), self.coarse_size);
+		self.params.check_coarse_spatial_stencil(updater.get_spatial_stencil(), updater.get_second_order_box());
+
+		let refinement = self.params.refinement;
+		let mut operations = SubgridUpdate {
+			subgrid: self,
+			field,
+			updater,
+			current_density,
+		};
+		update_subgrid(&mut operations, refinement, time, time_delta_s);
+	}
+
+	// Coarse update uses covered cells as neighbors of interface cells, replace them by fine cells of adjacent face.
+	fn correct_interface_field(
+		&self,
+		field: &ElectromagneticField,
+		source: SubgridFineField,
+		time_delta_s: f32,
+		weight: f32,
+	)
+	{
+		let fine_data = match source
+		{
+			SubgridFineField::Electric => &self.fine_field.electric_field,
+			SubgridFineField::PreviousElectric => &self.previous_fine_electric_field,
+			SubgridFineField::Magnetic => &self.fine_field.magnetic_field,
+		};
+		let region_size = self.params.get_region_size();
+		let uniforms = glium::uniform! {
+			dt: time_delta_s,
+			weight: weight,
+			correct_magnetic_field: source != SubgridFineField::Magnetic,
+			field_size: self.coarse_size,
+			region_min: self.params.region_min,
+			refinement: self.params.refinement,
+			fine_size: self.params.get_fine_size(),
+			coupled_axes: self.params.get_coupled_axes(self.coarse_size).map(u32::from),
+			electric_field_data: field.electric_field.get_buffer(),
+			magnetic_field_data: field.magnetic_field.get_buffer(),
+			fine_field_data: fine_data.get_buffer(),
+			interface_data: &self.interface_data_buffer,
+		};
+		self.shader_correct_interface_field
+			.execute(uniforms, region_size[0], region_size[1], region_size[2]);
+	}
+
+	fn store_previous_fields(&self, field: &ElectromagneticField)
+	{
+		let region_size = self.params.get_region_size();
+		let uniforms = glium::uniform! {
+			field_size: self.coarse_size,
+			region_min: self.params.region_min,
+			refinement: self.params.refinement,
+			fine_size: self.params.get_fine_size(),
+			coupled_axes: self.params.get_coupled_axes(self.coarse_size).map(u32::from),
+			magnetic_field_data: field.magnetic_field.get_buffer(),
+			previous_magnetic_field_data: self.previous_magnetic_field.get_buffer(),
+		};
+		self.shader_store_previous_magnetic_field
+			.execute(uniforms, region_size[0], region_size[1], region_size[2]);
+
+		self.fine_field
+			.electric_field
+			.get_buffer()
+			.copy_to(self.previous_fine_electric_field.get_buffer())
+			.unwrap();
+	}
+
+	// Set fine cells of interface layer to field of coarse cell, which contains them: electric field after step and
+	// magnetic field at the same time, which is average of magnetic field before and after step.
+	// Only fine cells adjacent to covered cells are used by fine update, other ones are set for visualization.
+	fn set_border_fields(&self, field: &ElectromagneticField)
+	{
+		let fine_size = self.params.get_fine_size();
+		let uniforms = glium::uniform! {
+			field_size: self.coarse_size,
+			region_min: self.params.region_min,
+			refinement: self.params.refinement,
+			fine_size: fine_size,
+			coupled_axes: self.params.get_coupled_axes(self.coarse_size).map(u32::from),
+			electric_field_data: field.electric_field.get_buffer(),
+			magnetic_field_data: field.magnetic_field.get_buffer(),
+			previous_magnetic_field_data: self.previous_magnetic_field.get_buffer(),
+			fine_electric_field_data: self.fine_field.electric_field.get_buffer(),
+			fine_magnetic_field_data: self.fine_field.magnetic_field.get_buffer(),
+		};
+		self.shader_set_border
+			.execute(uniforms, fine_size[0], fine_size[1], fine_size[2]);
+	}
+
+	fn restrict_covered_cells(&self, field: &ElectromagneticField)
+	{
+		let region_size = self.params.get_region_size();
+		let uniforms = glium::uniform! {
+			field_size: self.coarse_size,
+			region_min: self.params.region_min,
+			refinement: self.params.refinement,
+			fine_size: self.params.get_fine_size(),
+			coupled_axes: self.params.get_coupled_axes(self.coarse_size).map(u32::from),
+			electric_field_data: field.electric_field.get_buffer(),
+			magnetic_field_data: field.magnetic_field.get_buffer(),
+			fine_electric_field_data: self.fine_field.electric_field.get_buffer(),
+			fine_magnetic_field_data: self.fine_field.magnetic_field.get_buffer(),
+		};
+		self.shader_restrict
+			.execute(uniforms, region_size[0], region_size[1], region_size[2]);
+	}
+}
+
+// Operations of single update step, performed on coarse field with given updater.
+struct SubgridUpdate<'a>
+{
+	subgrid: &'a mut Subgrid,
+	field: &'a mut ElectromagneticField,
+	updater: &'a ElectromagneticFieldUpdater,
+	current_density: Option<&'a VectorField>,
+}
+
+// Updater considers time of magnetic field update to be time of electric field before whole step.
+impl SubgridOperations for SubgridUpdate<'_>
+{
+	fn update_coarse_electric_field(&mut self, time: f32, time_delta_s: f32)
+	{
+		self.updater
+			.update_electric_field(self.field, self.current_density, time, time_delta_s);
+	}
+
+	fn update_coarse_magnetic_field(&mut self, electric_time: f32, time_delta_s: f32)
+	{
+		self.updater
+			.update_magnetic_field(self.field, electric_time - time_delta_s, time_delta_s);
+	}
+
+	fn update_fine_electric_field(&mut self, time: f32, time_delta_s: f32)
+	{
+		self.subgrid
+			.fine_updater
+			.update_electric_field(&mut self.subgrid.fine_field, None, time, time_delta_s);
+	}
+
+	fn update_fine_magnetic_field(&mut self, electric_time: f32, time_delta_s: f32)
+	{
+		self.subgrid.fine_updater.update_magnetic_field(
+			&mut self.subgrid.fine_field,
+			electric_time - time_delta_s,
+			time_delta_s,
+		);
+	}
+
+	fn correct_interface_field(&mut self, source: SubgridFineField, time_delta_s: f32, weight: f32)
+	{
+		self.subgrid
+			.correct_interface_field(self.field, source, time_delta_s, weight);
+	}
+
+	fn store_previous_fields(&mut self)
+	{
+		self.subgrid.store_previous_fields(self.field);
+	}
+
+	fn set_border_fields(&mut self)
+	{
+		self.subgrid.set_border_fields(self.field);
+	}
+
+	fn restrict_covered_cells(&mut self)
+	{
+		self.subgrid.restrict_covered_cells(self.field);
+	}
+}
+
+// Common part of shaders - region parameters and helper functions.
+const SHADER_COMMON: &str = r#"
+	#version 430
+	layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
+
+	uniform uvec3 field_size;
+	uniform uvec3 region_min;
+	uniform uint refinement;
+	uniform uvec3 fine_size;
+	uniform uvec3 coupled_axes;
+
+	uint get_address(uvec3 size, uvec3 coord)
+	{
+		return coord.x + coord.y * size.x + coord.z * (size.x * size.y);
+	}
+
+	bool is_covered(uvec3 region_coord)
+	{
+		uvec3 region_size = fine_size / refinement;
+		for (int i = 0; i < 3; i++)
+		{
+			if (coupled_axes[i] != 0 && (region_coord[i] == 0 || region_coord[i] + 1 >= region_size[i]))
+			{
+				return false;
+			}
+		}
+		return true;
+	}
+
+	// Interface cell is cell of interface layer with covered neighbor. Returns axis and direction of this neighbor,
+	// axis is negative for other cells.
+	ivec2 get_covered_neighbor(uvec3 region_coord)
+	{
+		uvec3 region_size = fine_size / refinement;
+		for (int axis = 0; axis < 3; axis++)
+		{
+			if (coupled_axes[axis] == 0)
+			{
+				continue;
+			}
+			uvec3 neighbor_coord = region_coord;
+			if (region_coord[axis] == 0)
+			{
+				neighbor_coord[axis] += 1;
+				if (is_covered(neighbor_coord))
+				{
+					return ivec2(axis, 1);
+				}
+			}
+			else if (region_coord[axis] + 1 == region_size[axis])
+			{
+				neighbor_coord[axis] -= 1;
+				if (is_covered(neighbor_coord))
+				{
+					return ivec2(axis, -1);
+				}
+			}
+		}
+		return ivec2(-1, 0);
+	}
+"#;
+
+const SHADER_FACE_AVERAGE: &str = r#"
+	layout(std430) buffer fine_field_data
+	{
+		vec4 fine_vecs[];
+	};
+
+	// Average of fine cells of covered cell, adjacent to given interface cell.
+	vec3 get_face_average(uvec3 region_coord, int axis, int side)
+	{
+		vec3 sum = vec3(0.0);
+		for (uint j = 0; j < refinement; j++)
+		{
+			for (uint i = 0; i < refinement; i++)
+			{
+				uvec3 fine_coord;
+				fine_coord[axis] = side > 0 ? refinement : fine_size[axis] - refinement - 1;
+				fine_coord[(axis + 1) % 3] = region_coord[(axis + 1) % 3] * refinement + i;
+				fine_coord[(axis + 2) % 3] = region_coord[(axis + 2) % 3] * refinement + j;
+				sum += fine_vecs[get_address(fine_size, fine_coord)].xyz;
+			}
+		}
+		return sum / float(refinement * refinement);
+	}
+"#;
+
+// Coarse update uses covered cells as neighbors of interface cells, replace them by fine cells of adjacent face.
+// Coarse update uses covered cells as neighbors of interface cells, replace them by fine cells of adjacent face.
+const SHADER_CORRECT_INTERFACE_FIELD: &str = r#"
+	uniform float dt;
+	uniform float weight;
+	uniform bool correct_magnetic_field;
+	layout(std430) buffer electric_field_data
+	{
+		vec4 electric_vecs[];
+	};
+	layout(std430) buffer magnetic_field_data
+	{
+		vec4 magnetic_vecs[];
+	};
+	layout(std430) buffer interface_data
+	{
+		vec4 interface_vecs[];
+	};
+
+	void main()
+	{
+		uvec3 region_coord = gl_GlobalInvocationID;
+		ivec2 neighbor = get_covered_neighbor(region_coord);
+		if (neighbor.x < 0)
+		{
+			return;
+		}
+		int axis = neighbor.x;
+		int side = neighbor.y;
+
+		uint region_address = get_address(fine_size / refinement, region_coord);
+		uvec3 coord = region_coord + region_min;
+		uint address = get_address(field_size, coord);
+
+		uvec3 covered_coord = coord;
+		covered_coord[axis] = uint(int(coord[axis]) + side);
+		uint covered_address = get_address(field_size, covered_coord);
+		vec3 covered = correct_magnetic_field ? electric_vecs[covered_address].xyz : magnetic_vecs[covered_address].xyz;
+		vec3 face_average = get_face_average(region_coord, axis, side);
+
+		vec4 material = interface_vecs[region_address * 2];
+		float spacing = interface_vecs[region_address * 2 + 1][axis];
+		// Electric field is updated by curl of magnetic field, magnetic field - by negative curl of electric field.
+		float inverse_factor = correct_magnetic_field ?
+			-1.0 / (material.y + 0.5 * material.w * dt) :
+			1.0 / (material.x + 0.5 * material.z * dt);
+
+		vec3 direction = vec3(0.0);
+		direction[axis] = 1.0;
+		vec3 derivative_delta = (face_average - covered) * (float(side) * 0.5 / spacing);
+		vec3 delta = cross(direction, derivative_delta) * (dt * inverse_factor * weight);
+		if (correct_magnetic_field)
+		{
+			magnetic_vecs[address].xyz += delta;
+		}
+		else
+		{
+			electric_vecs[address].xyz += delta;
+		}
+	}
+"#;
+
+const SHADER_STORE_PREVIOUS_MAGNETIC_FIELD: &str = r#"
+	layout(std430) buffer magnetic_field_data
+	{
+		vec4 magnetic_vecs[];
+	};
+	layout(std430) buffer previous_magnetic_field_data
+	{
+		vec4 previous_magnetic_vecs[];
+	};
+
+	void main()
+	{
+		uvec3 region_coord = gl_GlobalInvocationID;
+		previous_magnetic_vecs[get_address(fine_size / refinement, region_coord)] =
+			magnetic_vecs[get_address(field_size, region_coord + region_min)];
+	}
+"#;
+
+const SHADER_SET_BORDER: &str = r#"
+	layout(std430) buffer electric_field_data
+	{
+		vec4 electric_vecs[];
+	};
+	layout(std430) buffer magnetic_field_data
+	{
+		vec4 magnetic_vecs[];
+	};
+	layout(std430) buffer previous_magnetic_field_data
+	{
+		vec4 previous_magnetic_vecs[];
+	};
+	layout(std430) buffer fine_electric_field_data
+	{
+		vec4 fine_electric_vecs[];
+	};
+	layout(std430) buffer fine_magnetic_field_data
+	{
+		vec4 fine_magnetic_vecs[];
+	};
+
+	void main()
+	{
+		uvec3 fine_coord = gl_GlobalInvocationID;
+		bool is_border = false;
+		for (int i = 0; i < 3; i++)
+		{
+			if (coupled_axes[i] != 0 && (fine_coord[i] < refinement || fine_coord[i] >= fine_size[i] - refinement))
+			{
+				is_border = true;
+			}
+		}
+		if (!is_border)
+		{
+			return;
+		}
+
+		uvec3 region_coord = fine_coord / refinement;
+		uint address = get_address(field_size, region_coord + region_min);
+		uint fine_address = get_address(fine_size, fine_coord);
+		fine_electric_vecs[fine_address] = electric_vecs[address];
+		fine_magnetic_vecs[fine_address] =
+			0.5 * (previous_magnetic_vecs[get_address(fine_size / refinement, region_coord)] + magnetic_vecs[address]);
+	}
+"#;
+
+// Covered cells are set to average of fine cells.
+const SHADER_RESTRICT: &str = r#"
+	layout(std430) buffer electric_field_data
+	{
+		vec4 electric_vecs[];
+	};
+	layout(std430) buffer magnetic_field_data
+	{
+		vec4 magnetic_vecs[];
+	};
+	layout(std430) buffer fine_electric_field_data
+	{
+		vec4 fine_electric_vecs[];
+	};
+	layout(std430) buffer fine_magnetic_field_data
+	{
+		vec4 fine_magnetic_vecs[];
+	};
+
+	void main()
+	{
+		uvec3 region_coord = gl_GlobalInvocationID;
+		if (!is_covered(region_coord))
+		{
+			return;
+		}
+
+		vec4 electric_sum = vec4(0.0);
+		vec4 magnetic_sum = vec4(0.0);
+		for (uint z = 0; z < refinement; z++)
+		{
+			for (uint y = 0; y < refinement; y++)
+			{
+				for (uint x = 0; x < refinement; x++)
+				{
+					uint fine_address = get_address(fine_size, region_coord * refinement + uvec3(x, y, z));
+					electric_sum += fine_electric_vecs[fine_address];
+					magnetic_sum += fine_magnetic_vecs[fine_address];
+				}
+			}
+		}
+
+		float scale = 1.0 / float(refinement * refinement * refinement);
+		uint address = get_address(field_size, region_coord + region_min);
+		electric_vecs[address] = electric_sum * scale;
+		magnetic_vecs[address] = magnetic_sum * scale;
+	}
+"#;
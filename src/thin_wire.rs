@@ -0,0 +1,110 @@
+// Perfectly conducting wire, which is thinner than a cell.
+// Wire goes through cell centers, segments between given points are replaced with axis-aligned staircase.
+// Tangential electric field is zeroed on wire, magnetic field update in cells near wire is corrected using
+// assumption that near field of wire is like field of infinite line current.
+#[derive(Clone)]
+pub struct ThinWire
+{
+	pub points: Vec<[u32; 3]>,
+	// Radius in cells, should be less than one cell. On graded mesh cells around wire should have equal sizes across
+	// wire, radius is relative to this size.
+	pub radius: f32,
+}
+
+// Per-cell data of thin wires for update shaders.
+pub struct ThinWireCells
+{
+	// Index of cell data plus one for each cell, zero if cell is not affected by wires.
+	pub cell_ids: Vec<u32>,
+	// Four values for each affected cell - scales of derivatives along X, Y, Z axes (for each field component) used
+	// in magnetic field update and mask of electric field components.
+	pub cells_data: Vec<[f32; 4]>,
+}
+
+pub fn build_thin_wire_cells(field_size: [u32; 3], wires: &[ThinWire]) -> ThinWireCells
+{
+	let mut result = ThinWireCells {
+		cell_ids: vec![0; (field_size[0] * field_size[1] * field_size[2]) as usize],
+		cells_data: Vec::new(),
+	};
+
+	for wire in wires
+	{
+		assert!(wire.radius > 0.0 && wire.radius < 1.0);
+		// Central difference of electric field across wire uses value on wire (which is zero) and value at distance
+		// of two cells. For field "ln(r / radius)" derivative at distance of one cell is underestimated by factor
+		// "ln(2 / radius) / 2".
+		let derivative_scale = 2.0 / (2.0 / wire.radius).ln();
+
+		for (position, axis) in rasterize_wire(&wire.points)
+		{
+			if !is_inside(field_size, position)
+			{
+				continue;
+			}
+			let data = get_cell_data(&mut result, field_size, position);
+			data[3][axis] = 0.0;
+
+			for direction in 0 .. 3
+			{
+				if direction == axis
+				{
+					continue;
+				}
+				for offset in [-1, 1]
+				{
+					let mut neighbor_position = position;
+					neighbor_position[direction] += offset;
+					if is_inside(field_size, neighbor_position)
+					{
+						get_cell_data(&mut result, field_size, neighbor_position)[direction][axis] = derivative_scale;
+					}
+				}
+			}
+		}
+	}
+
+	result
+}
+
+// Get cells along wire and axes of wire in these cells.
+fn rasterize_wire(points: &[[u32; 3]]) -> Vec<([i32; 3], usize)>
+{
+	let mut result = Vec::new();
+	for segment in points.windows(2)
+	{
+		let mut position = segment[0].map(|c| c as i32);
+		let end = segment[1].map(|c| c as i32);
+		for axis in 0 .. 3
+		{
+			let step = (end[axis] - position[axis]).signum();
+			while position[axis] != end[axis]
+			{
+				// Wire between two cell centers affects both cells.
+				result.push((position, axis));
+				position[axis] += step;
+				result.push((position, axis));
+			}
+		}
+	}
+	result
+}
+
+fn get_cell_data(cells: &mut ThinWireCells, field_size: [u32; 3], position: [i32; 3]) -> &mut [[f32; 4]]
+{
+	let address = (position[0] as u32 +
+		position[1] as u32 * field_size[0] +
+		position[2] as u32 * (field_size[0] * field_size[1])) as usize;
+	if cells.cell_ids[address] == 0
+	{
+		cells.cells_data.extend_from_slice(&[[1.0; 4]; 4]);
+		cells.cell_ids[address] = (cells.cells_data.len() / 4) as u32;
+	}
+	let offset = (cells.cell_ids[address] as usize - 1) * 4;
+	&mut cells.cells_data[offset .. offset + 4]
+}
+
+fn is_inside(field_size: [u32; 3], position: [i32; 3]) -> bool
+{
+	(0 .. 3).all(|i| position[i] >= 0 && position[i] < field_size[i] as i32)
+}
@@ -0,0 +1,95 @@
+// Draws time series as line plots on top of the screen.
+pub struct TimeSeriesVisualizer
+{
+	program: glium::Program,
+	values_buffer: glium::buffer::Buffer<[f32]>,
+}
+
+impl TimeSeriesVisualizer
+{
+	pub fn new(display: &glium::Display) -> Self
+	{
+		let program = glium::Program::from_source(display, VERTEX_SHADER, FRAGMENT_SHADER, None).unwrap();
+		let values_buffer = glium::buffer::Buffer::empty_array(
+			display,
+			glium::buffer::BufferType::ShaderStorageBuffer,
+			MAX_VALUES,
+			glium::buffer::BufferMode::Dynamic,
+		)
+		.unwrap();
+
+		Self { program, values_buffer }
+	}
+
+	// Draw given values inside rectangle (in normalized device coordinates - x, y, width, height).
+	// Values are scaled with given scale, only last values are drawn if there are too many of them.
+	pub fn visualize<S: glium::Surface>(
+		&self,
+		surface: &mut S,
+		values: &[f32],
+		value_scale: f32,
+		rect: [f32; 4],
+		color: [f32; 3],
+	)
+	{
+		let values = &values[values.len().saturating_sub(MAX_VALUES) ..];
+		if values.len() < 2
+		{
+			return;
+		}
+
+		self.values_buffer.slice(0 .. values.len()).unwrap().write(values);
+
+		let uniforms = glium::uniform! {
+			values_count: values.len() as u32,
+			value_scale: value_scale,
+			rect: rect,
+			color: color,
+			values_data: &self.values_buffer,
+		};
+
+		surface
+			.draw(
+				glium::vertex::EmptyVertexAttributes { len: values.len() },
+				glium::index::IndicesSource::NoIndices {
+					primitives: glium::index::PrimitiveType::LineStrip,
+				},
+				&self.program,
+				&uniforms,
+				&Default::default(),
+			)
+			.unwrap();
+	}
+}
+
+const MAX_VALUES: usize = 1024;
+
+const VERTEX_SHADER: &str = r#"
+	#version 430
+
+	uniform uint values_count;
+	uniform float value_scale;
+	uniform vec4 rect;
+
+	layout(std430) buffer values_data
+	{
+		float values[];
+	};
+
+	void main()
+	{
+		float x = float(gl_VertexID) / float(values_count - 1);
+		float y = clamp(values[gl_VertexID] * value_scale, 0.0, 1.0);
+		gl_Position = vec4(rect.xy + rect.zw * vec2(x, y), 0.0, 1.0);
+	}
+"#;
+
+const FRAGMENT_SHADER: &str = r#"
+	#version 430
+	uniform vec3 color;
+	out vec4 f_color;
+	void main()
+	{
+		f_color = vec4(color, 1.0);
+	}
+"#;
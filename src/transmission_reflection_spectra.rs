@@ -0,0 +1,452 @@
+use super::{
+	dft_field_monitor::*, electromagnetic_field::*, electromagnetic_field_updater::*, flux_monitor::*, medium::*,
+	vector_field::*,
+};
+use std::io::Write;
+
+// Setup for calculation of transmission and reflection spectra of a device.
+// Incident wave is a plane pulse, propagating along Y axis with electric field directed along X axis.
+// Boundaries are absorbing along Y axis and periodic along X and Z axes.
+pub struct SpectraSetup
+{
+	pub field_size: [u32; 3],
+	pub pulse: PlanePulseParams,
+	// Y position of plane between pulse source and device.
+	pub reflection_plane_position: u32,
+	// Y position of plane behind the device.
+	pub transmission_plane_position: u32,
+	pub frequencies: Vec<f32>,
+	pub absorbing_layer_width: u32,
+	pub spatial_stencil: SpatialStencil,
+	pub time_step: f32,
+	pub num_steps: u32,
+	pub device_medium: Medium,
+}
+
+#[derive(Copy, Clone)]
+pub struct PlanePulseParams
+{
+	// Initial Y position of pulse center.
+	pub center_position: f32,
+	// Carrier frequency.
+	pub frequency: f32,
+	// Standard deviation of Gaussian envelope (in cells).
+	pub width: f32,
+	pub amplitude: f32,
+}
+
+pub struct Spectra
+{
+	pub frequencies: Vec<f32>,
+	pub transmission: Vec<f32>,
+	pub reflection: Vec<f32>,
+}
+
+// DFT samples of fields on reflection and transmission planes, accumulated during one simulation.
+pub struct PlaneSamples
+{
+	pub reflection: Vec<DftFieldSample>,
+	pub transmission: Vec<DftFieldSample>,
+}
+
+// Flux monitor planes are perpendicular to propagation axis of incident wave.
+const PROPAGATION_AXIS: usize = 1;
+
+// Run automatically reference simulation (with empty domain) and device simulation.
+pub fn compute_transmission_reflection_spectra(display: &glium::Display, setup: &SpectraSetup) -> Spectra
+{
+	let reference_samples = run_simulation(display, setup, &setup.device_medium.make_vacuum_copy());
+	let device_samples = run_simulation(display, setup, &setup.device_medium);
+	calculate_transmission_reflection_spectra(setup, &reference_samples, &device_samples)
+}
+
+// Calculate spectra from samples of reference and device simulations. Incident fields are subtracted in order to get
+// reflected fields.
+pub fn calculate_transmission_reflection_spectra(
+	setup: &SpectraSetup,
+	reference_samples: &PlaneSamples,
+	device_samples: &PlaneSamples,
+) -> Spectra
+{
+	let num_frequencies = setup.frequencies.len();
+	let reflection_plane = create_flux_plane(setup, setup.reflection_plane_position);
+	let transmission_plane = create_flux_plane(setup, setup.transmission_plane_position);
+
+	let incident_flux = reflection_plane.calculate_flux_spectrum(&reference_samples.reflection, num_frequencies);
+	let transmitted_flux = transmission_plane.calculate_flux_spectrum(&device_samples.transmission, num_frequencies);
+
+	let reflected_samples = subtract_dft_samples(&device_samples.reflection, &reference_samples.reflection);
+	// Reflected wave propagates backwards, so negate its flux.
+	let reflected_flux = reflection_plane
+		.calculate_flux_spectrum(&reflected_samples, num_frequencies)
+		.iter()
+		.map(|f| -f)
+		.collect::<Vec<_>>();
+
+	Spectra {
+		frequencies: setup.frequencies.clone(),
+		transmission: normalize_flux(&transmitted_flux, &incident_flux),
+		reflection: normalize_flux(&reflected_flux, &incident_flux),
+	}
+}
+
+// Calculate spectrum of transmitted wave, normalized by peak of incident wave spectrum.
+// This is useful for nonlinear devices, which generate waves with new frequencies.
+pub fn compute_transmitted_power_spectrum(display: &glium::Display, setup: &SpectraSetup) -> Spectra
+{
+	let reference_samples = run_simulation(display, setup, &setup.device_medium.make_vacuum_copy());
+	let device_samples = run_simulation(display, setup, &setup.device_medium);
+
+	let num_frequencies = setup.frequencies.len();
+	let incident_flux = create_flux_plane(setup, setup.reflection_plane_position)
+		.calculate_flux_spectrum(&reference_samples.reflection, num_frequencies);
+	let max_incident_flux = incident_flux.iter().copied().fold(0.0, f32::max);
+
+	let transmitted_flux = create_flux_plane(setup, setup.transmission_plane_position)
+		.calculate_flux_spectrum(&device_samples.transmission, num_frequencies);
+
+	Spectra {
+		frequencies: setup.frequencies.clone(),
+		transmission: normalize_flux(&transmitted_flux, &vec![max_incident_flux; transmitted_flux.len()]),
+		reflection: Vec::new(),
+	}
+}
+
+pub fn write_spectra_table(spectra: &Spectra, file_path: &std::path::Path) -> std::io::Result<()>
+{
+	let mut file = std::io::BufWriter::new(std::fs::File::create(file_path)?);
+	// Reflection may be absent.
+	if spectra.reflection.is_empty()
+	{
+		writeln!(file, "frequency,transmission")?;
+		for (frequency, transmission) in spectra.frequencies.iter().zip(spectra.transmission.iter())
+		{
+			writeln!(file, "{},{}", frequency, transmission)?;
+		}
+	}
+	else
+	{
+		writeln!(file, "frequency,transmission,reflection")?;
+		for ((frequency, transmission), reflection) in spectra
+			.frequencies
+			.iter()
+			.zip(spectra.transmission.iter())
+			.zip(spectra.reflection.iter())
+		{
+			writeln!(file, "{},{},{}", frequency, transmission, reflection)?;
+		}
+	}
+	file.flush()
+}
+
+// Setup with Bragg mirror - a stack of quarter-wave layers, which reflects waves near its central frequency.
+pub fn create_test_bragg_mirror_setup(spatial_stencil: SpatialStencil) -> SpectraSetup
+{
+	let field_size = [1, 320, 1];
+	let absorbing_layer_width = 32;
+
+	let central_frequency = 1.0 / 24.0;
+	let high_permittivity = 4.0;
+
+	let mut device_medium = Medium::new(field_size);
+	let high_permittivity_material_id = device_medium.add_material(Material::dielectric(high_permittivity));
+
+	// Quarter-wave thicknesses for layers of high and low (vacuum) permittivity.
+	let high_layer_thickness = (0.25 / (central_frequency * high_permittivity.sqrt())).round() as u32;
+	let low_layer_thickness = (0.25 / central_frequency).round() as u32;
+	let num_periods = 6;
+	let device_start = 144;
+	for i in 0 .. num_periods
+	{
+		let layer_start = device_start + i * (high_layer_thickness + low_layer_thickness);
+		device_medium.fill_box(
+			[0, layer_start, 0],
+			[field_size[0], layer_start + high_layer_thickness, field_size[2]],
+			high_permittivity_material_id,
+		);
+	}
+
+	let num_frequencies = 64;
+	let frequencies = (0 .. num_frequencies)
+		.map(|i| central_frequency * 2.0 * ((i + 1) as f32) / (num_frequencies as f32))
+		.collect();
+
+	SpectraSetup {
+		field_size,
+		pulse: PlanePulseParams {
+			center_position: 64.0,
+			frequency: central_frequency,
+			width: 8.0,
+			amplitude: 1.0,
+		},
+		reflection_plane_position: 112,
+		transmission_plane_position: 272,
+		frequencies,
+		absorbing_layer_width,
+		spatial_stencil,
+		time_step: 0.5,
+		num_steps: 8000,
+		device_medium,
+	}
+}
+
+// Setup with slab of nonlinear crystal, which generates second and third harmonics of intense incident pulse.
+pub fn create_test_harmonic_generation_setup(spatial_stencil: SpatialStencil) -> SpectraSetup
+{
+	let field_size = [1, 320, 1];
+	let absorbing_layer_width = 32;
+
+	let fundamental_frequency = 1.0 / 32.0;
+
+	let mut device_medium = Medium::new(field_size);
+	let crystal_material_id = device_medium.add_material(Material {
+		permittivity: 2.0,
+		nonlinearity: Nonlinearity {
+			second_order_susceptibility: 0.1,
+			third_order_susceptibility: 0.05,
+			saturation_field: 0.0,
+		},
+		..Material::VACUUM
+	});
+	device_medium.fill_box([0, 144, 0], [field_size[0], 240, field_size[2]], crystal_material_id);
+
+	let num_frequencies = 128;
+	let frequencies = (0 .. num_frequencies)
+		.map(|i| fundamental_frequency * 4.0 * ((i + 1) as f32) / (num_frequencies as f32))
+		.collect();
+
+	SpectraSetup {
+		field_size,
+		pulse: PlanePulseParams {
+			center_position: 64.0,
+			frequency: fundamental_frequency,
+			width: 12.0,
+			amplitude: 1.0,
+		},
+		reflection_plane_position: 112,
+		transmission_plane_position: 272,
+		frequencies,
+		absorbing_layer_width,
+		spatial_stencil,
+		time_step: 0.5,
+		num_steps: 4000,
+		device_medium,
+	}
+}
+
+fn run_simulation(display: &glium::Display, setup: &SpectraSetup, medium: &Medium) -> PlaneSamples
+{
+	let (electric_data, magnetic_data) = create_plane_wave_pulse_data(setup.field_size, &setup.pulse, setup.time_step);
+	let mut field = ElectromagneticField {
+		electric_field: VectorField::new_with_data(display, setup.field_size, &electric_data),
+		magnetic_field: VectorField::new_with_data(display, setup.field_size, &magnetic_data),
+	};
+
+	let mut updater = ElectromagneticFieldUpdater::new(display, medium, get_boundary_conditions(setup));
+	updater.set_spatial_stencil(setup.spatial_stencil);
+
+	let make_plane_monitor = |position| DftFieldMonitor::new(display, &get_plane_monitor_params(setup, position));
+	let mut reflection_monitor = make_plane_monitor(setup.reflection_plane_position);
+	let mut transmission_monitor = make_plane_monitor(setup.transmission_plane_position);
+
+	let mut time = 0.0;
+	for _i in 0 .. setup.num_steps
+	{
+		updater.update(&mut field, time, setup.time_step);
+		time += setup.time_step;
+
+		reflection_monitor.accumulate(&field, time, setup.time_step);
+		transmission_monitor.accumulate(&field, time, setup.time_step);
+	}
+
+	PlaneSamples {
+		reflection: reflection_monitor.read_samples(),
+		transmission: transmission_monitor.read_samples(),
+	}
+}
+
+fn get_boundary_conditions(setup: &SpectraSetup) -> [BoundaryCondition; 3]
+{
+	[
+		BoundaryCondition::Periodic,
+		BoundaryCondition::Absorbing {
+			width: setup.absorbing_layer_width,
+		},
+		BoundaryCondition::Periodic,
+	]
+}
+
+// Parameters of monitor of plane at given position along propagation axis (see "FluxPlane").
+fn get_plane_monitor_params(setup: &SpectraSetup, position: u32) -> DftFieldMonitorParams
+{
+	let mut region_offset = [0; 3];
+	region_offset[PROPAGATION_AXIS] = position;
+	let mut region_size = setup.field_size;
+	region_size[PROPAGATION_AXIS] = 2;
+	DftFieldMonitorParams {
+		region_offset,
+		region_size,
+		frequencies: setup.frequencies.clone(),
+	}
+}
+
+fn create_flux_plane(setup: &SpectraSetup, position: u32) -> FluxPlane
+{
+	let params = get_plane_monitor_params(setup, position);
+	FluxPlane::new(
+		params.region_offset,
+		params.region_size,
+		PROPAGATION_AXIS,
+		setup.device_medium.get_grid_spacing(),
+	)
+}
+
+// Create data of electric and magnetic fields of Gaussian-windowed plane wave pulse, propagating towards positive Y
+// direction.
+fn create_plane_wave_pulse_data(
+	size: [u32; 3],
+	pulse: &PlanePulseParams,
+	time_step: f32,
+) -> (Vec<[f32; 4]>, Vec<[f32; 4]>)
+{
+	let pulse_value = |y: f32| {
+		let dy = y - pulse.center_position;
+		pulse.amplitude *
+			(-0.5 * dy * dy / (pulse.width * pulse.width)).exp() *
+			(dy * pulse.frequency * (2.0 * std::f32::consts::PI)).sin()
+	};
+
+	let len = (size[0] * size[1] * size[2]) as usize;
+	let mut electric_data = vec![[0.0; 4]; len];
+	let mut magnetic_data = vec![[0.0; 4]; len];
+	for z in 0 .. size[2]
+	{
+		for y in 0 .. size[1]
+		{
+			for x in 0 .. size[0]
+			{
+				let address = (x + y * size[0] + z * (size[0] * size[1])) as usize;
+				electric_data[address][0] = pulse_value(y as f32);
+				// Magnetic field is half time step ahead.
+				magnetic_data[address][2] = -pulse_value(y as f32 - 0.5 * time_step);
+			}
+		}
+	}
+
+	(electric_data, magnetic_data)
+}
+
+fn normalize_flux(flux: &[f32], incident_flux: &[f32]) -> Vec<f32>
+{
+	flux.iter()
+		.zip(incident_flux.iter())
+		.map(|(f, i)| if *i != 0.0 { f / i } else { 0.0 })
+		.collect()
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use crate::cpu_electromagnetic_field_updater::*;
+
+	// Same as "run_simulation", but with CPU field updater and DFT accumulated like in "DftFieldMonitor".
+	fn run_cpu_simulation(setup: &SpectraSetup, medium: &Medium) -> PlaneSamples
+	{
+		let mut field = CpuElectromagneticField::new(setup.field_size);
+		(field.electric_field, field.magnetic_field) =
+			create_plane_wave_pulse_data(setup.field_size, &setup.pulse, setup.time_step);
+
+		let mut updater = CpuElectromagneticFieldUpdater::new(medium, get_boundary_conditions(setup));
+		updater.set_spatial_stencil(setup.spatial_stencil);
+
+		let positions = [setup.reflection_plane_position, setup.transmission_plane_position];
+		let cell_count = (setup.field_size[0] * setup.field_size[2] * 2) as usize;
+		let zero_sample = DftFieldSample {
+			electric: [[0.0; 2]; 3],
+			magnetic: [[0.0; 2]; 3],
+		};
+		let mut samples = positions.map(|_| vec![zero_sample; cell_count * setup.frequencies.len()]);
+
+		let mut time = 0.0;
+		for _i in 0 .. setup.num_steps
+		{
+			updater.update(&mut field, setup.time_step);
+			time += setup.time_step;
+
+			for (position, plane_samples) in positions.iter().zip(samples.iter_mut())
+			{
+				let params = get_plane_monitor_params(setup, *position);
+				for (frequency_index, frequency) in setup.frequencies.iter().enumerate()
+				{
+					let angular_frequency = frequency * (2.0 * std::f32::consts::PI);
+					let electric_phase = angular_frequency * time;
+					let magnetic_phase = angular_frequency * (time + 0.5 * setup.time_step);
+					for (region_address, sample) in plane_samples
+						[frequency_index * cell_count .. (frequency_index + 1) * cell_count]
+						.iter_mut()
+						.enumerate()
+					{
+						let region_address = region_address as u32;
+						let size = params.region_size;
+						let coord = [
+							region_address % size[0] + params.region_offset[0],
+							region_address / size[0] % size[1] + params.region_offset[1],
+							region_address / (size[0] * size[1]) + params.region_offset[2],
+						];
+						let field_address = (coord[0] +
+							coord[1] * setup.field_size[0] +
+							coord[2] * (setup.field_size[0] * setup.field_size[1])) as usize;
+						for i in 0 .. 3
+						{
+							let e = field.electric_field[field_address][i] * setup.time_step;
+							let h = field.magnetic_field[field_address][i] * setup.time_step;
+							sample.electric[i][0] += e * electric_phase.cos();
+							sample.electric[i][1] -= e * electric_phase.sin();
+							sample.magnetic[i][0] += h * magnetic_phase.cos();
+							sample.magnetic[i][1] -= h * magnetic_phase.sin();
+						}
+					}
+				}
+			}
+		}
+
+		let [reflection, transmission] = samples;
+		PlaneSamples {
+			reflection,
+			transmission,
+		}
+	}
+
+	#[test]
+	fn transmission_reflection_energy_balance()
+	{
+		let setup = create_test_bragg_mirror_setup(SpatialStencil::SecondOrder);
+		let reference_samples = run_cpu_simulation(&setup, &setup.device_medium.make_vacuum_copy());
+		// Ratios are checked only at frequencies, where incident pulse has significant power.
+		let incident_flux = create_flux_plane(&setup, setup.reflection_plane_position)
+			.calculate_flux_spectrum(&reference_samples.reflection, setup.frequencies.len());
+		let max_incident_flux = incident_flux.iter().copied().fold(0.0, f32::max);
+		let is_significant = |index: usize| incident_flux[index] > 0.1 * max_incident_flux;
+
+		// Lossless mirror only redistributes incident power between transmitted and reflected waves.
+		let device_samples = run_cpu_simulation(&setup, &setup.device_medium);
+		let spectra = calculate_transmission_reflection_spectra(&setup, &reference_samples, &device_samples);
+		for (index, frequency) in spectra.frequencies.iter().enumerate().filter(|(index, _)| is_significant(*index))
+		{
+			let total = spectra.transmission[index] + spectra.reflection[index];
+			assert!((total - 1.0).abs() < 0.01, "T + R = {} at frequency {}", total, frequency);
+		}
+		// Stop band of mirror is near its central frequency.
+		assert!(spectra.reflection.iter().copied().fold(0.0, f32::max) > 0.99);
+
+		// Everything is transmitted through vacuum.
+		let spectra = calculate_transmission_reflection_spectra(&setup, &reference_samples, &reference_samples);
+		for (index, frequency) in spectra.frequencies.iter().enumerate().filter(|(index, _)| is_significant(*index))
+		{
+			let (transmission, reflection) = (spectra.transmission[index], spectra.reflection[index]);
+			assert!((transmission - 1.0).abs() < 1.0e-3, "T = {} at frequency {}", transmission, frequency);
+			assert!(reflection.abs() < 1.0e-3, "R = {} at frequency {}", reflection, frequency);
+		}
+	}
+}
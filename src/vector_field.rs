@@ -41,6 +41,7 @@ impl VectorField
 		&self.buffer
 	}
 
+	#[allow(dead_code)]
 	pub fn get_buffer_mut(&mut self) -> &mut BufferType
 	{
 		&mut self.buffer
@@ -1,4 +1,4 @@
-use super::{math_types::*, ogl_common::*, vector_field};
+use super::{grid_spacing::*, math_types::*, medium::*, ogl_common::*, vector_field};
 
 pub struct VectorFieldVisualizer
 {
@@ -9,23 +9,39 @@ impl VectorFieldVisualizer
 {
 	pub fn new(display: &glium::Display) -> Self
 	{
-		let program = glium::Program::from_source(display, VERTEX_SHADER, FRAGMENT_SHADER, None).unwrap();
+		let program = glium::Program::from_source(
+			display,
+			&[VERTEX_SHADER_HEADER, SHADER_GRID_SPACING, VERTEX_SHADER].concat(),
+			FRAGMENT_SHADER,
+			None,
+		)
+		.unwrap();
 		Self { program }
 	}
 
+	// Draw arrows in centers of cells of given field, which covers region of medium grid with given offset.
 	pub fn visualize<S: glium::Surface>(
 		&self,
 		surface: &mut S,
 		vector_field: &vector_field::VectorField,
+		region_offset: [u32; 3],
+		medium_buffers: &MediumBuffers,
 		view_matrix: &Mat4f,
 		base_color: [f32; 3],
 	)
 	{
-		let field_size = vector_field.get_size();
+		let region_size = vector_field.get_size();
+		for i in 0 .. 3
+		{
+			assert!(region_offset[i] + region_size[i] <= medium_buffers.get_size()[i]);
+		}
 
 		let uniforms = glium::uniform! {
 			view_matrix: make_uniform_matrix(view_matrix),
-			field_size: field_size,
+			field_size: medium_buffers.get_size(),
+			region_offset: region_offset,
+			region_size: region_size,
+			grid_spacing_data: medium_buffers.get_grid_spacing_buffer(),
 			field_data: vector_field.get_buffer(),
 			base_color: base_color,
 		};
@@ -33,7 +49,7 @@ impl VectorFieldVisualizer
 		surface
 			.draw(
 				glium::vertex::EmptyVertexAttributes {
-					len: (field_size[0] * field_size[1] * field_size[2] * 2) as usize,
+					len: (region_size[0] * region_size[1] * region_size[2] * 2) as usize,
 				},
 				glium::index::IndicesSource::NoIndices {
 					primitives: glium::index::PrimitiveType::LinesList,
@@ -46,10 +62,15 @@ impl VectorFieldVisualizer
 	}
 }
 
-const VERTEX_SHADER: &str = r#"
+const VERTEX_SHADER_HEADER: &str = r#"
 	#version 430
 
 	uniform uvec3 field_size;
+"#;
+
+const VERTEX_SHADER: &str = r#"
+	uniform uvec3 region_offset;
+	uniform uvec3 region_size;
 	uniform mat4 view_matrix;
 	uniform vec3 base_color;
 
@@ -63,20 +84,24 @@ const VERTEX_SHADER: &str = r#"
 	void main()
 	{
 		uint cell_id = uint(gl_VertexID) / 2;
-		uint layer_size = field_size.y * field_size.x;
+		uint layer_size = region_size.y * region_size.x;
 		uint z = cell_id / layer_size;
 		uint z_id = cell_id - z * layer_size;
-		uint y = z_id / field_size.x;
-		uint x = z_id % field_size.x;
+		uint y = z_id / region_size.x;
+		uint x = z_id % region_size.x;
 
-		vec3 position = vec3( uvec3(x, y, z) ) + vec3(0.5, 0.5, 0.5);
+		ivec3 coord = ivec3(uvec3(x, y, z) + region_offset);
+		vec3 position = get_cell_position(coord);
+		// Scale arrows in small cells in order to avoid overlapping.
+		vec3 cell_size = get_cell_size(coord);
+		float arrow_scale = min(cell_size.x, min(cell_size.y, cell_size.z));
 
 		float arrow_tip_factor = float(gl_VertexID & 1);
 		vec3 vec = vecs[ cell_id ].xyz;
 		float vec_len = length(vec);
 		vec3 vec_clamped = vec * ( min(vec_len, 1.5) / max(vec_len, 0.0000001) );
 
-		position += vec_clamped * arrow_tip_factor;
+		position += vec_clamped * (arrow_tip_factor * arrow_scale);
 
 		gl_Position = vec4(position, 1.0) * view_matrix;
 		f_color = base_color * (0.02 + (1.0 - arrow_tip_factor) * vec_len);
@@ -0,0 +1,160 @@
+// Temporal waveform of a source, defined by uniformly sampled values.
+// Values outside sampled range are zero.
+#[derive(Clone)]
+pub struct Waveform
+{
+	time_step: f32,
+	samples: Vec<f32>,
+	// Parameters of modulated Gaussian pulse, if waveform is created from it. Used to get spectrum analytically.
+	pulse: Option<ModulatedGaussianPulse>,
+}
+
+#[derive(Clone, Copy)]
+struct ModulatedGaussianPulse
+{
+	frequency: f32,
+	width: f32,
+}
+
+impl Waveform
+{
+	pub fn from_samples(time_step: f32, samples: Vec<f32>) -> Self
+	{
+		assert!(time_step > 0.0);
+		Self {
+			time_step,
+			samples,
+			pulse: None,
+		}
+	}
+
+	// Create waveform from given function on interval [0; duration].
+	pub fn from_function<F: Fn(f32) -> f32>(duration: f32, time_step: f32, f: F) -> Self
+	{
+		let num_samples = (duration / time_step).ceil() as usize + 1;
+		Self::from_samples(
+			time_step,
+			(0 .. num_samples).map(|i| f((i as f32) * time_step)).collect(),
+		)
+	}
+
+	// Gaussian-modulated sine pulse with given carrier frequency and envelope standard deviation.
+	pub fn modulated_gaussian_pulse(frequency: f32, width: f32) -> Self
+	{
+		let delay = PULSE_DELAY_WIDTHS * width;
+		let time_step = (width / SAMPLES_PER_WIDTH).min(1.0 / (frequency * SAMPLES_PER_PERIOD));
+		let waveform = Self::from_function(2.0 * delay, time_step, |t| {
+			let dt = t - delay;
+			let envelope = (-0.5 * dt * dt / (width * width)).exp();
+			envelope * (dt * frequency * (2.0 * std::f32::consts::PI)).sin()
+		});
+		Self {
+			pulse: Some(ModulatedGaussianPulse { frequency, width }),
+			..waveform
+		}
+	}
+
+	pub fn get_time_step(&self) -> f32
+	{
+		self.time_step
+	}
+
+	pub fn get_samples(&self) -> &[f32]
+	{
+		&self.samples
+	}
+
+	// Get highest frequency, at which magnitude of spectrum is not less than given fraction of its maximum.
+	pub fn get_max_frequency(&self, relative_magnitude: f32) -> f32
+	{
+		if self.samples.is_empty()
+		{
+			return 0.0;
+		}
+
+		let nyquist_frequency = 0.5 / self.time_step;
+		if let Some(pulse) = self.pulse
+		{
+			// Spectrum magnitude of pulse is proportional to "g(f - f0) - g(f + f0)", where
+			// "g(f) = exp(-2 * (pi * width * f)^2)". Its maximum is near carrier frequency "f0", with value not less than
+			// "1 - g(2 * f0)". Above carrier frequency second term only makes magnitude smaller, so ignoring it gives
+			// slightly overestimated frequency. Truncation of pulse is neglected.
+			let angular_width = 2.0 * std::f32::consts::PI * pulse.width;
+			let max_magnitude = 1.0 - (-0.5 * (2.0 * angular_width * pulse.frequency).powi(2)).exp();
+			let threshold = (relative_magnitude * max_magnitude).min(1.0);
+			let frequency = pulse.frequency + (-2.0 * threshold.ln()).sqrt() / angular_width;
+			return frequency.min(nyquist_frequency);
+		}
+
+		// Arbitrary waveform: sample spectrum up to Nyquist frequency with resolution finer than inverse of duration.
+		let duration = self.time_step * (self.samples.len() as f32);
+		let frequency_step = 0.25 / duration;
+		let num_frequencies = (nyquist_frequency / frequency_step) as usize + 1;
+		let magnitudes = (0 .. num_frequencies)
+			.map(|i| {
+				let angular_frequency = 2.0 * std::f64::consts::PI * (i as f64) * (frequency_step as f64);
+				let (re, im) = self.samples.iter().enumerate().fold((0.0, 0.0), |(re, im), (j, v)| {
+					let phase = angular_frequency * (j as f64) * (self.time_step as f64);
+					(re + (*v as f64) * phase.cos(), im - (*v as f64) * phase.sin())
+				});
+				(re * re + im * im).sqrt()
+			})
+			.collect::<Vec<_>>();
+		let max_magnitude = magnitudes.iter().copied().fold(0.0, f64::max);
+		magnitudes
+			.iter()
+			.rposition(|m| *m >= max_magnitude * (relative_magnitude as f64))
+			.map_or(0.0, |i| (i as f32) * frequency_step)
+	}
+
+	// Get linearly interpolated value at given time.
+	pub fn get_value(&self, time: f32) -> f32
+	{
+		let position = time / self.time_step;
+		if self.samples.is_empty() || position < 0.0 || position > (self.samples.len() - 1) as f32
+		{
+			return 0.0;
+		}
+		let index = (position as usize).min(self.samples.len().max(2) - 2);
+		let k = position - index as f32;
+		let next_value = self.samples[(index + 1).min(self.samples.len() - 1)];
+		self.samples[index] * (1.0 - k) + next_value * k
+	}
+}
+
+const PULSE_DELAY_WIDTHS: f32 = 4.0;
+const SAMPLES_PER_WIDTH: f32 = 16.0;
+const SAMPLES_PER_PERIOD: f32 = 32.0;
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn pulse_max_frequency()
+	{
+		// Analytic bandwidth of pulse should match one, calculated from spectrum of its samples.
+		for (frequency, width) in [(0.04, 8.0), (0.035, 12.0), (1.0 / 12.0, 6.0), (0.03, 8.0)]
+		{
+			let pulse = Waveform::modulated_gaussian_pulse(frequency, width);
+			let sampled = Waveform::from_samples(pulse.get_time_step(), pulse.get_samples().to_vec());
+			for relative_magnitude in [0.5, 0.1, 0.01]
+			{
+				let analytic_frequency = pulse.get_max_frequency(relative_magnitude);
+				let sampled_frequency = sampled.get_max_frequency(relative_magnitude);
+				// Resolution of sampled spectrum is quarter of inverse of pulse duration.
+				let frequency_step = 0.25 / (pulse.get_time_step() * (pulse.get_samples().len() as f32));
+				assert!(
+					(analytic_frequency - sampled_frequency).abs() <= frequency_step,
+					"frequency {}, width {}, relative magnitude {}: analytic {}, sampled {}",
+					frequency,
+					width,
+					relative_magnitude,
+					analytic_frequency,
+					sampled_frequency
+				);
+			}
+		}
+	}
+}